@@ -1,10 +1,17 @@
+use crate::chat::send_message::{ChatSenders, YoutubeSender};
+use crate::chat::twitch::eventsub::client::connect_eventsub;
 use crate::chat::twitch::irc::connect_twitch_websocket;
-use crate::chat::websocket::start_ws::initialize_websocket_server;
+use crate::chat::websocket::start_ws::{current_server, initialize_websocket_server, ChatServer};
+use crate::chat::websocket::ws_server::WebSocketServer;
 use crate::chat::youtube::polling::youtube_polling_cmd;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::task::JoinHandle;
+
+// How long (in steps of 100ms) united_chat_stop waits for the chat to stop
+const STOP_TIMEOUT_STEPS: u32 = 100;
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct UnitedChat {
@@ -12,6 +19,46 @@ pub(crate) struct UnitedChat {
     pub(crate) stop_flag: Arc<AtomicBool>,
 }
 
+impl UnitedChat {
+    /// Marks the chat as started, false when it already was
+    fn begin(&self) -> bool {
+        !std::mem::replace(&mut *self.websocket_started.lock().unwrap(), true)
+    }
+
+    /// Lets the chat be started again, once everything from the session is gone
+    fn end(&self) {
+        self.stop_flag.store(false, Ordering::Relaxed);
+        *self.websocket_started.lock().unwrap() = false;
+    }
+
+    fn is_started(&self) -> bool {
+        *self.websocket_started.lock().unwrap()
+    }
+
+    /// Resolves once the chat is asked to stop
+    async fn stopping(&self) {
+        while !self.stop_flag.load(Ordering::Relaxed) {
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Asks the session to stop and returns once it's over, false if it took too long
+    async fn stop(&self) -> bool {
+        if !self.is_started() {
+            return true;
+        }
+
+        self.stop_flag.store(true, Ordering::Relaxed);
+        for _ in 0..STOP_TIMEOUT_STEPS {
+            if !self.is_started() {
+                return true;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        }
+        false
+    }
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct YoutubeInfo {
     pub(crate) yt_id: String,
@@ -19,25 +66,38 @@ pub(crate) struct YoutubeInfo {
 }
 
 
+/// Starts the chat and returns the port its server is listening on, the chat keeps running in the background
+/// until `united_chat_stop` is called.
 #[tauri::command]
 pub(crate) async fn united_chat_init(
     app: AppHandle,  // app is not `Clone` by default
     youtube: Option<YoutubeInfo>,
-) {
-    let ws_started = Arc::clone(&app.state::<UnitedChat>().websocket_started);
+) -> Result<u16, String> {
+    let chat = app.state::<UnitedChat>().inner().clone();
+
+    if !chat.begin() {
+        return match current_server(&app) {
+            Some(ws_server) => ws_server.local_addr().await.map(|addr| addr.port()).ok_or_else(|| "The chat is still starting".to_string()),
+            None => Err("The chat is still starting".to_string()),
+        };
+    }
 
-    {
-        let mut started = ws_started.lock().unwrap();
-        if *started {
-            return;
+    let ws_server = match initialize_websocket_server(app.clone()).await {
+        Ok(ws_server) => ws_server,
+        Err(e) => {
+            chat.end();
+            return Err(e);
         }
-        *started = true;
-    }
+    };
+    let port = ws_server.local_addr().await.map(|addr| addr.port()).unwrap_or_default();
 
-    let stop_flag = Arc::clone(&app.state::<UnitedChat>().stop_flag);
+    tauri::async_runtime::spawn(run_chat_session(app, youtube, ws_server, chat));
+    Ok(port)
+}
 
-    // Start the WebSocket server
-    let ws_server = initialize_websocket_server(app.clone()).await;
+/// Runs every chat client of a session until the chat is stopped.
+async fn run_chat_session(app: AppHandle, youtube: Option<YoutubeInfo>, ws_server: Arc<WebSocketServer>, chat: UnitedChat) {
+    let stop_flag = Arc::clone(&chat.stop_flag);
 
     // Clone the `stop_flag` and `ws_server` before moving them into async blocks
     let stop_flag_twitch = Arc::clone(&stop_flag);
@@ -51,6 +111,14 @@ pub(crate) async fn united_chat_init(
         connect_twitch_websocket(app_twitch, stop_flag_twitch, ws_server_twitch).await;
     });
 
+    // EventSub runs on its own, the chat doesn't depend on it and it quits by itself when there's no linked account
+    let stop_flag_eventsub = Arc::clone(&stop_flag);
+    let ws_server_eventsub = ws_server.clone();
+    let app_eventsub = app.clone();
+    let eventsub_handle = tokio::spawn(async move {
+        connect_eventsub(app_eventsub, stop_flag_eventsub, ws_server_eventsub).await;
+    });
+
     // Clone again for the YouTube task
     let stop_flag_youtube = Arc::clone(&stop_flag);
     let ws_server_youtube = ws_server.clone();
@@ -60,6 +128,9 @@ pub(crate) async fn united_chat_init(
         Some(yt_info) => {
             let youtube_handle = if !yt_info.yt_id.is_empty() {
                 println!("Starting YouTube live chat client");
+                let app_youtube = app.clone();
+                *app.state::<ChatSenders>().youtube.lock().unwrap() = Some(YoutubeSender::new(yt_info.yt_id.clone()));
+
                 Some(tokio::spawn(async move {
                     youtube_polling_cmd(
                         yt_info.interval,
@@ -67,6 +138,8 @@ pub(crate) async fn united_chat_init(
                         stop_flag_youtube,
                         ws_server_youtube,
                     ).await;
+
+                    *app_youtube.state::<ChatSenders>().youtube.lock().unwrap() = None;
                 }))
             } else {
                 None
@@ -77,41 +150,64 @@ pub(crate) async fn united_chat_init(
         None => None,
     };
 
-    // Use tokio::spawn to monitor the stop_flag in the background
-    tokio::spawn(async move {
-        while !stop_flag.load(Ordering::Relaxed) {
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-        }
-
-        // If stop_flag is set, initiate the shutdown signal for the WebSocket server
-        ws_server.close().await;
+    let clients = [Some(twitch_handle), Some(eventsub_handle), youtube_handle].into_iter().flatten().collect();
+    wait_for_session(&chat, app.state::<ChatServer>().inner(), clients).await;
+    // The overlay windows close with the server
+    app.emit("chat::server_stopped", ())
+        .unwrap_or_else(|e| eprintln!("Failed to emit server stop: {}", e));
+}
 
-        // Wait for another two seconds to reset the flag
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-        println!("Resetting stop flag");
-        stop_flag.store(false, Ordering::Relaxed);
-    });
+/// Waits for the chat to be stopped, then for its server and clients to be gone.
+async fn wait_for_session(chat: &UnitedChat, chat_server: &ChatServer, clients: Vec<JoinHandle<()>>) {
+    chat.stopping().await;
 
-    // Wait for the spawned tasks to complete using join
-    if let Some(youtube_task) = youtube_handle {
-        let _ = tokio::join!(twitch_handle, youtube_task);
-    } else {
-        let _ = twitch_handle.await;
+    // Shut the WebSocket server down and wait for its port to be free
+    chat_server.stop().await;
+    for client in clients {
+        let _ = client.await;
     }
+
+    // Everything from this session is gone, the chat can be started again
+    println!("Resetting stop flag");
+    chat.end();
 }
 
 #[tauri::command]
 pub(crate) async fn united_chat_stop(app: AppHandle) {
-    let ws_started = Arc::clone(&app.state::<UnitedChat>().websocket_started);
+    // Returns once the session is over, so starting the chat right after works
+    if !app.state::<UnitedChat>().stop().await {
+        eprintln!("The chat is taking too long to stop");
+    }
+}
 
-    {
-        let mut started = ws_started.lock().unwrap();
-        if !*started {
-            return;
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chat::websocket::config::WebSocketConfig;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn starts_again_after_being_stopped() {
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let config = WebSocketConfig { port, port_fallback_attempts: 0, ..WebSocketConfig::default() };
+        let chat = UnitedChat::default();
+        let chat_server = Arc::new(ChatServer::default());
+
+        for _ in 0..3 {
+            assert!(chat.begin());
+            let (ws_server, _) = chat_server.start(config.clone()).await.unwrap();
+            assert_eq!(ws_server.local_addr().await.map(|addr| addr.port()), Some(port));
+            // Starting twice doesn't run a second session
+            assert!(!chat.begin());
+
+            let session_chat = chat.clone();
+            let session_server = Arc::clone(&chat_server);
+            let session = tokio::spawn(async move { wait_for_session(&session_chat, &session_server, Vec::new()).await });
+
+            assert!(chat.stop().await);
+            session.await.unwrap();
+            // The port is free as soon as stopping returns
+            drop(TcpListener::bind(("127.0.0.1", port)).await.unwrap());
         }
-        *started = false;
     }
-
-    let stop_flag = Arc::clone(&app.state::<UnitedChat>().stop_flag);
-    stop_flag.store(true, Ordering::Relaxed);
 }
\ No newline at end of file
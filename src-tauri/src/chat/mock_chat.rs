@@ -0,0 +1,546 @@
+use crate::chat::twitch::helpers::auth_helpers::escape_tag_value;
+use crate::chat::twitch::helpers::message_processor::{parse_privmsg, TwitchResponse};
+use crate::chat::twitch::user_notices::parse_user_notice;
+use crate::chat::websocket::protocol::Envelope;
+use crate::chat::websocket::start_ws::current_server;
+use crate::chat::youtube::structs::youtube_response::YoutubeResponse;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Channel (and YouTube video) the mock events come from
+pub(crate) const MOCK_CHANNEL: &str = "mock";
+// Past this the overlays are the bottleneck, not the generator
+const MAX_RATE: f64 = 1000.0;
+// Faster rates send a few events per tick instead of sleeping for less
+const MIN_TICK: Duration = Duration::from_millis(10);
+// Messages that can still be deleted
+const DELETABLE_MESSAGES: usize = 50;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum MockEvent {
+    Message,
+    LongMessage,
+    Emotes,
+    /// Right-to-left text, Arabic and Hebrew
+    Rtl,
+    /// Twitch's /me
+    Action,
+    Highlight,
+    Subscription,
+    Raid,
+    /// YouTube's paid messages
+    SuperChat,
+    /// Removes one of the messages sent before
+    Deletion,
+}
+
+/// Kinds of mock events and how often they come up, relative to each other.
+const MOCK_EVENTS: &[(MockEvent, u32)] = &[
+    (MockEvent::Message, 40),
+    (MockEvent::Emotes, 15),
+    (MockEvent::LongMessage, 8),
+    (MockEvent::Rtl, 6),
+    (MockEvent::Action, 4),
+    (MockEvent::Highlight, 3),
+    (MockEvent::Subscription, 4),
+    (MockEvent::Raid, 1),
+    (MockEvent::SuperChat, 2),
+    (MockEvent::Deletion, 3),
+];
+
+impl MockEvent {
+    fn platforms(self) -> &'static [&'static str] {
+        match self {
+            MockEvent::Action | MockEvent::Highlight | MockEvent::Subscription | MockEvent::Raid | MockEvent::Deletion => {
+                &["twitch"]
+            }
+            MockEvent::SuperChat => &["youtube"],
+            _ => &["twitch", "youtube"],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub(crate) struct MockChatOptions {
+    /// Events per second
+    pub(crate) rate: f64,
+    /// "twitch" and/or "youtube"
+    pub(crate) platforms: Vec<String>,
+    /// Kinds of events to send, all of them when empty
+    pub(crate) events: Vec<MockEvent>,
+    /// Stops after this many events, for load tests
+    pub(crate) limit: Option<u64>,
+    /// Same seed, same events
+    pub(crate) seed: Option<u64>,
+}
+
+impl Default for MockChatOptions {
+    fn default() -> Self {
+        Self {
+            rate: 2.0,
+            platforms: vec!["twitch".to_string(), "youtube".to_string()],
+            events: Vec::new(),
+            limit: None,
+            seed: None,
+        }
+    }
+}
+
+impl MockChatOptions {
+    fn validate(&self) -> Result<(), String> {
+        if !(self.rate > 0.0 && self.rate <= MAX_RATE) {
+            return Err(format!("The rate has to be between 0 and {} events per second", MAX_RATE));
+        }
+        if let Some(platform) = self.platforms.iter().find(|platform| !["twitch", "youtube"].contains(&platform.as_str())) {
+            return Err(format!("Unknown platform: {}", platform));
+        }
+        if self.allowed_events().is_empty() {
+            return Err("None of the events can be sent on the chosen platforms".into());
+        }
+        Ok(())
+    }
+
+    fn allowed_events(&self) -> Vec<(MockEvent, u32)> {
+        MOCK_EVENTS
+            .iter()
+            .filter(|(event, _)| self.events.is_empty() || self.events.contains(event))
+            .filter(|(event, _)| event.platforms().iter().any(|platform| self.platforms.iter().any(|p| p == platform)))
+            .copied()
+            .collect()
+    }
+}
+
+struct MockUser {
+    name: &'static str,
+    color: &'static str,
+    badges: &'static [&'static str],
+    /// YouTube only sends images for the member badges, the others are icons
+    youtube_badges: &'static [&'static str],
+}
+
+const BROADCASTER: &str = "https://static-cdn.jtvnw.net/badges/v1/5527c58c-fb7d-422d-b71b-f309dcb85cc1/3";
+const MODERATOR: &str = "https://static-cdn.jtvnw.net/badges/v1/3267646d-33f0-4b17-b3df-f923a41db1d0/3";
+const VIP: &str = "https://static-cdn.jtvnw.net/badges/v1/b817aba4-fad8-49e2-b88a-7cc744dfa6ec/3";
+const PRIME: &str = "https://static-cdn.jtvnw.net/badges/v1/bbbe0db0-a598-423e-86d0-f9fb98ca1933/3";
+// Member badges are each channel's own images, these stand in for them
+const NEW_MEMBER: &str = "data:image/svg+xml,%3Csvg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 16 16'%3E%3Ccircle cx='8' cy='8' r='7' fill='%232BA640'/%3E%3C/svg%3E";
+const YEAR_MEMBER: &str = "data:image/svg+xml,%3Csvg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 16 16'%3E%3Ccircle cx='8' cy='8' r='7' fill='%231E88E5'/%3E%3C/svg%3E";
+
+const USERS: &[MockUser] = &[
+    MockUser { name: "StreamerSan", color: "#9146FF", badges: &[BROADCASTER], youtube_badges: &[] },
+    MockUser { name: "ModeratorMika", color: "#00AD03", badges: &[MODERATOR, PRIME], youtube_badges: &[YEAR_MEMBER] },
+    MockUser { name: "vip_valentina", color: "#FF69B4", badges: &[VIP], youtube_badges: &[NEW_MEMBER] },
+    MockUser { name: "lurker42", color: "", badges: &[], youtube_badges: &[] },
+    MockUser { name: "TheQuickBrownFoxJumpsOverTheLazyDog", color: "#1E90FF", badges: &[PRIME], youtube_badges: &[YEAR_MEMBER] },
+    MockUser { name: "さくら", color: "#FF7F50", badges: &[], youtube_badges: &[NEW_MEMBER] },
+    MockUser { name: "Ünïcødé_Üser", color: "#DAA520", badges: &[MODERATOR], youtube_badges: &[] },
+    MockUser { name: "night_owl", color: "#5F9EA0", badges: &[], youtube_badges: &[] },
+];
+
+/// (Currency, symbol) of the super chats, amounts are the same in all of them
+const CURRENCIES: &[(&str, &str)] = &[("USD", "$"), ("EUR", "€"), ("GBP", "£")];
+/// (Amount, tier) of YouTube's super chat colors, from blue to red
+const SUPER_CHAT_TIERS: &[(u32, u8)] = &[(1, 1), (2, 2), (5, 3), (10, 4), (20, 5), (50, 6), (100, 7)];
+const SUPER_CHAT_AMOUNTS: &[u32] = &[1, 2, 5, 10, 20, 50, 100, 500];
+
+const MESSAGES: &[&str] = &[
+    "hello chat!",
+    "first time here, love the overlay",
+    "gg",
+    "how long have you been streaming today?",
+    "that was so close",
+    "can you play that song again?",
+    "LETS GOOOO",
+    "brb getting snacks",
+    "what keyboard is that?",
+    "こんにちは！",
+    "🔥🔥🔥",
+    "is this the new theme? looks great",
+];
+
+const LONG_MESSAGE: &str = "okay so I have been thinking about this for a while and I really need to say it, \
+the way you handled that last fight was honestly one of the best things I have seen on this channel, \
+the timing, the positioning, waiting for the cooldowns and then going all in right when they split up, \
+I went back and watched the clip three times and it still holds up, anyway keep it up, \
+this is exactly why I keep coming back to the stream every single week";
+
+const RTL_MESSAGES: &[&str] = &[
+    "مرحبا بالجميع! كيف حالكم اليوم؟",
+    "שלום לכולם, סטרים מעולה",
+    "هذا رائع جدا 👏",
+];
+
+const ACTIONS: &[&str] = &["waves at everyone", "throws confetti", "grabs popcorn"];
+
+/// Twitch global emotes, as (name, id)
+const EMOTES: &[(&str, &str)] = &[
+    ("Kappa", "25"),
+    ("LUL", "425618"),
+    ("HeyGuys", "30259"),
+    ("BibleThump", "86"),
+    ("SeemsGood", "64138"),
+];
+
+/// Makes up chat events that look like the ones the platforms send.
+pub(crate) struct MockChatGenerator {
+    rng: StdRng,
+    options: MockChatOptions,
+    events: Vec<(MockEvent, u32)>,
+    sent: u64,
+    /// Ids of the Twitch messages sent, the deletions pick from them
+    messages: VecDeque<String>,
+}
+
+impl MockChatGenerator {
+    pub(crate) fn new(options: MockChatOptions) -> Self {
+        let rng = match options.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let events = options.allowed_events();
+        Self { rng, options, events, sent: 0, messages: VecDeque::new() }
+    }
+
+    fn pick_event(&mut self) -> MockEvent {
+        let total: u32 = self.events.iter().map(|(_, weight)| weight).sum();
+        let mut roll = self.rng.gen_range(0..total);
+        for (event, weight) in &self.events {
+            if roll < *weight {
+                return *event;
+            }
+            roll -= weight;
+        }
+        self.events[0].0
+    }
+
+    fn pick_platform(&mut self, event: MockEvent) -> &'static str {
+        let platforms: Vec<&'static str> = event
+            .platforms()
+            .iter()
+            .copied()
+            .filter(|platform| self.options.platforms.iter().any(|p| p == platform))
+            .collect();
+        platforms.choose(&mut self.rng).copied().unwrap_or("twitch")
+    }
+
+    fn next_id(&mut self) -> String {
+        self.sent += 1;
+        format!("mock-{}", self.sent)
+    }
+
+    pub(crate) fn next_event(&mut self) -> Envelope {
+        let mut event = self.pick_event();
+        // Nothing to delete yet
+        if event == MockEvent::Deletion && self.messages.is_empty() {
+            event = MockEvent::Message;
+        }
+        let platform = self.pick_platform(event);
+        let user = USERS.choose(&mut self.rng).unwrap();
+
+        match event {
+            MockEvent::Deletion => {
+                let index = self.rng.gen_range(0..self.messages.len());
+                let id = self.messages.remove(index).unwrap();
+                Envelope::new("twitch", Some(MOCK_CHANNEL), "message_deleted", json!({ "id": id }))
+            }
+            MockEvent::Subscription => {
+                let months = self.rng.gen_range(1..=48);
+                let (msg_id, message) = if months > 1 { ("resub", " :happy to be here") } else { ("sub", "") };
+                let system_message = format!("{} subscribed at Tier 1. They've subscribed for {} months!", user.name, months);
+                let params = format!(
+                    "msg-id={};msg-param-cumulative-months={};msg-param-sub-plan=1000;system-msg={}",
+                    msg_id,
+                    months,
+                    escape_tag_value(&system_message)
+                );
+                self.user_notice(user, &params, message)
+            }
+            MockEvent::Raid => {
+                let viewers = self.rng.gen_range(2..=2500);
+                let params = format!("msg-id=raid;msg-param-displayName={};msg-param-viewerCount={}", user.name, viewers);
+                self.user_notice(user, &params, "")
+            }
+            MockEvent::SuperChat => {
+                let amount = *SUPER_CHAT_AMOUNTS.choose(&mut self.rng).unwrap();
+                let (currency, symbol) = *CURRENCIES.choose(&mut self.rng).unwrap();
+                let tier = SUPER_CHAT_TIERS.iter().rev().find(|(min, _)| amount >= *min).map(|(_, tier)| *tier).unwrap_or(1);
+                // Some are sent without a message
+                let text = if self.rng.gen_bool(0.2) { "" } else { MESSAGES.choose(&mut self.rng).unwrap() };
+
+                let mut message = self.youtube_message(user, text);
+                message.amount = Some(format!("{}{}.00", symbol, amount));
+                let mut data = json!(message);
+                data["currency"] = json!(currency);
+                data["tier"] = json!(tier);
+                Envelope::new("youtube", Some(MOCK_CHANNEL), message.event(), data)
+            }
+            _ => {
+                let text = match event {
+                    MockEvent::LongMessage => LONG_MESSAGE.to_string(),
+                    MockEvent::Rtl => RTL_MESSAGES.choose(&mut self.rng).unwrap().to_string(),
+                    MockEvent::Action => ACTIONS.choose(&mut self.rng).unwrap().to_string(),
+                    MockEvent::Emotes => {
+                        let (first, _) = EMOTES.choose(&mut self.rng).unwrap();
+                        let (second, _) = EMOTES.choose(&mut self.rng).unwrap();
+                        format!("{} that was amazing {} {}", first, second, second)
+                    }
+                    _ => MESSAGES.choose(&mut self.rng).unwrap().to_string(),
+                };
+                if platform == "youtube" {
+                    let message = self.youtube_message(user, &text);
+                    return Envelope::new("youtube", Some(MOCK_CHANNEL), "message", json!(message));
+                }
+                let message = self.twitch_message(user, &text, event);
+                Envelope::new("twitch", Some(MOCK_CHANNEL), "message", json!(message))
+            }
+        }
+    }
+
+    /// Same as a message sent on Twitch, built from the IRC line it would arrive as.
+    fn twitch_message(&mut self, user: &MockUser, text: &str, event: MockEvent) -> TwitchResponse {
+        let id = self.next_id();
+        self.messages.push_back(id.clone());
+        if self.messages.len() > DELETABLE_MESSAGES {
+            self.messages.pop_front();
+        }
+
+        // Positions of the emotes, in characters, e.g. "25:0-4,12-16/86:6-15"
+        let mut emotes: Vec<(&str, Vec<String>)> = Vec::new();
+        let mut position = 0;
+        for word in text.split(' ') {
+            let length = word.chars().count();
+            if let Some((_, emote_id)) = EMOTES.iter().find(|(name, _)| *name == word) {
+                let range = format!("{}-{}", position, position + length - 1);
+                match emotes.iter_mut().find(|(id, _)| id == emote_id) {
+                    Some((_, ranges)) => ranges.push(range),
+                    None => emotes.push((emote_id, vec![range])),
+                }
+            }
+            position += length + 1;
+        }
+        let emotes: Vec<String> = emotes.iter().map(|(id, ranges)| format!("{}:{}", id, ranges.join(","))).collect();
+
+        let login = user.name.to_lowercase();
+        let msg_id = if event == MockEvent::Highlight { ";msg-id=highlighted-message" } else { "" };
+        let content = match event {
+            MockEvent::Action => format!("\u{1}ACTION {}\u{1}", text),
+            _ => text.to_string(),
+        };
+        let line = format!(
+            "@color={};display-name={};emotes={};id={}{};user-id=mock-{} :{}!{}@{}.tmi.twitch.tv PRIVMSG #{} :{}",
+            user.color, user.name, emotes.join("/"), id, msg_id, login, login, login, login, MOCK_CHANNEL, content
+        );
+
+        let mut message = parse_privmsg(&line).expect("mock lines are valid PRIVMSGs");
+        // message_processor gets them from the API
+        message.user_badges = user.badges.iter().map(|badge| badge.to_string()).collect();
+        message
+    }
+
+    /// Same as a USERNOTICE sent on Twitch, `params` are the tags of its kind and `message` its trailing text.
+    fn user_notice(&mut self, user: &MockUser, params: &str, message: &str) -> Envelope {
+        let login = user.name.to_lowercase();
+        let line = format!(
+            "@color={};display-name={};id={};login={};{} :tmi.twitch.tv USERNOTICE #{}{}",
+            user.color,
+            user.name,
+            self.next_id(),
+            login,
+            params,
+            MOCK_CHANNEL,
+            message
+        );
+        parse_user_notice(&line).expect("mock lines are valid USERNOTICEs")
+    }
+
+    fn youtube_message(&mut self, user: &MockUser, text: &str) -> YoutubeResponse {
+        YoutubeResponse {
+            id: self.next_id(),
+            author_id: format!("mock-{}", user.name.to_lowercase()),
+            author_name: user.name.to_string(),
+            author_badges: user.youtube_badges.iter().map(|badge| badge.to_string()).collect(),
+            message: text.to_string(),
+            message_emotes: Vec::new(),
+            // YouTube's are in microseconds
+            timestamp: (chrono::Local::now().timestamp_millis() * 1000).to_string(),
+            tracking_params: String::new(),
+            amount: None,
+        }
+    }
+}
+
+/// The running mock chat, if any
+#[derive(Default)]
+pub(crate) struct MockChat {
+    running: Mutex<Option<(Arc<AtomicBool>, MockChatOptions)>>,
+}
+
+async fn run_mock_chat(app: AppHandle, options: MockChatOptions, stop_flag: Arc<AtomicBool>) {
+    let mut generator = MockChatGenerator::new(options.clone());
+    let tick = Duration::from_secs_f64(1.0 / options.rate).max(MIN_TICK);
+    let mut interval = tokio::time::interval(tick);
+    let mut owed = 0.0;
+    let mut sent = 0;
+
+    'ticks: while !stop_flag.load(Ordering::Relaxed) {
+        interval.tick().await;
+        owed += options.rate * tick.as_secs_f64();
+
+        // Looked up every tick, the events would go nowhere once the chat is stopped
+        let Some(server) = current_server(&app) else {
+            eprintln!("The chat server stopped, stopping the mock chat");
+            break;
+        };
+        while owed >= 1.0 {
+            owed -= 1.0;
+            let envelope = generator.next_event();
+            // Same as a deletion coming from Twitch
+            if envelope.event == "message_deleted" {
+                let id = envelope.data["id"].clone();
+                server.remove_from_history(|message| message.event == "message" && message.data["id"] == id).await;
+            }
+            server.broadcast(envelope).await;
+
+            sent += 1;
+            if options.limit.is_some_and(|limit| sent >= limit) {
+                break 'ticks;
+            }
+        }
+    }
+
+    // Only forget it if it wasn't replaced by a newer one in the meantime
+    {
+        let state = app.state::<MockChat>();
+        let mut running = state.running.lock().unwrap();
+        if running.as_ref().is_some_and(|(flag, _)| Arc::ptr_eq(flag, &stop_flag)) {
+            *running = None;
+        }
+    }
+    app.emit_to("main", "chat::mock_chat_stopped", json!({ "sent": sent }))
+        .unwrap_or_else(|e| eprintln!("Failed to emit mock chat stop: {}", e));
+}
+
+/// Sends made up events to the overlays through the running chat server, to preview and load test themes offline.
+/// A mock chat that's already running is replaced.
+#[tauri::command]
+pub(crate) async fn start_mock_chat(options: Option<MockChatOptions>, app: AppHandle) -> Result<(), String> {
+    let options = options.unwrap_or_default();
+    options.validate()?;
+    if current_server(&app).is_none() {
+        return Err("The chat server isn't running, start the chat first".into());
+    }
+
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let previous = app.state::<MockChat>().running.lock().unwrap().replace((stop_flag.clone(), options.clone()));
+    if let Some((previous_flag, _)) = previous {
+        previous_flag.store(true, Ordering::Relaxed);
+    }
+
+    tauri::async_runtime::spawn(run_mock_chat(app, options, stop_flag));
+    Ok(())
+}
+
+/// Returns whether a mock chat was running.
+#[tauri::command]
+pub(crate) async fn stop_mock_chat(app: AppHandle) -> bool {
+    match app.state::<MockChat>().running.lock().unwrap().take() {
+        Some((stop_flag, _)) => {
+            stop_flag.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Options of the running mock chat, None when it isn't running.
+#[tauri::command]
+pub(crate) async fn get_mock_chat(app: AppHandle) -> Option<MockChatOptions> {
+    app.state::<MockChat>().running.lock().unwrap().as_ref().map(|(_, options)| options.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::misc::editor::template::template_kind;
+
+    fn generator(events: Vec<MockEvent>, platforms: &[&str]) -> MockChatGenerator {
+        MockChatGenerator::new(MockChatOptions {
+            events,
+            platforms: platforms.iter().map(|platform| platform.to_string()).collect(),
+            seed: Some(7),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn events_look_like_the_platforms_ones() {
+        let envelope = generator(vec![MockEvent::Emotes], &["twitch"]).next_event();
+        assert_eq!((envelope.platform.as_str(), envelope.event.as_str()), ("twitch", "message"));
+        let message: TwitchResponse = serde_json::from_value(envelope.data).unwrap();
+        assert!(!message.emotes.is_empty());
+        assert!(message.message.contains("<img"));
+
+        let envelope = generator(vec![MockEvent::Action], &["twitch"]).next_event();
+        assert_eq!(template_kind(&envelope), Some("action"));
+        assert!(!envelope.data["message"].as_str().unwrap().contains('\u{1}'));
+        let envelope = generator(vec![MockEvent::Subscription], &["twitch"]).next_event();
+        assert_eq!(template_kind(&envelope), Some("subscription"));
+        assert!(envelope.data["system_message"].as_str().unwrap().contains("subscribed at Tier 1"));
+        let envelope = generator(vec![MockEvent::Highlight], &["twitch"]).next_event();
+        assert_eq!(template_kind(&envelope), Some("highlight"));
+
+        let envelope = generator(vec![MockEvent::Message], &["youtube"]).next_event();
+        let message: YoutubeResponse = serde_json::from_value(envelope.data).unwrap();
+        assert!(!message.author_name.is_empty());
+        let mut mock = generator(vec![MockEvent::Message], &["youtube"]);
+        assert!((0..50).any(|_| mock.next_event().data["author_badges"].as_array().is_some_and(|badges| !badges.is_empty())));
+
+        let envelope = generator(vec![MockEvent::SuperChat], &["twitch", "youtube"]).next_event();
+        assert_eq!((envelope.platform.as_str(), envelope.event.as_str()), ("youtube", "super_chat"));
+        assert_eq!(template_kind(&envelope), Some("super_chat"));
+        let tier = envelope.data["tier"].as_u64().unwrap();
+        assert!((1..=7).contains(&tier));
+        let (_, symbol) = CURRENCIES.iter().find(|(currency, _)| envelope.data["currency"] == *currency).unwrap();
+        assert!(envelope.data["amount"].as_str().unwrap().starts_with(symbol));
+    }
+
+    #[test]
+    fn deletions_remove_sent_messages() {
+        let mut mock = generator(vec![MockEvent::Message, MockEvent::Deletion], &["twitch"]);
+        let mut sent = Vec::new();
+        for _ in 0..200 {
+            let envelope = mock.next_event();
+            match envelope.event.as_str() {
+                "message" => sent.push(envelope.data["id"].clone()),
+                "message_deleted" => {
+                    assert!(sent.contains(&envelope.data["id"]));
+                    sent.retain(|id| *id != envelope.data["id"]);
+                }
+                event => panic!("Unexpected event {}", event),
+            }
+        }
+    }
+
+    #[test]
+    fn options_are_checked() {
+        assert!(MockChatOptions::default().validate().is_ok());
+        assert!(MockChatOptions { rate: 0.0, ..Default::default() }.validate().is_err());
+        assert!(MockChatOptions { rate: 5000.0, ..Default::default() }.validate().is_err());
+        // Raids only exist on Twitch
+        let options = MockChatOptions { events: vec![MockEvent::Raid], platforms: vec!["youtube".into()], ..Default::default() };
+        assert!(options.validate().is_err());
+        // And super chats on YouTube
+        let options = MockChatOptions { events: vec![MockEvent::SuperChat], platforms: vec!["twitch".into()], ..Default::default() };
+        assert!(options.validate().is_err());
+    }
+}
@@ -1,6 +1,8 @@
 pub(crate) mod twitch;
-mod websocket;
+pub(crate) mod websocket;
 pub(crate) mod youtube;
+pub(crate) mod send_message;
+pub(crate) mod mock_chat;
 
 #[macro_use]
 pub(crate) mod initialize;
@@ -0,0 +1,324 @@
+use crate::chat::twitch::auth::UserInformation;
+use crate::chat::twitch::helpers::message_processor::parse_privmsg;
+use crate::chat::websocket::protocol::Envelope;
+use crate::chat::websocket::start_ws::current_server;
+use crate::chat::youtube::send_message::{get_live_chat_id, get_youtube_token, send_youtube_message};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tokio::sync::mpsc::UnboundedSender;
+
+// Twitch allows 100 messages every 30 seconds when you're the broadcaster or a moderator of the channel,
+// which is always the case here since we only ever join the linked user's own channel.
+const TWITCH_MESSAGE_LIMIT: usize = 100;
+const TWITCH_MESSAGE_WINDOW: Duration = Duration::from_secs(30);
+
+// YouTube doesn't publish a chat rate for the Data API, so we stick to Twitch's regular user pace to avoid
+// getting the account flagged as spam.
+const YOUTUBE_MESSAGE_LIMIT: usize = 20;
+const YOUTUBE_MESSAGE_WINDOW: Duration = Duration::from_secs(30);
+
+// Longest messages the platforms take, in characters. Twitch drops longer ones without telling us
+const TWITCH_MAX_LENGTH: usize = 500;
+const YOUTUBE_MAX_LENGTH: usize = 200;
+
+/// Sliding window rate limiter, keeps the instant of every message sent inside the window.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limit: usize,
+    window: Duration,
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            sent: VecDeque::with_capacity(limit),
+        }
+    }
+
+    /// Registers a new message if there's room for it on the window.
+    /// Otherwise, returns how long the caller has to wait before trying again.
+    pub(crate) fn try_acquire(&mut self) -> Result<(), Duration> {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&mut self, now: Instant) -> Result<(), Duration> {
+        while let Some(oldest) = self.sent.front() {
+            if now.duration_since(*oldest) >= self.window {
+                self.sent.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if self.sent.len() >= self.limit {
+            let oldest = self.sent.front().unwrap();
+            return Err(self.window - now.duration_since(*oldest));
+        }
+
+        self.sent.push_back(now);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct TwitchSender {
+    /// Raw lines written to the IRC socket by `connect_twitch_websocket`
+    pub(crate) tx: UnboundedSender<String>,
+    pub(crate) channel: String,
+    pub(crate) limiter: RateLimiter,
+}
+
+impl TwitchSender {
+    pub(crate) fn new(tx: UnboundedSender<String>, channel: String) -> Self {
+        Self {
+            tx,
+            channel,
+            limiter: RateLimiter::new(TWITCH_MESSAGE_LIMIT, TWITCH_MESSAGE_WINDOW),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct YoutubeSender {
+    pub(crate) video_id: String,
+    /// Resolved on the first message, the Data API needs it instead of the video id
+    pub(crate) live_chat_id: Option<String>,
+    pub(crate) limiter: RateLimiter,
+}
+
+impl YoutubeSender {
+    pub(crate) fn new(video_id: String) -> Self {
+        Self {
+            video_id,
+            live_chat_id: None,
+            limiter: RateLimiter::new(YOUTUBE_MESSAGE_LIMIT, YOUTUBE_MESSAGE_WINDOW),
+        }
+    }
+}
+
+/// Holds a sender for every platform we're currently connected to.
+/// Each one is only set while its chat client is running.
+#[derive(Default, Debug)]
+pub(crate) struct ChatSenders {
+    pub(crate) twitch: Mutex<Option<TwitchSender>>,
+    pub(crate) youtube: Mutex<Option<YoutubeSender>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SendMessageResult {
+    pub(crate) platform: String,
+    pub(crate) sent: bool,
+    pub(crate) error: Option<String>,
+    /// Set when the message was rate limited
+    pub(crate) retry_after_ms: Option<u64>,
+}
+
+impl SendMessageResult {
+    fn sent(platform: &str) -> Self {
+        Self {
+            platform: platform.to_string(),
+            sent: true,
+            error: None,
+            retry_after_ms: None,
+        }
+    }
+
+    fn failed(platform: &str, error: String) -> Self {
+        Self {
+            platform: platform.to_string(),
+            sent: false,
+            error: Some(error),
+            retry_after_ms: None,
+        }
+    }
+
+    fn rate_limited(platform: &str, wait: Duration) -> Self {
+        Self {
+            platform: platform.to_string(),
+            sent: false,
+            error: Some("Rate limited".to_string()),
+            retry_after_ms: Some(wait.as_millis() as u64),
+        }
+    }
+}
+
+/// Refuses messages the platform wouldn't take, before they count towards the rate limit.
+fn check_length(platform: &str, message: &str) -> Result<(), String> {
+    let max_length = if platform == "twitch" { TWITCH_MAX_LENGTH } else { YOUTUBE_MAX_LENGTH };
+    if message.chars().count() > max_length {
+        return Err(format!("Messages can't be longer than {} characters", max_length));
+    }
+    Ok(())
+}
+
+/// The line Twitch would have sent for our own message, it doesn't echo the ones we send.
+fn own_message_line(channel: &str, display_name: &str, user_id: &str, message: &str) -> String {
+    format!(
+        "@display-name={};user-id={} :{}!{}@{}.tmi.twitch.tv PRIVMSG #{} :{}",
+        display_name, user_id, channel, channel, channel, channel, message
+    )
+}
+
+/// Shows a message sent to Twitch on the overlays, like the ones coming from the chat.
+async fn echo_twitch_message(app: &AppHandle, message: &str) {
+    let channel = app.state::<ChatSenders>().twitch.lock().unwrap().as_ref().map(|sender| sender.channel.clone());
+    let Some(channel) = channel else {
+        return;
+    };
+    let (display_name, user_id) = match app.try_state::<UserInformation>() {
+        Some(user) => (user.internal_info.display_name.clone(), user.user_id.clone()),
+        None => (channel.clone(), String::new()),
+    };
+    let line = own_message_line(&channel, &display_name, &user_id, message);
+    let (Some(server), Some(response)) = (current_server(app), parse_privmsg(&line)) else {
+        return;
+    };
+    server.broadcast(Envelope::new("twitch", Some(&channel), "message", json!(response))).await;
+}
+
+fn send_twitch(app: &AppHandle, message: &str) -> Option<SendMessageResult> {
+    let senders = app.state::<ChatSenders>();
+    let mut twitch = senders.twitch.lock().unwrap();
+    let sender = twitch.as_mut()?;
+
+    if let Err(e) = check_length("twitch", message) {
+        return Some(SendMessageResult::failed("twitch", e));
+    }
+    if let Err(wait) = sender.limiter.try_acquire() {
+        return Some(SendMessageResult::rate_limited("twitch", wait));
+    }
+
+    let line = format!("PRIVMSG #{} :{}", sender.channel, message);
+    match sender.tx.send(line) {
+        Ok(_) => Some(SendMessageResult::sent("twitch")),
+        Err(_) => {
+            // The IRC client is gone, drop the sender so we stop reporting it as connected
+            *twitch = None;
+            Some(SendMessageResult::failed("twitch", "Twitch chat is not connected".to_string()))
+        }
+    }
+}
+
+async fn send_youtube(app: &AppHandle, message: &str) -> Option<SendMessageResult> {
+    let (video_id, live_chat_id) = {
+        let senders = app.state::<ChatSenders>();
+        let mut youtube = senders.youtube.lock().unwrap();
+        let sender = youtube.as_mut()?;
+
+        if let Err(e) = check_length("youtube", message) {
+            return Some(SendMessageResult::failed("youtube", e));
+        }
+        if let Err(wait) = sender.limiter.try_acquire() {
+            return Some(SendMessageResult::rate_limited("youtube", wait));
+        }
+
+        (sender.video_id.clone(), sender.live_chat_id.clone())
+    };
+
+    let token = match get_youtube_token() {
+        Ok(token) => token,
+        Err(e) => return Some(SendMessageResult::failed("youtube", e)),
+    };
+
+    let live_chat_id = match live_chat_id {
+        Some(id) => id,
+        None => match get_live_chat_id(&token, &video_id).await {
+            Ok(id) => {
+                let senders = app.state::<ChatSenders>();
+                if let Some(sender) = senders.youtube.lock().unwrap().as_mut() {
+                    sender.live_chat_id = Some(id.clone());
+                }
+                id
+            }
+            Err(e) => return Some(SendMessageResult::failed("youtube", e)),
+        },
+    };
+
+    match send_youtube_message(&token, &live_chat_id, message).await {
+        Ok(_) => Some(SendMessageResult::sent("youtube")),
+        Err(e) => Some(SendMessageResult::failed("youtube", e)),
+    }
+}
+
+/// Sends a chat message to a single platform ("twitch" or "youtube") or, if none is given, to every connected one.
+#[tauri::command]
+pub(crate) async fn send_chat_message(
+    app: AppHandle,
+    message: String,
+    platform: Option<String>,
+) -> Result<Vec<SendMessageResult>, String> {
+    let message = message.trim().replace(['\r', '\n'], " ");
+    if message.is_empty() {
+        return Err("Message is empty".into());
+    }
+
+    let mut results = Vec::new();
+
+    match platform.as_deref() {
+        Some("twitch") => {
+            let result = send_twitch(&app, &message).ok_or("Twitch chat is not connected")?;
+            results.push(result);
+        }
+        Some("youtube") => {
+            let result = send_youtube(&app, &message).await.ok_or("YouTube chat is not connected")?;
+            results.push(result);
+        }
+        Some(other) => return Err(format!("Unknown platform: {}", other)),
+        None => {
+            results.extend(send_twitch(&app, &message));
+            results.extend(send_youtube(&app, &message).await);
+
+            if results.is_empty() {
+                return Err("No chat is connected".into());
+            }
+        }
+    }
+
+    // YouTube's polling picks up our own messages, Twitch's IRC doesn't
+    if results.iter().any(|result| result.platform == "twitch" && result.sent) {
+        echo_twitch_message(&app, &message).await;
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rate_limiter_blocks_until_window_slides() {
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(30));
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire_at(start).is_ok());
+        assert!(limiter.try_acquire_at(start + Duration::from_secs(1)).is_ok());
+
+        let wait = limiter.try_acquire_at(start + Duration::from_secs(10)).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(20));
+
+        assert!(limiter.try_acquire_at(start + Duration::from_secs(30)).is_ok());
+    }
+
+    #[test]
+    fn checks_the_length_of_messages() {
+        assert!(check_length("twitch", &"é".repeat(500)).is_ok());
+        assert!(check_length("twitch", &"é".repeat(501)).is_err());
+        assert!(check_length("youtube", &"a".repeat(200)).is_ok());
+        assert!(check_length("youtube", &"a".repeat(201)).is_err());
+    }
+
+    #[test]
+    fn own_messages_look_like_the_chat_ones() {
+        let response = parse_privmsg(&own_message_line("nixyan", "Nixyan", "42", "hi <b>chat</b>")).unwrap();
+        assert_eq!(response.display_name, "Nixyan");
+        assert_eq!(response.message, "hi &lt;b&gt;chat&lt;/b&gt;");
+        assert!(response.tags.contains(&("user-id".to_string(), "42".to_string())));
+    }
+}
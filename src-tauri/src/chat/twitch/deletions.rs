@@ -0,0 +1,67 @@
+use crate::chat::twitch::helpers::auth_helpers::{parse_irc_channel, parse_irc_command, parse_irc_tags, parse_twitch_tags};
+use crate::chat::websocket::protocol::Envelope;
+use crate::chat::websocket::ws_server::WebSocketServer;
+use serde_json::{json, Value};
+
+fn find_tag<'a>(tags: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    tags.iter()
+        .find(|(tag, value)| tag == name && !value.is_empty())
+        .map(|(_, value)| value.as_str())
+}
+
+/// Whether a buffered Twitch message was sent by the given user, the user id is one of the message tags.
+fn sent_by(data: &Value, user_id: &str) -> bool {
+    data["tags"]
+        .as_array()
+        .map(|tags| tags.iter().any(|tag| tag[0] == "user-id" && tag[1] == user_id))
+        .unwrap_or(false)
+}
+
+/// Handles CLEARMSG (a single message was deleted) and CLEARCHAT (a user was timed out or banned, or the whole chat was cleared),
+/// removing the messages from the history and telling the overlays to remove them too.
+pub(crate) async fn handle_deletion(ws_server: &WebSocketServer, line: &str) {
+    let Some(channel) = parse_irc_channel(line) else {
+        return;
+    };
+    let tags = parse_twitch_tags(parse_irc_tags(line).unwrap_or(""));
+    let in_channel = |envelope: &Envelope| {
+        envelope.platform == "twitch"
+            && envelope.event == "message"
+            && envelope.channel.as_deref().map(|c| c.eq_ignore_ascii_case(channel)).unwrap_or(false)
+    };
+
+    let envelope = match parse_irc_command(line) {
+        Some("CLEARMSG") => {
+            let Some(message_id) = find_tag(&tags, "target-msg-id") else {
+                return;
+            };
+
+            ws_server
+                .remove_from_history(|envelope| in_channel(envelope) && envelope.data["id"] == message_id)
+                .await;
+
+            Envelope::new("twitch", Some(channel), "message_deleted", json!({ "id": message_id }))
+        }
+        Some("CLEARCHAT") => {
+            let user_id = find_tag(&tags, "target-user-id");
+            // The user's login is the trailing parameter, missing when the whole chat was cleared
+            let user_login = user_id.and_then(|_| line.rsplit_once(" :").map(|(_, login)| login.trim()));
+
+            ws_server
+                .remove_from_history(|envelope| {
+                    in_channel(envelope) && user_id.map(|user_id| sent_by(&envelope.data, user_id)).unwrap_or(true)
+                })
+                .await;
+
+            Envelope::new(
+                "twitch",
+                Some(channel),
+                "messages_cleared",
+                json!({ "user_id": user_id, "user_login": user_login }),
+            )
+        }
+        _ => return,
+    };
+
+    ws_server.broadcast(envelope).await;
+}
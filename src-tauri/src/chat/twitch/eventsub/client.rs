@@ -0,0 +1,336 @@
+use crate::chat::twitch::auth::{ImplicitGrantFlow, UserInformation};
+use crate::chat::twitch::eventsub::config::{get_enabled_topics, EventSubTopic};
+use crate::chat::twitch::eventsub::events::EventSubEvent;
+use crate::chat::twitch::helpers::auth_helpers::TWITCH_CLIENT_ID;
+use crate::chat::twitch::redemptions::{dequeue_redemption, queue_redemption};
+use crate::chat::websocket::protocol::Envelope;
+use crate::chat::websocket::ws_server::WebSocketServer;
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::{Error, Message};
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+type EventSubStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+const EVENTSUB_URL: &str = "wss://eventsub.wss.twitch.tv/ws";
+// Used until the welcome message tells us the real one
+const DEFAULT_KEEPALIVE_SECONDS: u64 = 10;
+// Twitch may deliver the same message more than once
+const MAX_SEEN_MESSAGES: usize = 50;
+
+#[derive(Deserialize, Debug)]
+struct EventSubMetadata {
+    message_id: String,
+    message_type: String,
+    subscription_type: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EventSubMessage {
+    metadata: EventSubMetadata,
+    payload: Value,
+}
+
+/// Ids of the last messages received, to skip the ones Twitch delivers twice.
+#[derive(Default)]
+struct SeenMessages(VecDeque<String>);
+
+impl SeenMessages {
+    /// Remembers the id, false if it was already seen.
+    fn first_time(&mut self, message_id: &str) -> bool {
+        if self.0.iter().any(|seen| seen == message_id) {
+            return false;
+        }
+        self.0.push_back(message_id.to_string());
+        if self.0.len() > MAX_SEEN_MESSAGES {
+            self.0.pop_front();
+        }
+        true
+    }
+}
+
+fn parse_message(text: &str) -> Result<EventSubMessage, String> {
+    serde_json::from_str(text).map_err(|e| format!("Failed to parse EventSub message: {}", e))
+}
+
+/// How long to wait for a message before giving up on the session, from its welcome message.
+/// Twitch closes the connection if nothing arrives in its timeout, we give it a few extra seconds.
+fn keepalive_timeout(welcome: &EventSubMessage) -> Duration {
+    let timeout = welcome.payload["session"]["keepalive_timeout_seconds"].as_u64().unwrap_or(DEFAULT_KEEPALIVE_SECONDS);
+    Duration::from_secs(timeout + 5)
+}
+
+/// Where a session_reconnect message asks us to connect to.
+fn reconnect_url(message: &EventSubMessage) -> Option<&str> {
+    message.payload["session"]["reconnect_url"].as_str()
+}
+
+/// Waits for the next message of the connection that will replace the current one, if there's any.
+async fn next_replacement_message(replacement: &mut Option<EventSubStream>) -> Option<Result<Message, Error>> {
+    match replacement {
+        Some(stream) => stream.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn create_subscriptions(
+    app: &AppHandle,
+    auth: &ImplicitGrantFlow,
+    user: &UserInformation,
+    session_id: &str,
+    topics: &[&EventSubTopic],
+) {
+    let client = reqwest::Client::new();
+    let mut failed = Vec::new();
+
+    for topic in topics {
+        let condition = if topic.needs_moderator {
+            json!({ "broadcaster_user_id": user.user_id, "moderator_user_id": user.user_id })
+        } else {
+            json!({ "broadcaster_user_id": user.user_id })
+        };
+
+        let response = client
+            .post("https://api.twitch.tv/helix/eventsub/subscriptions")
+            .header("Client-Id", TWITCH_CLIENT_ID)
+            .bearer_auth(&auth.access_token)
+            .json(&json!({
+                "type": topic.subscription_type,
+                "version": topic.version,
+                "condition": condition,
+                "transport": {
+                    "method": "websocket",
+                    "session_id": session_id,
+                }
+            }))
+            .send()
+            .await;
+
+        match response {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => {
+                let resp = response.text().await.unwrap_or_default();
+                eprintln!("Failed to subscribe to {}: {}", topic.subscription_type, resp);
+                failed.push(topic.subscription_type);
+            }
+            Err(e) => {
+                eprintln!("Failed to subscribe to {}: {}", topic.subscription_type, e);
+                failed.push(topic.subscription_type);
+            }
+        }
+    }
+
+    // Most of the time it's a missing scope, the dashboard tells the user to link the account again
+    if !failed.is_empty() {
+        app.emit_to("main", "twitch::eventsub_failed", failed)
+            .unwrap_or_else(|e| eprintln!("Failed to emit EventSub status: {}", e));
+    }
+}
+
+async fn handle_notification(
+    app: &AppHandle,
+    ws_server: &WebSocketServer,
+    channel: &str,
+    subscription_type: &str,
+    payload: Value,
+) {
+    let event = match EventSubEvent::parse(subscription_type, payload["event"].clone()) {
+        Ok(event) => event,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    match &event {
+        EventSubEvent::RedemptionAdd(redemption) => queue_redemption(app, redemption),
+        EventSubEvent::RedemptionUpdate(redemption) => dequeue_redemption(app, &redemption.id),
+        _ => {}
+    }
+
+    ws_server
+        .broadcast(Envelope::new("twitch", Some(channel), event.name(), json!(event)))
+        .await;
+}
+
+/// Connects to EventSub and keeps the session alive until the chat is stopped.
+/// Only works with a linked account, since every subscription needs the user's token.
+pub(crate) async fn connect_eventsub(app: AppHandle, stop_flag: Arc<AtomicBool>, ws_server: Arc<WebSocketServer>) {
+    let auth = app.state::<ImplicitGrantFlow>().inner().clone();
+    let Some(user) = app.try_state::<UserInformation>().map(|user| user.inner().clone()) else {
+        return;
+    };
+    if auth.skipped != Some(false) || auth.access_token.is_empty() || user.user_id.is_empty() {
+        return;
+    }
+
+    let topics = get_enabled_topics(&app);
+    if topics.is_empty() {
+        return;
+    }
+
+    let mut seen_messages = SeenMessages::default();
+
+    'session: while !stop_flag.load(Ordering::Relaxed) {
+        let mut ws_stream = match connect_async(EVENTSUB_URL).await {
+            Ok((ws_stream, _)) => ws_stream,
+            Err(e) => {
+                eprintln!("Failed to connect to EventSub: {}", e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        // Subscriptions carry over when Twitch asks us to reconnect, so they're only created on new sessions
+        let mut subscribe = true;
+        // Twitch keeps sending events to the old connection until the new one gets its welcome
+        let mut replacement: Option<EventSubStream> = None;
+        let mut keepalive = Duration::from_secs(DEFAULT_KEEPALIVE_SECONDS);
+        let mut last_message = Instant::now();
+
+        loop {
+            if stop_flag.load(Ordering::Relaxed) {
+                println!("Stopping EventSub connection...");
+                break 'session;
+            }
+
+            tokio::select! {
+                maybe_msg = ws_stream.next() => {
+                    let text = match maybe_msg {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                            println!("EventSub connection closed, starting a new session...");
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                            continue 'session;
+                        }
+                        // Pings are answered by tungstenite itself
+                        Some(Ok(_)) => continue,
+                    };
+
+                    last_message = Instant::now();
+
+                    let message = match parse_message(&text) {
+                        Ok(message) => message,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            continue;
+                        }
+                    };
+                    if !seen_messages.first_time(&message.metadata.message_id) {
+                        continue;
+                    }
+
+                    match message.metadata.message_type.as_str() {
+                        "session_welcome" => {
+                            keepalive = keepalive_timeout(&message);
+
+                            if subscribe {
+                                let session_id = message.payload["session"]["id"].as_str().unwrap_or_default();
+                                create_subscriptions(&app, &auth, &user, session_id, &topics).await;
+                                subscribe = false;
+                            }
+                        }
+                        "session_keepalive" => {}
+                        "notification" => {
+                            let subscription_type = message.metadata.subscription_type.unwrap_or_default();
+                            handle_notification(&app, ws_server.as_ref(), &user.login, &subscription_type, message.payload).await;
+                        }
+                        "session_reconnect" => {
+                            if let Some(reconnect_url) = reconnect_url(&message) {
+                                println!("EventSub asked to reconnect");
+                                match connect_async(reconnect_url).await {
+                                    Ok((stream, _)) => replacement = Some(stream),
+                                    Err(e) => eprintln!("Failed to reconnect to EventSub, staying on the current session: {}", e),
+                                }
+                            }
+                        }
+                        "revocation" => {
+                            let subscription_type = message.metadata.subscription_type.unwrap_or_default();
+                            eprintln!("EventSub subscription revoked: {}", subscription_type);
+                            app.emit_to("main", "twitch::eventsub_failed", vec![subscription_type])
+                                .unwrap_or_else(|e| eprintln!("Failed to emit EventSub status: {}", e));
+                        }
+                        other => println!("Unknown EventSub message type: {}", other),
+                    }
+                },
+                maybe_msg = next_replacement_message(&mut replacement) => {
+                    let text = match maybe_msg {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(_)) => continue,
+                        Some(Err(_)) | None => {
+                            eprintln!("The new EventSub connection closed, staying on the current session");
+                            replacement = None;
+                            continue;
+                        }
+                    };
+
+                    let Ok(message) = parse_message(&text) else {
+                        continue;
+                    };
+                    if message.metadata.message_type == "session_welcome" {
+                        keepalive = keepalive_timeout(&message);
+                        last_message = Instant::now();
+                        seen_messages.first_time(&message.metadata.message_id);
+                        // The old connection is only dropped once the new one is ready to receive events
+                        if let Some(stream) = replacement.take() {
+                            ws_stream = stream;
+                        }
+                    }
+                },
+                _ = tokio::time::sleep(Duration::from_millis(100)) => {
+                    if last_message.elapsed() > keepalive {
+                        println!("EventSub keepalive timed out, starting a new session...");
+                        continue 'session;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn skips_messages_seen_before() {
+        let mut seen = SeenMessages::default();
+        assert!(seen.first_time("a"));
+        assert!(!seen.first_time("a"));
+
+        for i in 0..MAX_SEEN_MESSAGES {
+            assert!(seen.first_time(&i.to_string()));
+        }
+        // Only the last ones are remembered
+        assert!(seen.first_time("a"));
+    }
+
+    #[test]
+    fn reads_welcome_and_reconnect_messages() {
+        let welcome = parse_message(
+            r#"{"metadata":{"message_id":"96a3f3b5-5dec-4eed-908e-e11ee657416c","message_type":"session_welcome","message_timestamp":"2023-07-19T14:56:51.634234626Z"},
+                "payload":{"session":{"id":"AQoQILE98gtqShGmLD7AM6yJThAB","status":"connected","connected_at":"2023-07-19T14:56:51.616329898Z","keepalive_timeout_seconds":10,"reconnect_url":null}}}"#,
+        )
+        .unwrap();
+        assert_eq!(welcome.metadata.message_type, "session_welcome");
+        assert_eq!(keepalive_timeout(&welcome), Duration::from_secs(15));
+        assert_eq!(reconnect_url(&welcome), None);
+
+        let reconnect = parse_message(
+            r#"{"metadata":{"message_id":"84c1e79a-2a4b-4c13-ba0b-4312293e9308","message_type":"session_reconnect","message_timestamp":"2022-11-18T09:10:11.634234626Z"},
+                "payload":{"session":{"id":"AQoQexAWVYKSTIu4ec_2VAxyuhAB","status":"reconnecting","keepalive_timeout_seconds":null,"reconnect_url":"wss://eventsub.wss.twitch.tv?reconnect=1","connected_at":"2022-11-16T10:11:12.634234626Z"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(reconnect_url(&reconnect), Some("wss://eventsub.wss.twitch.tv?reconnect=1"));
+        assert_eq!(keepalive_timeout(&reconnect), Duration::from_secs(DEFAULT_KEEPALIVE_SECONDS + 5));
+
+        assert!(parse_message("not json").is_err());
+    }
+}
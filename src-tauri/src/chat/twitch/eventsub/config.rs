@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use std::ops::Deref;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+
+pub(crate) struct EventSubTopic {
+    pub(crate) subscription_type: &'static str,
+    pub(crate) version: &'static str,
+    /// Scope the user token needs for Twitch to accept the subscription
+    pub(crate) scope: &'static str,
+    /// Some topics also need the moderator id on the condition, which is the broadcaster itself for us
+    pub(crate) needs_moderator: bool,
+}
+
+pub(crate) const TOPICS: &[EventSubTopic] = &[
+    EventSubTopic { subscription_type: "channel.follow", version: "2", scope: "moderator:read:followers", needs_moderator: true },
+    EventSubTopic { subscription_type: "channel.channel_points_custom_reward_redemption.add", version: "1", scope: "channel:read:redemptions", needs_moderator: false },
+    EventSubTopic { subscription_type: "channel.channel_points_custom_reward_redemption.update", version: "1", scope: "channel:read:redemptions", needs_moderator: false },
+    EventSubTopic { subscription_type: "channel.hype_train.begin", version: "1", scope: "channel:read:hype_train", needs_moderator: false },
+    EventSubTopic { subscription_type: "channel.hype_train.progress", version: "1", scope: "channel:read:hype_train", needs_moderator: false },
+    EventSubTopic { subscription_type: "channel.hype_train.end", version: "1", scope: "channel:read:hype_train", needs_moderator: false },
+    EventSubTopic { subscription_type: "channel.poll.begin", version: "1", scope: "channel:read:polls", needs_moderator: false },
+    EventSubTopic { subscription_type: "channel.poll.progress", version: "1", scope: "channel:read:polls", needs_moderator: false },
+    EventSubTopic { subscription_type: "channel.poll.end", version: "1", scope: "channel:read:polls", needs_moderator: false },
+    EventSubTopic { subscription_type: "channel.prediction.begin", version: "1", scope: "channel:read:predictions", needs_moderator: false },
+    EventSubTopic { subscription_type: "channel.prediction.progress", version: "1", scope: "channel:read:predictions", needs_moderator: false },
+    EventSubTopic { subscription_type: "channel.prediction.lock", version: "1", scope: "channel:read:predictions", needs_moderator: false },
+    EventSubTopic { subscription_type: "channel.prediction.end", version: "1", scope: "channel:read:predictions", needs_moderator: false },
+    EventSubTopic { subscription_type: "channel.ad_break.begin", version: "1", scope: "channel:read:ads", needs_moderator: false },
+];
+
+const SETTINGS_TREE: &str = "settings";
+const TOPICS_KEY: &str = "eventsub_topics";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct EventSubTopicInfo {
+    pub(crate) subscription_type: String,
+    pub(crate) scope: String,
+    pub(crate) enabled: bool,
+}
+
+/// Returns the topics the user wants to subscribe to, every topic is enabled until they choose otherwise.
+pub(crate) fn get_enabled_topics(app: &AppHandle) -> Vec<&'static EventSubTopic> {
+    let db: Arc<Db> = app.state::<Arc<Db>>().deref().clone();
+
+    let stored: Option<Vec<String>> = db
+        .open_tree(SETTINGS_TREE)
+        .ok()
+        .and_then(|tree| tree.get(TOPICS_KEY).ok().flatten())
+        .and_then(|topics| serde_json::from_slice(&topics).ok());
+
+    match stored {
+        Some(enabled) => TOPICS
+            .iter()
+            .filter(|topic| enabled.iter().any(|name| name == topic.subscription_type))
+            .collect(),
+        None => TOPICS.iter().collect(),
+    }
+}
+
+#[tauri::command]
+pub(crate) async fn get_eventsub_topics(app: AppHandle) -> Result<Vec<EventSubTopicInfo>, String> {
+    let enabled = get_enabled_topics(&app);
+
+    Ok(TOPICS
+        .iter()
+        .map(|topic| EventSubTopicInfo {
+            subscription_type: topic.subscription_type.to_string(),
+            scope: topic.scope.to_string(),
+            enabled: enabled.iter().any(|e| e.subscription_type == topic.subscription_type),
+        })
+        .collect())
+}
+
+/// Changes which topics are subscribed to, takes effect the next time the chat is started.
+#[tauri::command]
+pub(crate) async fn set_eventsub_topics(app: AppHandle, topics: Vec<String>) -> Result<bool, String> {
+    if let Some(unknown) = topics
+        .iter()
+        .find(|name| !TOPICS.iter().any(|topic| topic.subscription_type == name.as_str()))
+    {
+        return Err(format!("Unknown EventSub topic: {}", unknown));
+    }
+
+    let db: Arc<Db> = app.state::<Arc<Db>>().deref().clone();
+    let tree = db.open_tree(SETTINGS_TREE).map_err(|e| e.to_string())?;
+    let serialized_data = serde_json::to_vec(&topics).map_err(|e| e.to_string())?;
+
+    tree.insert(TOPICS_KEY, serialized_data).map_err(|e| e.to_string())?;
+    tree.flush().map_err(|e| e.to_string())?;
+
+    Ok(true)
+}
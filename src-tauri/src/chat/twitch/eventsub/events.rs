@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct FollowEvent {
+    pub(crate) user_id: String,
+    pub(crate) user_login: String,
+    pub(crate) user_name: String,
+    pub(crate) followed_at: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct RedemptionReward {
+    pub(crate) id: String,
+    pub(crate) title: String,
+    pub(crate) cost: u64,
+    #[serde(default)]
+    pub(crate) prompt: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct RedemptionEvent {
+    pub(crate) id: String,
+    pub(crate) broadcaster_user_id: String,
+    pub(crate) user_id: String,
+    pub(crate) user_login: String,
+    pub(crate) user_name: String,
+    #[serde(default)]
+    pub(crate) user_input: String,
+    /// "unfulfilled", "fulfilled", "canceled" or "unknown"
+    pub(crate) status: String,
+    pub(crate) reward: RedemptionReward,
+    pub(crate) redeemed_at: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct HypeTrainContribution {
+    pub(crate) user_id: String,
+    pub(crate) user_login: String,
+    pub(crate) user_name: String,
+    /// "bits", "subscription" or "other"
+    #[serde(rename = "type")]
+    pub(crate) contribution_type: String,
+    pub(crate) total: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct HypeTrainEvent {
+    pub(crate) id: String,
+    pub(crate) level: Option<u32>,
+    pub(crate) total: u64,
+    pub(crate) progress: Option<u64>,
+    pub(crate) goal: Option<u64>,
+    #[serde(default)]
+    pub(crate) top_contributions: Vec<HypeTrainContribution>,
+    pub(crate) started_at: String,
+    pub(crate) expires_at: Option<String>,
+    pub(crate) ended_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct PollChoice {
+    pub(crate) id: String,
+    pub(crate) title: String,
+    pub(crate) votes: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct PollEvent {
+    pub(crate) id: String,
+    pub(crate) title: String,
+    pub(crate) choices: Vec<PollChoice>,
+    /// Only sent when the poll ends
+    pub(crate) status: Option<String>,
+    pub(crate) started_at: String,
+    pub(crate) ends_at: Option<String>,
+    pub(crate) ended_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct PredictionOutcome {
+    pub(crate) id: String,
+    pub(crate) title: String,
+    /// "blue" or "pink"
+    pub(crate) color: String,
+    pub(crate) users: Option<u64>,
+    pub(crate) channel_points: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct PredictionEvent {
+    pub(crate) id: String,
+    pub(crate) title: String,
+    pub(crate) outcomes: Vec<PredictionOutcome>,
+    /// Only sent when the prediction ends
+    pub(crate) status: Option<String>,
+    pub(crate) winning_outcome_id: Option<String>,
+    pub(crate) started_at: String,
+    pub(crate) locks_at: Option<String>,
+    pub(crate) locked_at: Option<String>,
+    pub(crate) ended_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct AdBreakEvent {
+    pub(crate) duration_seconds: u64,
+    pub(crate) started_at: String,
+    pub(crate) is_automatic: bool,
+    #[serde(default)]
+    pub(crate) requester_user_login: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub(crate) enum EventSubEvent {
+    Follow(FollowEvent),
+    RedemptionAdd(RedemptionEvent),
+    RedemptionUpdate(RedemptionEvent),
+    HypeTrainBegin(HypeTrainEvent),
+    HypeTrainProgress(HypeTrainEvent),
+    HypeTrainEnd(HypeTrainEvent),
+    PollBegin(PollEvent),
+    PollProgress(PollEvent),
+    PollEnd(PollEvent),
+    PredictionBegin(PredictionEvent),
+    PredictionProgress(PredictionEvent),
+    PredictionLock(PredictionEvent),
+    PredictionEnd(PredictionEvent),
+    AdBreakBegin(AdBreakEvent),
+}
+
+impl EventSubEvent {
+    /// Parses the "event" object of a notification based on its subscription type.
+    pub(crate) fn parse(subscription_type: &str, event: Value) -> Result<Self, String> {
+        let parsed = match subscription_type {
+            "channel.follow" => serde_json::from_value(event).map(Self::Follow),
+            "channel.channel_points_custom_reward_redemption.add" => serde_json::from_value(event).map(Self::RedemptionAdd),
+            "channel.channel_points_custom_reward_redemption.update" => serde_json::from_value(event).map(Self::RedemptionUpdate),
+            "channel.hype_train.begin" => serde_json::from_value(event).map(Self::HypeTrainBegin),
+            "channel.hype_train.progress" => serde_json::from_value(event).map(Self::HypeTrainProgress),
+            "channel.hype_train.end" => serde_json::from_value(event).map(Self::HypeTrainEnd),
+            "channel.poll.begin" => serde_json::from_value(event).map(Self::PollBegin),
+            "channel.poll.progress" => serde_json::from_value(event).map(Self::PollProgress),
+            "channel.poll.end" => serde_json::from_value(event).map(Self::PollEnd),
+            "channel.prediction.begin" => serde_json::from_value(event).map(Self::PredictionBegin),
+            "channel.prediction.progress" => serde_json::from_value(event).map(Self::PredictionProgress),
+            "channel.prediction.lock" => serde_json::from_value(event).map(Self::PredictionLock),
+            "channel.prediction.end" => serde_json::from_value(event).map(Self::PredictionEnd),
+            "channel.ad_break.begin" => serde_json::from_value(event).map(Self::AdBreakBegin),
+            _ => return Err(format!("Unsupported subscription type: {}", subscription_type)),
+        };
+
+        parsed.map_err(|e| format!("Failed to parse {}: {}", subscription_type, e))
+    }
+
+    /// Event name sent to the overlays
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Follow(_) => "follow",
+            Self::RedemptionAdd(_) => "redemption",
+            Self::RedemptionUpdate(_) => "redemption_update",
+            Self::HypeTrainBegin(_) => "hype_train_begin",
+            Self::HypeTrainProgress(_) => "hype_train_progress",
+            Self::HypeTrainEnd(_) => "hype_train_end",
+            Self::PollBegin(_) => "poll_begin",
+            Self::PollProgress(_) => "poll_progress",
+            Self::PollEnd(_) => "poll_end",
+            Self::PredictionBegin(_) => "prediction_begin",
+            Self::PredictionProgress(_) => "prediction_progress",
+            Self::PredictionLock(_) => "prediction_lock",
+            Self::PredictionEnd(_) => "prediction_end",
+            Self::AdBreakBegin(_) => "ad_break_begin",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn redemption(status: &str) -> Value {
+        json!({
+            "id": "17fa2df1-ad76-4804-bfa5-a40ef63efe63",
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cool_user",
+            "broadcaster_user_name": "Cool_User",
+            "user_id": "9001",
+            "user_login": "cooler_user",
+            "user_name": "Cooler_User",
+            "user_input": "pogchamp",
+            "status": status,
+            "reward": { "id": "92af127c-7326-4483-a52b-b0da0be61c01", "title": "title", "cost": 100, "prompt": "reward prompt" },
+            "redeemed_at": "2020-07-15T17:16:03.17106713Z"
+        })
+    }
+
+    #[test]
+    fn parses_twitch_payloads() {
+        let follow = json!({
+            "user_id": "1234",
+            "user_login": "cool_user",
+            "user_name": "Cool_User",
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cooler_user",
+            "broadcaster_user_name": "Cooler_User",
+            "followed_at": "2020-07-15T18:16:11.17106713Z"
+        });
+        let event = EventSubEvent::parse("channel.follow", follow).unwrap();
+        assert_eq!(event.name(), "follow");
+        assert!(matches!(&event, EventSubEvent::Follow(follow) if follow.user_name == "Cool_User"));
+
+        let event = EventSubEvent::parse("channel.channel_points_custom_reward_redemption.add", redemption("unfulfilled")).unwrap();
+        assert_eq!(event.name(), "redemption");
+        assert!(matches!(&event, EventSubEvent::RedemptionAdd(redemption) if redemption.reward.cost == 100 && redemption.user_input == "pogchamp"));
+
+        let event = EventSubEvent::parse("channel.channel_points_custom_reward_redemption.update", redemption("fulfilled")).unwrap();
+        assert_eq!(event.name(), "redemption_update");
+        assert!(matches!(&event, EventSubEvent::RedemptionUpdate(redemption) if redemption.status == "fulfilled"));
+
+        let progress = json!({
+            "id": "1b0AsbInCHZW2SQFQkCzqN07Ib2",
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cool_user",
+            "broadcaster_user_name": "Cool_User",
+            "level": 2,
+            "total": 700,
+            "progress": 200,
+            "goal": 1000,
+            "top_contributions": [
+                { "user_id": "123", "user_login": "pogchamp", "user_name": "PogChamp", "type": "bits", "total": 50 },
+                { "user_id": "456", "user_login": "kappa", "user_name": "Kappa", "type": "subscription", "total": 45 }
+            ],
+            "last_contribution": { "user_id": "123", "user_login": "pogchamp", "user_name": "PogChamp", "type": "bits", "total": 50 },
+            "started_at": "2020-07-15T17:16:03.17106713Z",
+            "expires_at": "2020-07-15T17:16:11.17106713Z"
+        });
+        let event = EventSubEvent::parse("channel.hype_train.progress", progress).unwrap();
+        assert_eq!(event.name(), "hype_train_progress");
+        let EventSubEvent::HypeTrainProgress(train) = &event else {
+            panic!("Expected a hype train progress, got {:?}", event);
+        };
+        assert_eq!((train.level, train.progress, train.goal), (Some(2), Some(200), Some(1000)));
+        assert_eq!(train.top_contributions[1].contribution_type, "subscription");
+        // Sent to the overlays as Twitch sent it, the field names included
+        assert_eq!(json!(event)["top_contributions"][0]["type"], "bits");
+    }
+
+    #[test]
+    fn refuses_unknown_and_broken_payloads() {
+        let error = EventSubEvent::parse("channel.cheer", json!({})).unwrap_err();
+        assert_eq!(error, "Unsupported subscription type: channel.cheer");
+        let error = EventSubEvent::parse("channel.follow", json!({ "user_id": "1234" })).unwrap_err();
+        assert!(error.starts_with("Failed to parse channel.follow"));
+    }
+}
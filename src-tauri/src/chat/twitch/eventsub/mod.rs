@@ -0,0 +1,4 @@
+#[macro_use]
+pub(crate) mod config;
+pub(crate) mod client;
+pub(crate) mod events;
@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tauri::State;
 
+pub(crate) const TWITCH_CLIENT_ID: &str = "h3yvglc6y3kmtrzyq7it20z7vi5sa2";
+
 pub(crate) fn parse_twitch_message(message: &str) -> Option<(String, String, String)> {
     let re = Regex::new(r"@(?P<tags>[^ ]*) (?P<username>[^!]+)!.* PRIVMSG #[^ ]* :(?P<message>.*)")
         .unwrap();
@@ -17,6 +19,37 @@ pub(crate) fn parse_twitch_message(message: &str) -> Option<(String, String, Str
     None
 }
 
+/// Returns the IRC command of a line, skipping the tags and the prefix if there are any.
+/// E.g.: "@emote-only=0 :tmi.twitch.tv ROOMSTATE #channel" -> "ROOMSTATE"
+pub(crate) fn parse_irc_command(line: &str) -> Option<&str> {
+    line.split(' ')
+        .filter(|part| !part.is_empty())
+        .find(|part| !part.starts_with('@') && !part.starts_with(':'))
+}
+
+/// Whether a line is the NOTICE Twitch sends when it rejects the token. Only the notice text is
+/// checked, chat lines quoting it must not end the session.
+pub(crate) fn is_login_failure(line: &str) -> bool {
+    parse_irc_command(line) == Some("NOTICE")
+        && line
+            .split_once(" NOTICE ")
+            .and_then(|(_, params)| params.split_once(':'))
+            .is_some_and(|(_, text)| text == "Login authentication failed")
+}
+
+/// Returns the channel (without the "#") a line was sent to, if any.
+pub(crate) fn parse_irc_channel(line: &str) -> Option<&str> {
+    line.split(' ')
+        .find(|part| part.starts_with('#'))
+        .map(|channel| &channel[1..])
+}
+
+/// Returns the raw tags of a line, without the leading "@".
+pub(crate) fn parse_irc_tags(line: &str) -> Option<&str> {
+    line.strip_prefix('@')
+        .and_then(|line| line.split(' ').next())
+}
+
 pub(crate) fn parse_twitch_tags(tags_str: &str) -> Vec<(String, String)> {
     let tags_vec: Vec<&str> = tags_str.split(';').collect();
     let mut tags: Vec<(String, String)> = Vec::new();
@@ -31,6 +64,95 @@ pub(crate) fn parse_twitch_tags(tags_str: &str) -> Vec<(String, String)> {
     tags
 }
 
+/// Escapes a tag value the way Twitch does, the reverse of [unescape_tag_value].
+pub(crate) fn escape_tag_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ';' => escaped.push_str("\\:"),
+            ' ' => escaped.push_str("\\s"),
+            '\\' => escaped.push_str("\\\\"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Tag values escape spaces, semicolons and backslashes, this reverts it.
+/// https://ircv3.net/specs/extensions/message-tags#escaping-values
+pub(crate) fn unescape_tag_value(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some(':') => unescaped.push(';'),
+            Some('s') => unescaped.push(' '),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => {}
+        }
+    }
+
+    unescaped
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct TwitchReply {
+    /// Message being replied to
+    pub(crate) parent_msg_id: String,
+    pub(crate) parent_user_login: String,
+    pub(crate) parent_display_name: String,
+    pub(crate) parent_msg_body: String,
+    /// First message of the thread, same as the parent when replying to a message that isn't a reply itself
+    pub(crate) thread_parent_msg_id: String,
+    pub(crate) thread_parent_user_login: String,
+}
+
+/// Builds the reply context out of the `reply-*` tags, if the message is a reply.
+pub(crate) fn parse_reply_tags(tags: &[(String, String)]) -> Option<TwitchReply> {
+    let get = |name: &str| {
+        tags.iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| unescape_tag_value(value))
+    };
+
+    let parent_msg_id = get("reply-parent-msg-id").filter(|id| !id.is_empty())?;
+    let parent_user_login = get("reply-parent-user-login").unwrap_or_default();
+
+    Some(TwitchReply {
+        thread_parent_msg_id: get("reply-thread-parent-msg-id").unwrap_or(parent_msg_id.clone()),
+        thread_parent_user_login: get("reply-thread-parent-user-login").unwrap_or(parent_user_login.clone()),
+        parent_display_name: get("reply-parent-display-name").unwrap_or(parent_user_login.clone()),
+        parent_msg_body: get("reply-parent-msg-body").unwrap_or_default(),
+        parent_msg_id,
+        parent_user_login,
+    })
+}
+
+/// Twitch prepends "@parent_login " to the content of every reply, themes render the reply bubble themselves.
+pub(crate) fn strip_reply_mention(message: &str, reply: &TwitchReply) -> String {
+    for name in [&reply.parent_user_login, &reply.parent_display_name] {
+        let mention = format!("@{} ", name);
+        let starts_with_mention = message
+            .get(..mention.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(&mention));
+        if !name.is_empty() && starts_with_mention {
+            return message[mention.len()..].to_string();
+        }
+    }
+
+    message.to_string()
+}
+
 pub(crate) fn construct_emote_url(emote_id: &str) -> String {
     format!(
         "https://static-cdn.jtvnw.net/emoticons/v2/{}/default/dark/1.0",
@@ -85,3 +207,41 @@ pub(crate) async fn get_chat_badges(
 
     badges
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_reply_tags_and_strips_mention() {
+        let tags = parse_twitch_tags(
+            "reply-parent-display-name=Nixyan;reply-parent-msg-body=hello\\sthere\\:);reply-parent-msg-id=abc;reply-parent-user-login=nixyan;reply-thread-parent-msg-id=root;reply-thread-parent-user-login=tocka",
+        );
+        let reply = parse_reply_tags(&tags).unwrap();
+
+        assert_eq!(reply.parent_msg_id, "abc");
+        assert_eq!(reply.parent_display_name, "Nixyan");
+        assert_eq!(reply.parent_msg_body, "hello there;)");
+        assert_eq!(reply.thread_parent_msg_id, "root");
+        assert_eq!(reply.thread_parent_user_login, "tocka");
+        assert_eq!(strip_reply_mention("@nixyan hi!", &reply), "hi!");
+        assert_eq!(strip_reply_mention("hi @nixyan ", &reply), "hi @nixyan ");
+        assert_eq!(strip_reply_mention("héllo wörld", &reply), "héllo wörld");
+        assert_eq!(strip_reply_mention("日本語のテキスト", &reply), "日本語のテキスト");
+    }
+
+    #[test]
+    fn detects_login_failures_only_on_notices() {
+        assert!(is_login_failure(":tmi.twitch.tv NOTICE * :Login authentication failed"));
+        assert!(!is_login_failure(
+            "@badges=;color= :nixyan!nixyan@nixyan.tmi.twitch.tv PRIVMSG #nixyan :Login authentication failed"
+        ));
+        assert!(!is_login_failure(":tmi.twitch.tv NOTICE #nixyan :Login authentication failed? not here"));
+    }
+
+    #[test]
+    fn messages_without_reply_tags_have_no_context() {
+        let tags = parse_twitch_tags("badges=;color=#FF0000;display-name=Nixyan");
+        assert!(parse_reply_tags(&tags).is_none());
+    }
+}
@@ -1,13 +1,14 @@
 use crate::chat::twitch::auth::ImplicitGrantFlow;
-use crate::chat::twitch::helpers::auth_helpers::{construct_emote_url, get_chat_badges, parse_twitch_message, parse_twitch_tags};
+use crate::chat::twitch::helpers::auth_helpers::{construct_emote_url, get_chat_badges, parse_irc_channel, parse_reply_tags, parse_twitch_message, parse_twitch_tags, strip_reply_mention, TwitchReply};
 use crate::chat::twitch::irc::UserInformationState;
+use crate::chat::websocket::protocol::Envelope;
 use crate::chat::websocket::ws_server::WebSocketServer;
+use crate::misc::editor::template::{emote_image, escape_html};
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tauri::State;
-use tokio_tungstenite::tungstenite::Message;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub(crate) struct RawTwitchResponse {
@@ -26,6 +27,99 @@ pub(crate) struct TwitchResponse {
     pub(crate) emotes: Vec<(String, String)>,
     pub(crate) raw_data: RawTwitchResponse,
     pub(crate) tags: Vec<(String, String)>,
+    /// Set when the message is a reply to another one
+    pub(crate) reply: Option<TwitchReply>,
+    /// Sent with /me
+    pub(crate) action: bool,
+}
+
+/// The text of a /me message, which Twitch sends wrapped as a CTCP ACTION ("\x01ACTION waves\x01").
+pub(crate) fn strip_action(content: &str) -> Option<&str> {
+    let text = content.strip_prefix("\u{1}ACTION ")?;
+    Some(text.strip_suffix('\u{1}').unwrap_or(text))
+}
+
+/// Turns the content of a message into HTML, its emotes as images and everything else escaped.
+/// The emotes tag (e.g. "25:0-4,12-16/1902:6-10") has the positions of every emote, in characters.
+pub(crate) fn format_message(content: &str, emotes: &str) -> (String, Vec<(String, String)>) {
+    let chars: Vec<char> = content.chars().collect();
+    let mut positions: Vec<(usize, usize, &str)> = emotes
+        .split('/')
+        .filter_map(|emote| emote.split_once(':'))
+        .flat_map(|(emote_id, ranges)| {
+            ranges.split(',').filter_map(move |range| {
+                let (start, end) = range.split_once('-')?;
+                Some((start.parse().ok()?, end.parse().ok()?, emote_id))
+            })
+        })
+        .filter(|(start, end, _)| start <= end && *end < chars.len())
+        .collect();
+    positions.sort();
+
+    let mut html = String::with_capacity(content.len());
+    let mut parsed_emotes: Vec<(String, String)> = Vec::new();
+    let mut position = 0;
+    for (start, end, emote_id) in positions {
+        if start < position {
+            continue;
+        }
+
+        escape_html(&chars[position..start].iter().collect::<String>(), &mut html);
+        let emote_name: String = chars[start..=end].iter().collect();
+        let emote_url = construct_emote_url(emote_id);
+        html.push_str(&emote_image(&emote_name, &emote_url, None));
+        if !parsed_emotes.iter().any(|(name, _)| *name == emote_name) {
+            parsed_emotes.push((emote_name, emote_url));
+        }
+        position = end + 1;
+    }
+    escape_html(&chars[position..].iter().collect::<String>(), &mut html);
+
+    (html, parsed_emotes)
+}
+
+/// Builds the message of a PRIVMSG line, without the badges since they need the API.
+pub(crate) fn parse_privmsg(line: &str) -> Option<TwitchResponse> {
+    let (tags, username, content) = parse_twitch_message(line)?;
+    // Emote positions of actions are relative to their text
+    let action = strip_action(&content).map(String::from);
+    let content = action.clone().unwrap_or(content);
+    let parsed_tags = parse_twitch_tags(&tags);
+    let tag = |name: &str| parsed_tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.clone());
+
+    // Use Twitch's own message id when there's one, so replies (and removals) can point back to it
+    let id = tag("id").filter(|id| !id.is_empty()).unwrap_or_else(|| {
+        rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(16)
+            .map(char::from)
+            .collect()
+    });
+    let reply = parse_reply_tags(&parsed_tags);
+    let emotes = tag("emotes").unwrap_or_default();
+
+    let (mut message, parsed_emotes) = format_message(&content, &emotes);
+    // Emote positions are based on the full content, so the mention is only removed after replacing them
+    if let Some(reply) = &reply {
+        message = strip_reply_mention(&message, reply);
+    }
+
+    Some(TwitchResponse {
+        id,
+        timestamp: chrono::Local::now().timestamp_millis(),
+        display_name: tag("display-name").unwrap_or(username),
+        user_color: tag("color").unwrap_or_default(),
+        user_badges: Vec::new(),
+        message,
+        emotes: parsed_emotes,
+        raw_data: RawTwitchResponse {
+            raw_message: content,
+            raw_emotes: emotes,
+        },
+        tags: parsed_tags,
+        reply,
+        action: action.is_some(),
+    })
 }
 
 pub(crate) async fn message_processor(
@@ -34,155 +128,74 @@ pub(crate) async fn message_processor(
     auth_state: State<'_, ImplicitGrantFlow>,
     user_information: UserInformationState,
 ) {
-    let msg = msg.to_string();
-    let id: String = rand::thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(16)
-        .map(char::from)
-        .collect();
-
-    if let Some((tags, username, content)) = parse_twitch_message(&*msg) {
-        let parsed_tags = parse_twitch_tags(&tags);
-        // Get badges from tags, can be none
-        let ws_badges = parsed_tags
+    let channel = parse_irc_channel(&msg);
+    let Some(mut response) = parse_privmsg(&msg) else {
+        return;
+    };
+
+    if let UserInformationState::Regular(user_info) = &user_information {
+        let badges = get_chat_badges(auth_state, user_info).await;
+        // From the tags we get: "broadcaster/1,subscriber/18,glitchcon2020/1" etc.
+        let ws_badges = response
+            .tags
             .iter()
-            .find(|(name, _)| *name == "badges")
-            .map(|(_, value)| value);
-        // Get color from tags, can be none
-        let color = parsed_tags
-            .iter()
-            .find(|(name, _)| *name == "color")
-            .map(|(_, value)| value);
-        // Get display-name from tags, can be none
-        let display_name = parsed_tags
-            .iter()
-            .find(|(name, _)| *name == "display-name")
-            .map(|(_, value)| value);
-        // Get emotes from tags, can be none
-        let emotes = parsed_tags
-            .iter()
-            .find(|(name, _)| *name == "emotes")
-            .map(|(_, value)| value)
-            .unwrap();
-
-        let mut msg = content.clone();
-        let mut parsed_emotes: Vec<(String, String)> = Vec::new();
-        if !emotes.is_empty() {
-            // Calculate the emote position by using the :Number-Number on the emote tag
-            let emotes_vec: Vec<&str> = emotes.split('/').collect();
-
-            let mut emote_positions: Vec<(usize, usize, String)> = Vec::new();
-            for emote in &emotes_vec {
-                let emote_parts: Vec<&str> = emote.split(':').collect();
-                let emote_positions_str = emote_parts[1];
-                let emote_positions_vec: Vec<&str> =
-                    emote_positions_str.split(',').collect();
-                let start_end: Vec<usize> = emote_positions_vec[0]
-                    .split('-')
-                    .map(|x| x.parse().unwrap())
-                    .collect();
-                let emote_id = emote_parts[0];
-                emote_positions.push((start_end[0], start_end[1], emote_id.to_string()));
-            }
+            .find(|(name, _)| name == "badges")
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default();
+
+        for badge_set in badges.data {
+            for (ws_badge_name, ws_badge_id) in ws_badges.split(',').filter_map(|badge| badge.split_once('/')) {
+                if badge_set.set_id != ws_badge_name {
+                    continue;
+                }
 
-            // Get the equivalent emote name on the content
-            for (start, end, emote_id) in emote_positions {
-                // Name, not id
-                let emote_name = &content[start..end + 1];
-                let emote_url = construct_emote_url(&emote_id);
-                parsed_emotes.push((emote_name.to_string(), emote_url.clone()));
-                let emote_image = format!(
-                    "<img id=\"{}\" src=\"{}\" alt=\"{}\" />",
-                    emote_name, emote_url, emote_name
-                );
-                msg = msg.replace(emote_name, &emote_image);
+                if let Some(badge_version) = badge_set.versions.iter().find(|version| version.id == ws_badge_id) {
+                    response.user_badges.push(badge_version.image_url_4x.clone());
+                }
             }
         }
+    }
 
-        match &user_information {
-            UserInformationState::Regular(user_info) => {
-                let badges = get_chat_badges(auth_state.clone(), user_info).await;
-
-                let mut user_badges: Vec<String> = Vec::new();
-                for badge_set in badges.data {
-                    // From ws_badges we get: Some("broadcaster/1,subscriber/18,glitchcon2020/1") etc.
-                    // We need to split the badges by comma and then by slash to get the badge name and version
-                    let ws_badges_vec: Vec<&str> = ws_badges.unwrap().split(',').collect();
-
-                    if ws_badges_vec.is_empty() {
-                        continue;
-                    }
-
-                    for ws_badge in ws_badges_vec {
-                        let ws_badge_parts: Vec<&str> = ws_badge.split('/').collect();
-                        let ws_badge_name = ws_badge_parts[0];
-                        let ws_badge_id = ws_badge_parts[1];
-
-                        let get_matching_badge = badge_set.set_id == ws_badge_name;
-
-                        if get_matching_badge {
-                            let badge_version = badge_set
-                                .versions
-                                .iter()
-                                .find(|version| version.id == ws_badge_id);
-
-                            if let Some(badge_version) = badge_version {
-                                user_badges.push(badge_version.image_url_4x.clone());
-                            }
-                        }
-                    }
-                }
+    ws_server
+        .broadcast(Envelope::new("twitch", channel, "message", json!(response)))
+        .await;
+}
 
-                let response = TwitchResponse {
-                    id,
-                    timestamp: chrono::Local::now().timestamp_millis(),
-                    display_name: display_name.unwrap_or(&username).to_string(),
-                    user_color: color.unwrap_or(&"".into()).to_string(),
-                    user_badges,
-                    message: msg,
-                    emotes: parsed_emotes,
-                    raw_data: RawTwitchResponse {
-                        raw_message: content,
-                        raw_emotes: emotes.to_string(),
-                    },
-                    tags: parsed_tags,
-                };
-
-                // Add "platform" to the websocket response
-                let ws_response = json!({
-                            "platform": "twitch",
-                            "data": response
-                        });
-
-                ws_server
-                    .broadcast(Message::Text(serde_json::to_string(&ws_response).unwrap()))
-                    .await;
-            }
-            UserInformationState::Skipped(_) => {
-                let response = TwitchResponse {
-                    id,
-                    timestamp: chrono::Local::now().timestamp_millis(),
-                    display_name: display_name.unwrap_or(&username).to_string(),
-                    user_color: color.unwrap_or(&"".into()).to_string(),
-                    user_badges: Vec::new(),
-                    message: msg,
-                    emotes: parsed_emotes,
-                    raw_data: RawTwitchResponse {
-                        raw_message: content,
-                        raw_emotes: emotes.to_string(),
-                    },
-                    tags: parsed_tags,
-                };
-
-                let ws_response = json!({
-                            "platform": "twitch",
-                            "data": response
-                        });
-
-                ws_server
-                    .broadcast(Message::Text(serde_json::to_string(&ws_response).unwrap()))
-                    .await;
-            }
-        }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::misc::editor::template::{message_context, Template};
+
+    #[test]
+    fn escapes_the_text_around_emotes() {
+        let line = "@badges=;color=#FF0000;display-name=Viewer;emotes=25:26-30;id=abc :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #channel :<script>alert(1)</script> Kappa \"é\"";
+        let response = parse_privmsg(line).unwrap();
+
+        assert_eq!(
+            response.message,
+            "&lt;script&gt;alert(1)&lt;/script&gt; <img id=\"Kappa\" src=\"https://static-cdn.jtvnw.net/emoticons/v2/25/default/dark/1.0\" alt=\"Kappa\" /> &quot;é&quot;"
+        );
+        let rendered = Template::parse("{formatedMessage}").unwrap().render(&message_context("twitch", Some("channel"), &json!(response)));
+        assert!(!rendered.contains("<script>"));
+
+        // Positions count characters, not bytes
+        let (html, emotes) = format_message("héhé Kappa <b>", "25:5-9");
+        assert!(html.starts_with("héhé <img id=\"Kappa\""));
+        assert!(html.ends_with(" &lt;b&gt;"));
+        assert_eq!(emotes.len(), 1);
+        // Broken positions are ignored instead of panicking
+        assert_eq!(format_message("hi", "25:0-9/1:x-1").0, "hi");
+    }
+
+    #[test]
+    fn unwraps_actions() {
+        let line = "@display-name=Viewer;emotes=25:6-10;id=abc :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #channel :\u{1}ACTION waves Kappa\u{1}";
+        let response = parse_privmsg(line).unwrap();
+        assert!(response.action);
+        assert_eq!(response.raw_data.raw_message, "waves Kappa");
+        assert!(response.message.starts_with("waves <img id=\"Kappa\""));
+
+        let response = parse_privmsg("@id=abc :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #channel :ACTION waves").unwrap();
+        assert!(!response.action);
     }
 }
@@ -1,5 +1,10 @@
+use crate::chat::send_message::{ChatSenders, TwitchSender};
 use crate::chat::twitch::auth::{ImplicitGrantFlow, UserInformation, UserSkippedInformation};
+use crate::chat::twitch::deletions::handle_deletion;
+use crate::chat::twitch::helpers::auth_helpers::{is_login_failure, parse_irc_command};
 use crate::chat::twitch::helpers::message_processor::message_processor;
+use crate::chat::twitch::room_state::{handle_room_state, TwitchRoomStates};
+use crate::chat::twitch::user_notices::{handle_notice, handle_user_notice};
 use crate::chat::websocket::ws_server::WebSocketServer;
 use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
@@ -53,9 +58,23 @@ pub(crate) async fn connect_twitch_websocket(app: AppHandle, stop_flag: Arc<Atom
         .await
         .unwrap_or_else(|e| panic!("Error during handshake: {}", e));
 
-    ws_stream.send("NICK justinfan1234".into()).await.unwrap();
+    // Lines queued by `send_chat_message`, only registered once we're logged in with the user's token
+    let (outgoing_tx, mut outgoing_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let mut outgoing_tx = Some(outgoing_tx);
 
-    loop {
+    match &user_information {
+        UserInformationState::Regular(user_info) if !state.access_token.is_empty() => {
+            ws_stream.send(format!("PASS oauth:{}", state.access_token).into()).await.unwrap();
+            ws_stream.send(format!("NICK {}", user_info.login).into()).await.unwrap();
+        }
+        _ => {
+            // Anonymous login, we can read the chat but not send anything to it
+            outgoing_tx = None;
+            ws_stream.send("NICK justinfan1234".into()).await.unwrap();
+        }
+    }
+
+    'irc: loop {
         // Check if stop flag has been set
         if stop_flag.load(Ordering::Relaxed) {
             println!("Stopping WebSocket connection...");
@@ -68,21 +87,52 @@ pub(crate) async fn connect_twitch_websocket(app: AppHandle, stop_flag: Arc<Atom
         tokio::select! {
             maybe_msg = message_future => {
                 if let Some(Ok(msg)) = maybe_msg {
-                    if msg.to_string().contains("PING") {
-                        ws_stream.send("PONG :tmi.twitch.tv".into()).await.unwrap();
-                    } else if msg.to_string().contains("Welcome, GLHF!") {
-                        ws_stream.send("CAP REQ :twitch.tv/tags".into()).await.unwrap();
-                        match &user_information {
-                            UserInformationState::Skipped(user) => {
-                                let username = user.username.clone();
-                                ws_stream.send(format!("JOIN #{}", username).into()).await.unwrap();
+                    // Twitch may batch several IRC lines on the same frame
+                    for line in msg.to_string().split("\r\n").filter(|line| !line.is_empty()) {
+                        match parse_irc_command(line) {
+                            Some("PING") => {
+                                ws_stream.send("PONG :tmi.twitch.tv".into()).await.unwrap();
+                            }
+                            // "Welcome, GLHF!"
+                            Some("001") => {
+                                ws_stream.send("CAP REQ :twitch.tv/tags twitch.tv/commands".into()).await.unwrap();
+                                match &user_information {
+                                    UserInformationState::Skipped(user) => {
+                                        let username = user.username.clone();
+                                        ws_stream.send(format!("JOIN #{}", username).into()).await.unwrap();
+                                    }
+                                    UserInformationState::Regular(user_info) => {
+                                        ws_stream.send(format!("JOIN #{}", user_info.login).into()).await.unwrap();
+
+                                        if let Some(tx) = outgoing_tx.take() {
+                                            let sender = TwitchSender::new(tx, user_info.login.clone());
+                                            *app.state::<ChatSenders>().twitch.lock().unwrap() = Some(sender);
+                                        }
+                                    }
+                                }
+                            }
+                            Some("NOTICE") if is_login_failure(line) => {
+                                eprintln!("Twitch rejected the user token, reconnect the account to chat again.");
+                                break 'irc;
                             }
-                            UserInformationState::Regular(user_info) => {
-                                ws_stream.send(format!("JOIN #{}", user_info.login).into()).await.unwrap();
+                            Some("ROOMSTATE") => {
+                                handle_room_state(&app, ws_server.deref(), line).await;
                             }
+                            Some("NOTICE") => {
+                                handle_room_state(&app, ws_server.deref(), line).await;
+                                handle_notice(ws_server.deref(), line).await;
+                            }
+                            Some("CLEARMSG") | Some("CLEARCHAT") => {
+                                handle_deletion(ws_server.deref(), line).await;
+                            }
+                            Some("USERNOTICE") => {
+                                handle_user_notice(ws_server.deref(), line).await;
+                            }
+                            Some("PRIVMSG") => {
+                                message_processor(line.to_string(), ws_server.clone().deref(), state.clone(), user_information.clone()).await;
+                            }
+                            _ => {}
                         }
-                    } else if msg.to_string().contains("PRIVMSG") {
-                        message_processor(msg.to_string(), ws_server.clone().deref(), state.clone(), user_information.clone()).await;
                     }
                 } else {
                     // Handle disconnection or error
@@ -90,6 +140,11 @@ pub(crate) async fn connect_twitch_websocket(app: AppHandle, stop_flag: Arc<Atom
                     break;
                 }
             },
+            Some(line) = outgoing_rx.recv() => {
+                if let Err(e) = ws_stream.send(line.into()).await {
+                    eprintln!("Error sending message to Twitch: {}", e);
+                }
+            },
             // If the stop flag is set during the message wait, break out of the loop immediately
             _ = tokio::time::sleep(std::time::Duration::from_millis(100)) => {
                 if stop_flag.load(Ordering::Relaxed) {
@@ -99,6 +154,11 @@ pub(crate) async fn connect_twitch_websocket(app: AppHandle, stop_flag: Arc<Atom
         }
     }
 
+    // Nothing can be sent after this point
+    *app.state::<ChatSenders>().twitch.lock().unwrap() = None;
+    // Neither is the room state up to date anymore
+    app.state::<TwitchRoomStates>().rooms.lock().unwrap().clear();
+
     // Close WebSocket connection
     ws_stream.send("QUIT".into()).await.unwrap();
 }
\ No newline at end of file
@@ -7,3 +7,13 @@ pub(crate) mod auth;
 #[macro_use]
 pub(crate) mod get_user;
 pub(crate) mod helpers;
+pub(crate) mod eventsub;
+
+#[macro_use]
+pub(crate) mod room_state;
+
+#[macro_use]
+pub(crate) mod redemptions;
+
+pub(crate) mod deletions;
+pub(crate) mod user_notices;
@@ -0,0 +1,193 @@
+use crate::chat::twitch::auth::{ImplicitGrantFlow, UserInformation};
+use crate::chat::twitch::eventsub::events::RedemptionEvent;
+use crate::chat::twitch::helpers::auth_helpers::TWITCH_CLIENT_ID;
+use crate::chat::websocket::protocol::Envelope;
+use crate::chat::websocket::start_ws::current_server;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sled::Db;
+use std::ops::Deref;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+
+const REDEMPTIONS_TREE: &str = "redemptions";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct QueuedRedemption {
+    pub(crate) redemption: RedemptionEvent,
+    /// Local timestamp (ms) of when we received it
+    pub(crate) received_at: i64,
+}
+
+fn redemptions_tree(app: &AppHandle) -> Result<sled::Tree, String> {
+    let db: Arc<Db> = app.state::<Arc<Db>>().deref().clone();
+    db.open_tree(REDEMPTIONS_TREE).map_err(|e| e.to_string())
+}
+
+/// Every redemption waiting for approval, oldest first, same order as Twitch's own queue.
+fn read_queue(tree: &sled::Tree) -> Result<Vec<QueuedRedemption>, String> {
+    let mut queue = Vec::new();
+
+    for entry in tree.iter() {
+        let (_, value) = entry.map_err(|e| e.to_string())?;
+        match serde_json::from_slice::<QueuedRedemption>(&value) {
+            Ok(redemption) => queue.push(redemption),
+            Err(e) => eprintln!("Skipping invalid redemption: {}", e),
+        }
+    }
+
+    queue.sort_by_key(|queued| queued.received_at);
+    Ok(queue)
+}
+
+/// Stores a new redemption, false for the ones that skip the queue on Twitch, they arrive already fulfilled.
+fn store_redemption(tree: &sled::Tree, redemption: &RedemptionEvent, received_at: i64) -> Result<bool, String> {
+    if redemption.status != "unfulfilled" {
+        return Ok(false);
+    }
+
+    let queued = QueuedRedemption {
+        redemption: redemption.clone(),
+        received_at,
+    };
+    let serialized_data = serde_json::to_vec(&queued).map_err(|e| e.to_string())?;
+    tree.insert(&redemption.id, serialized_data).map_err(|e| e.to_string())?;
+    tree.flush().map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+/// Removes a redemption from the queue, false if it wasn't there.
+fn remove_redemption(tree: &sled::Tree, redemption_id: &str) -> Result<bool, String> {
+    let removed = tree.remove(redemption_id).map_err(|e| e.to_string())?;
+    tree.flush().map_err(|e| e.to_string())?;
+    Ok(removed.is_some())
+}
+
+fn emit_queue(app: &AppHandle) {
+    match redemptions_tree(app).and_then(|tree| read_queue(&tree)) {
+        Ok(queue) => app
+            .emit_to("main", "twitch::redemption_queue", queue)
+            .unwrap_or_else(|e| eprintln!("Failed to emit redemption queue: {}", e)),
+        Err(e) => eprintln!("Failed to read redemption queue: {}", e),
+    }
+}
+
+/// Adds a new redemption to the queue.
+pub(crate) fn queue_redemption(app: &AppHandle, redemption: &RedemptionEvent) {
+    let received_at = chrono::Local::now().timestamp_millis();
+    match redemptions_tree(app).and_then(|tree| store_redemption(&tree, redemption, received_at)) {
+        Ok(true) => emit_queue(app),
+        Ok(false) => {}
+        Err(e) => eprintln!("Failed to queue redemption: {}", e),
+    }
+}
+
+/// Removes a redemption that was resolved somewhere else (e.g. on the Twitch dashboard).
+pub(crate) fn dequeue_redemption(app: &AppHandle, redemption_id: &str) {
+    match redemptions_tree(app).and_then(|tree| remove_redemption(&tree, redemption_id)) {
+        Ok(true) => emit_queue(app),
+        Ok(false) => {}
+        Err(e) => eprintln!("Failed to remove redemption: {}", e),
+    }
+}
+
+async fn update_redemption_status(app: &AppHandle, id: &str, status: &str) -> Result<QueuedRedemption, String> {
+    let tree = redemptions_tree(app)?;
+    let queued: QueuedRedemption = match tree.get(id).map_err(|e| e.to_string())? {
+        Some(value) => serde_json::from_slice(&value).map_err(|e| e.to_string())?,
+        None => return Err("Redemption not found".into()),
+    };
+
+    let auth = app.state::<ImplicitGrantFlow>();
+    let response = reqwest::Client::new()
+        .patch("https://api.twitch.tv/helix/channel_points/custom_rewards/redemptions")
+        .query(&[
+            ("id", queued.redemption.id.as_str()),
+            ("broadcaster_id", queued.redemption.broadcaster_user_id.as_str()),
+            ("reward_id", queued.redemption.reward.id.as_str()),
+        ])
+        .header("Client-Id", TWITCH_CLIENT_ID)
+        .bearer_auth(&auth.access_token)
+        .json(&json!({ "status": status }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !response.status().is_success() {
+        // Twitch only lets us update rewards created with our own client id, a 403 usually means that
+        let resp = response.text().await.unwrap_or_default();
+        return Err(format!("Failed to update redemption: {}", resp));
+    }
+
+    remove_redemption(&tree, id)?;
+    emit_queue(app);
+
+    Ok(queued)
+}
+
+#[tauri::command]
+pub(crate) async fn get_redemption_queue(app: AppHandle) -> Result<Vec<QueuedRedemption>, String> {
+    read_queue(&redemptions_tree(&app)?)
+}
+
+/// Approves a redemption, which also shows it on the overlays.
+#[tauri::command]
+pub(crate) async fn fulfill_redemption(app: AppHandle, id: String) -> Result<bool, String> {
+    let queued = update_redemption_status(&app, &id, "FULFILLED").await?;
+
+    if let Some(ws_server) = current_server(&app) {
+        let channel = app.try_state::<UserInformation>().map(|user| user.login.clone());
+        ws_server
+            .broadcast(Envelope::new("twitch", channel.as_deref(), "redemption_approved", json!(queued.redemption)))
+            .await;
+    }
+
+    Ok(true)
+}
+
+/// Rejects a redemption, Twitch refunds the points to the viewer.
+#[tauri::command]
+pub(crate) async fn cancel_redemption(app: AppHandle, id: String) -> Result<bool, String> {
+    update_redemption_status(&app, &id, "CANCELED").await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn redemption(id: &str, status: &str) -> RedemptionEvent {
+        serde_json::from_value(json!({
+            "id": id,
+            "broadcaster_user_id": "1337",
+            "user_id": "9001",
+            "user_login": "cooler_user",
+            "user_name": "Cooler_User",
+            "status": status,
+            "reward": { "id": "92af127c-7326-4483-a52b-b0da0be61c01", "title": "Hydrate", "cost": 100 },
+            "redeemed_at": "2020-07-15T17:16:03.17106713Z"
+        }))
+        .unwrap()
+    }
+
+    fn queued_ids(tree: &sled::Tree) -> Vec<String> {
+        read_queue(tree).unwrap().into_iter().map(|queued| queued.redemption.id).collect()
+    }
+
+    #[test]
+    fn queues_unfulfilled_redemptions_oldest_first() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree(REDEMPTIONS_TREE).unwrap();
+
+        // Inserted out of order, the ids sort the other way around
+        assert!(store_redemption(&tree, &redemption("b", "unfulfilled"), 2).unwrap());
+        assert!(store_redemption(&tree, &redemption("a", "unfulfilled"), 3).unwrap());
+        assert!(store_redemption(&tree, &redemption("c", "unfulfilled"), 1).unwrap());
+        assert!(!store_redemption(&tree, &redemption("d", "fulfilled"), 4).unwrap());
+        assert_eq!(queued_ids(&tree), vec!["c", "b", "a"]);
+
+        assert!(remove_redemption(&tree, "b").unwrap());
+        assert!(!remove_redemption(&tree, "b").unwrap());
+        assert_eq!(queued_ids(&tree), vec!["c", "a"]);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::chat::twitch::helpers::auth_helpers::{parse_irc_channel, parse_irc_command, parse_irc_tags, parse_twitch_tags};
+use crate::chat::websocket::protocol::Envelope;
+use crate::chat::websocket::ws_server::WebSocketServer;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub(crate) struct RoomState {
+    pub(crate) channel: String,
+    pub(crate) room_id: String,
+    pub(crate) emote_only: bool,
+    /// Minutes an user has to follow the channel before chatting, None when followers-only mode is off
+    pub(crate) followers_only: Option<i64>,
+    /// Also known as r9k
+    pub(crate) unique_chat: bool,
+    /// Seconds an user has to wait between messages, 0 when slow mode is off
+    pub(crate) slow: u64,
+    pub(crate) subs_only: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct RoomStateChange {
+    pub(crate) state: RoomState,
+    /// Which modes changed: "emote_only", "followers_only", "unique_chat", "slow" or "subs_only"
+    pub(crate) changes: Vec<String>,
+}
+
+/// Room state of every joined channel, keyed by the channel name
+#[derive(Default, Debug)]
+pub(crate) struct TwitchRoomStates {
+    pub(crate) rooms: Mutex<HashMap<String, RoomState>>,
+}
+
+impl RoomState {
+    fn new(channel: &str) -> Self {
+        Self {
+            channel: channel.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// ROOMSTATE only carries the modes that changed (except right after joining), so missing tags are left untouched.
+    fn apply_tags(&mut self, tags: &[(String, String)]) -> Vec<String> {
+        let mut changes = Vec::new();
+
+        for (name, value) in tags {
+            match name.as_str() {
+                "room-id" => self.room_id = value.clone(),
+                "emote-only" => set_mode(&mut self.emote_only, value == "1", "emote_only", &mut changes),
+                "followers-only" => {
+                    let minutes = value.parse::<i64>().ok().filter(|minutes| *minutes >= 0);
+                    set_mode(&mut self.followers_only, minutes, "followers_only", &mut changes)
+                }
+                "r9k" => set_mode(&mut self.unique_chat, value == "1", "unique_chat", &mut changes),
+                "slow" => set_mode(&mut self.slow, value.parse().unwrap_or(0), "slow", &mut changes),
+                "subs-only" => set_mode(&mut self.subs_only, value == "1", "subs_only", &mut changes),
+                _ => {}
+            }
+        }
+
+        changes
+    }
+
+    /// NOTICEs tell when a mode is toggled but not its settings, those come with the ROOMSTATE sent right after.
+    fn apply_notice(&mut self, msg_id: &str) -> Vec<String> {
+        let mut changes = Vec::new();
+
+        match msg_id {
+            "emote_only_on" => set_mode(&mut self.emote_only, true, "emote_only", &mut changes),
+            "emote_only_off" => set_mode(&mut self.emote_only, false, "emote_only", &mut changes),
+            "followers_on_zero" => set_mode(&mut self.followers_only, Some(0), "followers_only", &mut changes),
+            "followers_on" => {
+                let minutes = Some(self.followers_only.unwrap_or(0));
+                set_mode(&mut self.followers_only, minutes, "followers_only", &mut changes)
+            }
+            "followers_off" => set_mode(&mut self.followers_only, None, "followers_only", &mut changes),
+            "r9k_on" => set_mode(&mut self.unique_chat, true, "unique_chat", &mut changes),
+            "r9k_off" => set_mode(&mut self.unique_chat, false, "unique_chat", &mut changes),
+            "slow_off" => set_mode(&mut self.slow, 0, "slow", &mut changes),
+            "subs_on" => set_mode(&mut self.subs_only, true, "subs_only", &mut changes),
+            "subs_off" => set_mode(&mut self.subs_only, false, "subs_only", &mut changes),
+            _ => {}
+        }
+
+        changes
+    }
+}
+
+fn set_mode<T: PartialEq>(current: &mut T, value: T, name: &str, changes: &mut Vec<String>) {
+    if *current != value {
+        *current = value;
+        changes.push(name.to_string());
+    }
+}
+
+/// Updates the room state from a ROOMSTATE or NOTICE line, broadcasting the change if anything changed.
+pub(crate) async fn handle_room_state(app: &AppHandle, ws_server: &WebSocketServer, line: &str) {
+    let Some(channel) = parse_irc_channel(line) else {
+        return;
+    };
+    let tags = parse_twitch_tags(parse_irc_tags(line).unwrap_or(""));
+
+    let change = {
+        let room_states = app.state::<TwitchRoomStates>();
+        let mut rooms = room_states.rooms.lock().unwrap();
+        let room = rooms
+            .entry(channel.to_string())
+            .or_insert_with(|| RoomState::new(channel));
+
+        let changes = match parse_irc_command(line) {
+            Some("ROOMSTATE") => room.apply_tags(&tags),
+            _ => {
+                let msg_id = tags
+                    .iter()
+                    .find(|(name, _)| *name == "msg-id")
+                    .map(|(_, value)| value.as_str())
+                    .unwrap_or("");
+                room.apply_notice(msg_id)
+            }
+        };
+
+        if changes.is_empty() {
+            return;
+        }
+
+        RoomStateChange {
+            state: room.clone(),
+            changes,
+        }
+    };
+
+    app.emit_to("main", "twitch::room_state", change.clone())
+        .unwrap_or_else(|e| eprintln!("Failed to emit room state: {}", e));
+
+    let channel = change.state.channel.clone();
+    ws_server
+        .broadcast(Envelope::new("twitch", Some(&channel), "room_state", json!(change)))
+        .await;
+}
+
+/// Returns the room state of a channel, or of every joined channel when none is given.
+#[tauri::command]
+pub(crate) async fn get_room_state(app: AppHandle, channel: Option<String>) -> Result<Vec<RoomState>, String> {
+    let room_states = app.state::<TwitchRoomStates>();
+    let rooms = room_states.rooms.lock().unwrap();
+
+    match channel {
+        Some(channel) => {
+            let channel = channel.trim_start_matches('#').to_lowercase();
+            rooms
+                .get(&channel)
+                .map(|room| vec![room.clone()])
+                .ok_or(format!("Not connected to #{}", channel))
+        }
+        None => Ok(rooms.values().cloned().collect()),
+    }
+}
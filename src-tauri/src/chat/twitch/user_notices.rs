@@ -0,0 +1,140 @@
+use crate::chat::twitch::helpers::auth_helpers::{parse_irc_channel, parse_irc_tags, parse_twitch_tags, unescape_tag_value};
+use crate::chat::websocket::protocol::Envelope;
+use crate::chat::websocket::ws_server::WebSocketServer;
+use serde_json::json;
+
+/// (msg-id, event) of the USERNOTICEs themes have their own templates for, the others (announcements, bits badges...)
+/// are sent as "system_notice".
+const NOTICE_EVENTS: &[(&str, &str)] = &[
+    ("sub", "subscription"),
+    ("resub", "resubscription"),
+    ("subgift", "gift_subscription"),
+    ("raid", "raid"),
+];
+
+/// Builds the event of a USERNOTICE line, None when it has no msg-id.
+pub(crate) fn parse_user_notice(line: &str) -> Option<Envelope> {
+    let tags = parse_twitch_tags(parse_irc_tags(line).unwrap_or(""));
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|(tag, value)| tag == name && !value.is_empty())
+            .map(|(_, value)| unescape_tag_value(value))
+    };
+    let number = |name: &str| tag(name).and_then(|value| value.parse::<u64>().ok());
+
+    let msg_id = tag("msg-id")?;
+    let event = NOTICE_EVENTS
+        .iter()
+        .find(|(id, _)| *id == msg_id)
+        .map(|(_, event)| *event)
+        .unwrap_or("system_notice");
+    // What the user wrote along with it, e.g. the text of a resubscription or an announcement
+    let message = line
+        .split_once(" USERNOTICE ")
+        .and_then(|(_, params)| params.split_once(" :"))
+        .map(|(_, message)| message)
+        .unwrap_or_default();
+
+    let mut data = json!({
+        "id": tag("id"),
+        "timestamp": chrono::Local::now().timestamp_millis(),
+        "display_name": tag("display-name").or_else(|| tag("login")),
+        "user_login": tag("login"),
+        "user_color": tag("color").unwrap_or_default(),
+        "message": message,
+        // Twitch's own description, e.g. "Viewer subscribed at Tier 1. They've subscribed for 3 months!"
+        "system_message": tag("system-msg"),
+        "tags": tags,
+    });
+    match event {
+        "raid" => data["viewers"] = json!(number("msg-param-viewerCount")),
+        "system_notice" => data["notice"] = json!(msg_id),
+        "gift_subscription" => {
+            data["months"] = json!(number("msg-param-gift-months").unwrap_or(1));
+            data["recipient"] = json!(tag("msg-param-recipient-display-name"));
+        }
+        _ => {
+            data["cumulative_months"] = json!(number("msg-param-cumulative-months"));
+            data["tier"] = json!(tag("msg-param-sub-plan"));
+        }
+    }
+
+    Some(Envelope::new("twitch", parse_irc_channel(line), event, data))
+}
+
+/// Builds the "system_notice" event of a NOTICE line, e.g. "This room is now in slow mode.".
+pub(crate) fn parse_notice(line: &str) -> Option<Envelope> {
+    let tags = parse_twitch_tags(parse_irc_tags(line).unwrap_or(""));
+    let msg_id = tags.iter().find(|(tag, _)| tag == "msg-id").map(|(_, value)| value.clone())?;
+    let message = line.split_once(" NOTICE ")?.1.split_once(" :")?.1;
+    let timestamp = chrono::Local::now().timestamp_millis();
+
+    let data = json!({
+        // NOTICEs have no id of their own
+        "id": format!("{}-{}", msg_id, timestamp),
+        "timestamp": timestamp,
+        "message": message,
+        "system_message": message,
+        "notice": msg_id,
+        "tags": tags,
+    });
+    Some(Envelope::new("twitch", parse_irc_channel(line), "system_notice", data))
+}
+
+/// Handles USERNOTICE, sending subscriptions, gifted subscriptions, raids and Twitch's other notices to the overlays.
+pub(crate) async fn handle_user_notice(ws_server: &WebSocketServer, line: &str) {
+    if let Some(envelope) = parse_user_notice(line) {
+        ws_server.broadcast(envelope).await;
+    }
+}
+
+/// Handles NOTICE, sending what Twitch tells the channel to the overlays.
+pub(crate) async fn handle_notice(ws_server: &WebSocketServer, line: &str) {
+    if let Some(envelope) = parse_notice(line) {
+        ws_server.broadcast(envelope).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builds_subscriptions_and_raids() {
+        let resub = parse_user_notice(
+            "@color=#FF0000;display-name=Viewer;id=abc;login=viewer;msg-id=resub;msg-param-cumulative-months=6;msg-param-sub-plan=1000;\
+             system-msg=Viewer\\ssubscribed\\sat\\sTier\\s1. :tmi.twitch.tv USERNOTICE #channel :<b>six months</b>",
+        )
+        .unwrap();
+        assert_eq!((resub.event.as_str(), resub.channel.as_deref()), ("resubscription", Some("channel")));
+        assert_eq!(resub.data["cumulative_months"], 6);
+        assert_eq!(resub.data["message"], "<b>six months</b>");
+        assert_eq!(resub.data["system_message"], "Viewer subscribed at Tier 1.");
+
+        let raid = parse_user_notice(
+            "@display-name=Raider;id=def;login=raider;msg-id=raid;msg-param-viewerCount=42 :tmi.twitch.tv USERNOTICE #channel",
+        )
+        .unwrap();
+        assert_eq!(raid.event, "raid");
+        assert_eq!(raid.data["viewers"], 42);
+        assert_eq!(raid.data["message"], "");
+
+        let announcement = parse_user_notice(
+            "@display-name=Mod;id=ghi;login=mod;msg-id=announcement;msg-param-color=PRIMARY :tmi.twitch.tv USERNOTICE #channel :hi",
+        )
+        .unwrap();
+        assert_eq!((announcement.event.as_str(), announcement.data["notice"].as_str()), ("system_notice", Some("announcement")));
+        assert_eq!(announcement.data["message"], "hi");
+        assert!(parse_user_notice(":tmi.twitch.tv USERNOTICE #channel :hi").is_none());
+    }
+
+    #[test]
+    fn builds_system_notices() {
+        let notice = parse_notice("@msg-id=slow_on :tmi.twitch.tv NOTICE #channel :This room is now in slow mode. You may send messages every 30 seconds.").unwrap();
+        assert_eq!((notice.event.as_str(), notice.channel.as_deref()), ("system_notice", Some("channel")));
+        assert_eq!(notice.data["message"], "This room is now in slow mode. You may send messages every 30 seconds.");
+        assert_eq!(notice.data["notice"], "slow_on");
+
+        assert!(parse_notice(":tmi.twitch.tv NOTICE * :Login authentication failed").is_none());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::chat::websocket::start_ws::current_server;
+use crate::chat::websocket::ws_server::ServerMetrics;
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use std::ops::Deref;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+
+const SETTINGS_TREE: &str = "settings";
+const WEBSOCKET_KEY: &str = "websocket";
+// The history is allocated up front, this keeps a typo from reserving gigabytes
+const MAX_HISTORY_SIZE: usize = 10_000;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub(crate) struct WebSocketConfig {
+    pub(crate) bind_address: String,
+    pub(crate) port: u16,
+    /// How many of the following ports we try when the configured one is taken
+    pub(crate) port_fallback_attempts: u16,
+    /// Browser origins allowed to connect without the token
+    pub(crate) allowed_origins: Vec<String>,
+    /// When set, clients outside the allowed origins (and tools that don't send one) must add "?token=" to the URL
+    pub(crate) token: Option<String>,
+    /// How many recent events are kept to replay to new clients, 0 disables it
+    pub(crate) history_size: usize,
+    /// Events older than this aren't replayed anymore
+    pub(crate) history_max_age_seconds: u64,
+    /// Events waiting to be sent to a single client, past this it's considered slow
+    pub(crate) client_queue_size: usize,
+    pub(crate) slow_client_policy: SlowClientPolicy,
+}
+
+/// What happens to a client whose queue is full, e.g. a browser source that's hidden and stalled
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SlowClientPolicy {
+    /// Skips the events it can't keep up with
+    #[default]
+    Drop,
+    /// Closes the connection, the overlay catches up with the history when it reconnects
+    Disconnect,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: "127.0.0.1".to_string(),
+            port: 9888,
+            port_fallback_attempts: 10,
+            allowed_origins: vec![
+                // WebChat window (tauri-plugin-localhost)
+                "http://localhost:9889".to_string(),
+                // Main window, dev and production
+                "http://localhost:3000".to_string(),
+                "tauri://localhost".to_string(),
+                "http://tauri.localhost".to_string(),
+                "https://tauri.localhost".to_string(),
+            ],
+            token: None,
+            history_size: 100,
+            history_max_age_seconds: 600,
+            client_queue_size: 256,
+            slow_client_policy: SlowClientPolicy::Drop,
+        }
+    }
+}
+
+impl WebSocketConfig {
+    /// Checks the Origin header and token of a new client, `port` is the one we actually bound to.
+    pub(crate) fn is_client_allowed(&self, origin: Option<&str>, token: Option<&str>, port: u16) -> bool {
+        let token_matches = match &self.token {
+            Some(expected) if !expected.is_empty() => token == Some(expected.as_str()),
+            _ => false,
+        };
+        if token_matches {
+            return true;
+        }
+
+        match origin {
+            Some(origin) => {
+                let origin = origin.trim_end_matches('/');
+                // Pages served by the chat server itself, like the overlays
+                let same_origin = [
+                    format!("http://localhost:{}", port),
+                    format!("http://127.0.0.1:{}", port),
+                    format!("http://{}:{}", self.bind_address, port),
+                ];
+
+                same_origin.iter().any(|allowed| allowed == origin)
+                    || self.allowed_origins.iter().any(|allowed| allowed.trim_end_matches('/') == origin)
+            }
+            // Not a browser, only allowed when there's no token to check
+            None => self.token.as_deref().map(|token| token.is_empty()).unwrap_or(true),
+        }
+    }
+}
+
+pub(crate) fn load_websocket_config(app: &AppHandle) -> WebSocketConfig {
+    let db: Arc<Db> = app.state::<Arc<Db>>().deref().clone();
+
+    db.open_tree(SETTINGS_TREE)
+        .ok()
+        .and_then(|tree| tree.get(WEBSOCKET_KEY).ok().flatten())
+        .and_then(|config| serde_json::from_slice(&config).ok())
+        .unwrap_or_default()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct WebSocketStatus {
+    pub(crate) running: bool,
+    /// Address the server is actually bound to, may differ from the configured port if it was taken
+    pub(crate) address: Option<String>,
+    pub(crate) metrics: ServerMetrics,
+}
+
+#[tauri::command]
+pub(crate) async fn get_websocket_config(app: AppHandle) -> Result<WebSocketConfig, String> {
+    Ok(load_websocket_config(&app))
+}
+
+/// Saves the server configuration, takes effect the next time the chat is started.
+#[tauri::command]
+pub(crate) async fn set_websocket_config(app: AppHandle, config: WebSocketConfig) -> Result<bool, String> {
+    if config.bind_address.parse::<std::net::IpAddr>().is_err() && config.bind_address != "localhost" {
+        return Err(format!("Invalid bind address: {}", config.bind_address));
+    }
+    if config.port == 0 {
+        return Err("Port must be greater than 0".into());
+    }
+    if config.client_queue_size == 0 {
+        return Err("The client queue size must be greater than 0".into());
+    }
+    if config.history_size > MAX_HISTORY_SIZE {
+        return Err(format!("The history can't keep more than {} events", MAX_HISTORY_SIZE));
+    }
+
+    let db: Arc<Db> = app.state::<Arc<Db>>().deref().clone();
+    let tree = db.open_tree(SETTINGS_TREE).map_err(|e| e.to_string())?;
+    let serialized_data = serde_json::to_vec(&config).map_err(|e| e.to_string())?;
+
+    tree.insert(WEBSOCKET_KEY, serialized_data).map_err(|e| e.to_string())?;
+    tree.flush().map_err(|e| e.to_string())?;
+
+    Ok(true)
+}
+
+#[tauri::command]
+pub(crate) async fn get_websocket_status(app: AppHandle) -> Result<WebSocketStatus, String> {
+    let (address, metrics) = match current_server(&app) {
+        Some(ws_server) => (ws_server.local_addr().await, ws_server.metrics().await),
+        None => (None, ServerMetrics::default()),
+    };
+
+    Ok(WebSocketStatus {
+        running: address.is_some(),
+        address: address.map(|address| address.to_string()),
+        metrics,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_allowed_origins_or_token_holders_connect() {
+        let mut config = WebSocketConfig::default();
+
+        assert!(config.is_client_allowed(Some("http://localhost:9889"), None, 9888));
+        assert!(config.is_client_allowed(Some("http://localhost:9890/"), None, 9890));
+        assert!(!config.is_client_allowed(Some("https://evil.example"), None, 9888));
+        // Tools that aren't browsers don't send an Origin
+        assert!(config.is_client_allowed(None, None, 9888));
+
+        config.token = Some("secret".to_string());
+        assert!(!config.is_client_allowed(None, None, 9888));
+        assert!(!config.is_client_allowed(Some("https://evil.example"), Some("wrong"), 9888));
+        assert!(config.is_client_allowed(Some("https://evil.example"), Some("secret"), 9888));
+        assert!(config.is_client_allowed(Some("tauri://localhost"), None, 9888));
+    }
+}
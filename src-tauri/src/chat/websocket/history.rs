@@ -0,0 +1,117 @@
+use crate::chat::websocket::protocol::Envelope;
+use crate::chat::websocket::ws_server::OutgoingEvent;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Recent events, replayed to overlays when they (re)connect.
+pub(crate) struct EventHistory {
+    events: VecDeque<(Instant, Arc<OutgoingEvent>)>,
+    max_size: usize,
+    max_age: Duration,
+    last_seq: u64,
+}
+
+impl EventHistory {
+    pub(crate) fn new(max_size: usize, max_age: Duration) -> Self {
+        Self {
+            events: VecDeque::with_capacity(max_size),
+            max_size,
+            max_age,
+            last_seq: 0,
+        }
+    }
+
+    /// Seq of the last broadcasted event, buffered or not
+    pub(crate) fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    pub(crate) fn next_seq(&mut self) -> u64 {
+        self.last_seq += 1;
+        self.last_seq
+    }
+
+    pub(crate) fn push(&mut self, event: Arc<OutgoingEvent>) {
+        self.push_at(event, Instant::now());
+    }
+
+    fn push_at(&mut self, event: Arc<OutgoingEvent>, now: Instant) {
+        if self.max_size == 0 {
+            return;
+        }
+
+        self.events.push_back((now, event));
+        while self.events.len() > self.max_size {
+            self.events.pop_front();
+        }
+        self.prune(now);
+    }
+
+    /// Buffered events after `since`, oldest first.
+    pub(crate) fn since(&mut self, since: Option<u64>) -> Vec<Arc<OutgoingEvent>> {
+        self.prune(Instant::now());
+
+        let since = since.unwrap_or(0);
+        self.events
+            .iter()
+            .filter(|(_, event)| event.envelope.seq > since)
+            .map(|(_, event)| Arc::clone(event))
+            .collect()
+    }
+
+    /// Drops buffered events, used when messages are deleted so they don't come back on a replay.
+    pub(crate) fn remove(&mut self, predicate: impl Fn(&Envelope) -> bool) -> usize {
+        let before = self.events.len();
+        self.events.retain(|(_, event)| !predicate(&event.envelope));
+        before - self.events.len()
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some((received_at, _)) = self.events.front() {
+            if now.duration_since(*received_at) <= self.max_age {
+                break;
+            }
+            self.events.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn event(history: &mut EventHistory, id: &str) -> Arc<OutgoingEvent> {
+        let mut envelope = Envelope::new("twitch", Some("channel"), "message", json!({ "id": id }));
+        envelope.seq = history.next_seq();
+        Arc::new(OutgoingEvent::new(envelope).unwrap())
+    }
+
+    #[test]
+    fn keeps_recent_events_and_applies_deletions() {
+        let mut history = EventHistory::new(3, Duration::from_secs(60));
+        let start = Instant::now();
+
+        for (i, id) in ["a", "b", "c", "d"].iter().enumerate() {
+            let event = event(&mut history, id);
+            history.push_at(event, start + Duration::from_secs(i as u64));
+        }
+
+        let ids = |events: Vec<Arc<OutgoingEvent>>| -> Vec<String> {
+            events.iter().map(|event| event.envelope.data["id"].as_str().unwrap().to_string()).collect()
+        };
+        // Oldest one is over the size limit
+        assert_eq!(ids(history.since(None)), vec!["b", "c", "d"]);
+        assert_eq!(ids(history.since(Some(3))), vec!["d"]);
+
+        assert_eq!(history.remove(|envelope| envelope.data["id"] == "c"), 1);
+        assert_eq!(ids(history.since(None)), vec!["b", "d"]);
+
+        // Everything but "d" is too old by then
+        let event = event(&mut history, "e");
+        history.push_at(event, start + Duration::from_secs(63));
+        assert_eq!(ids(history.since(None)), vec!["d", "e"]);
+        assert_eq!(history.last_seq(), 5);
+    }
+}
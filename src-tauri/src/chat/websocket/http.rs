@@ -0,0 +1,543 @@
+use crate::chat::websocket::protocol::{Subscription, PROTOCOL_VERSION};
+use crate::chat::websocket::ws_server::{OutgoingEvent, WebSocketServer, HEARTBEAT_INTERVAL, SEND_TIMEOUT};
+use crate::misc::editor::overlay::{overlay_asset, render_overlay_page};
+use crate::misc::editor::template::{render_event, ThemeTemplates};
+use crate::misc::editor::theme_assets::read_asset;
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::TcpStream;
+
+// Requests are only a line and a few headers, anything bigger isn't for us
+const MAX_HEAD_SIZE: usize = 8192;
+const MAX_HEADERS: usize = 64;
+const DEFAULT_MESSAGES_LIMIT: usize = 50;
+
+pub(crate) struct HttpRequest {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    pub(crate) query: Option<String>,
+    pub(crate) headers: Vec<(String, String)>,
+}
+
+impl HttpRequest {
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub(crate) fn query_param(&self, name: &str) -> Option<String> {
+        self.query.as_ref().and_then(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        })
+    }
+
+    pub(crate) fn is_websocket_upgrade(&self) -> bool {
+        self.header("Upgrade")
+            .map(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+            .unwrap_or(false)
+    }
+
+    /// Same filters as the "subscribe" message, as comma separated lists (e.g. "?events=follow,redemption")
+    pub(crate) fn subscription(&self) -> Subscription {
+        let list = |name: &str| -> Vec<String> {
+            self.query_param(name)
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(|item| item.trim().to_string())
+                        .filter(|item| !item.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        Subscription {
+            platforms: list("platforms"),
+            channels: list("channels"),
+            events: list("events"),
+        }
+    }
+}
+
+/// Decodes the %XX escapes of a path segment, None when the result isn't UTF-8.
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok()?;
+            let byte = u8::from_str_radix(hex, 16).ok()?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+/// Parses the request line and headers, None while they haven't all arrived yet.
+pub(crate) fn parse_request_head(buffer: &[u8]) -> Result<Option<HttpRequest>, String> {
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut request = httparse::Request::new(&mut headers);
+    match request.parse(buffer) {
+        Ok(httparse::Status::Complete(_)) => {}
+        Ok(httparse::Status::Partial) => return Ok(None),
+        Err(e) => return Err(format!("Malformed request: {}", e)),
+    }
+
+    let target = request.path.unwrap_or_default();
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+
+    let headers = request
+        .headers
+        .iter()
+        .map(|header| {
+            let value = std::str::from_utf8(header.value).map_err(|_| format!("Malformed header: {}", header.name))?;
+            Ok((header.name.to_string(), value.trim().to_string()))
+        })
+        .collect::<Result<_, String>>()?;
+
+    Ok(Some(HttpRequest { method: request.method.unwrap_or_default().to_string(), path, query, headers }))
+}
+
+/// Reads until the request head is complete. The bytes read are returned too, the WebSocket handshake reads them again.
+pub(crate) async fn read_request(stream: &mut TcpStream) -> Result<(HttpRequest, Vec<u8>), String> {
+    let mut buffer = Vec::with_capacity(1024);
+
+    loop {
+        let len = stream.read_buf(&mut buffer).await.map_err(|e| e.to_string())?;
+        if len == 0 {
+            return Err("Connection closed".into());
+        }
+
+        if let Some(request) = parse_request_head(&buffer)? {
+            return Ok((request, buffer));
+        }
+        if buffer.len() >= MAX_HEAD_SIZE {
+            return Err("Request head too large".into());
+        }
+    }
+}
+
+/// A connection that gives back the bytes already read from it before reading more.
+pub(crate) struct BufferedStream {
+    buffer: Vec<u8>,
+    position: usize,
+    stream: TcpStream,
+}
+
+impl BufferedStream {
+    pub(crate) fn new(buffer: Vec<u8>, stream: TcpStream) -> Self {
+        Self { buffer, position: 0, stream }
+    }
+}
+
+impl AsyncRead for BufferedStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.position < this.buffer.len() {
+            let len = buf.remaining().min(this.buffer.len() - this.position);
+            buf.put_slice(&this.buffer[this.position..this.position + len]);
+            this.position += len;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for BufferedStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, headers: &[(&str, String)], body: impl AsRef<[u8]>) {
+    let body = body.as_ref();
+    let mut head = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    let mut response = head.into_bytes();
+    response.extend_from_slice(body);
+    if let Err(e) = stream.write_all(&response).await {
+        eprintln!("Failed to send HTTP response: {}", e);
+    }
+    let _ = stream.shutdown().await;
+}
+
+async fn write_json(stream: &mut TcpStream, cors: &[(&str, String)], value: Value) {
+    let mut headers = vec![("Content-Type", "application/json".to_string())];
+    headers.extend_from_slice(cors);
+    write_response(stream, "200 OK", &headers, &value.to_string()).await;
+}
+
+/// With a theme, the events it shows also get their `html`, rendered with the theme's template for them.
+fn sse_frame(event: &OutgoingEvent, templates: Option<&ThemeTemplates>) -> String {
+    let html = templates.and_then(|templates| render_event(templates, &event.envelope));
+    let data = match html {
+        Some(html) => {
+            let mut envelope = json!(event.envelope);
+            envelope["html"] = json!(html);
+            Cow::Owned(envelope.to_string())
+        }
+        None => Cow::Borrowed(&*event.text),
+    };
+    format!("id: {}\nevent: {}\ndata: {}\n\n", event.envelope.seq, event.envelope.event, data)
+}
+
+/// Overlay pages and their files are only the code of a theme, they're served without the token so browser sources
+/// and the relative URLs in them keep working. The events these pages load still need it.
+fn is_public_route(path: &str) -> bool {
+    ["/overlay/", "/overlay-assets/", "/icons/brands/"]
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+}
+
+/// Handles everything that isn't a WebSocket upgrade:
+/// - `GET /events`: Server-Sent Events stream of the same events the WebSocket clients get, `?theme=` renders the messages
+/// - `GET /api/messages`: buffered events as JSON, `?since=` and `?limit=` page through them
+/// - `GET /api/status`: whether the server runs, where, and its metrics
+/// - `GET /overlay/<theme>`: page rendering a saved theme, meant to be added to OBS as a browser source
+/// - `GET /overlay/<theme>/assets/<name>`: fonts, images and sounds of a theme
+///
+/// The overlay routes are public, see `is_public_route`.
+pub(crate) async fn handle_http(server: Arc<WebSocketServer>, mut stream: TcpStream, request: HttpRequest, port: u16) {
+    let origin = request.header("Origin").map(|origin| origin.to_string());
+    let token = request.query_param("token");
+    if !is_public_route(&request.path) && !server.config().is_client_allowed(origin.as_deref(), token.as_deref(), port) {
+        eprintln!("Rejected HTTP client from origin {:?}", origin);
+        write_response(&mut stream, "403 Forbidden", &[], "Origin not allowed").await;
+        return;
+    }
+
+    // Browsers only read the response of an allowed cross-origin request with this
+    let cors: Vec<(&str, String)> = origin
+        .map(|origin| vec![("Access-Control-Allow-Origin", origin)])
+        .unwrap_or_default();
+
+    if request.method != "GET" {
+        write_response(&mut stream, "405 Method Not Allowed", &[("Allow", "GET".to_string())], "").await;
+        return;
+    }
+
+    let subscription = request.subscription();
+    let since = request.query_param("since").and_then(|since| since.parse::<u64>().ok());
+
+    match request.path.trim_end_matches('/') {
+        "/events" => {
+            // EventSource sends the id of the last event it got when it reconnects
+            let since = request
+                .header("Last-Event-ID")
+                .and_then(|id| id.parse::<u64>().ok())
+                .or(since);
+            let template = match request.query_param("theme").map(|theme| server.templates().get(&theme)) {
+                Some(Ok(template)) => Some(template),
+                Some(Err(e)) => {
+                    write_response(&mut stream, "404 Not Found", &cors, &e).await;
+                    return;
+                }
+                None => None,
+            };
+            stream_events(server, stream, cors, subscription, since, template).await;
+        }
+        "/api/messages" => {
+            let limit = request
+                .query_param("limit")
+                .and_then(|limit| limit.parse::<usize>().ok())
+                .unwrap_or(DEFAULT_MESSAGES_LIMIT);
+            let mut events: Vec<Value> = server
+                .recent_events(since)
+                .await
+                .iter()
+                .filter(|event| subscription.matches(&event.envelope))
+                .map(|event| json!(event.envelope))
+                .collect();
+            // Keep the latest ones, still oldest first
+            if events.len() > limit {
+                events = events.split_off(events.len() - limit);
+            }
+
+            write_json(&mut stream, &cors, json!(events)).await;
+        }
+        "/api/status" => {
+            let address = server.local_addr().await;
+            let status = json!({
+                "running": address.is_some(),
+                "address": address.map(|address| address.to_string()),
+                "version": PROTOCOL_VERSION,
+                "metrics": server.metrics().await,
+            });
+
+            write_json(&mut stream, &cors, status).await;
+        }
+        path if path.starts_with("/overlay/") && path["/overlay/".len()..].contains('/') => {
+            let (theme, asset) = path["/overlay/".len()..].split_once('/').unwrap();
+            let asset = match (percent_decode(theme), asset.strip_prefix("assets/").and_then(percent_decode)) {
+                (Some(theme), Some(asset)) => read_asset(&theme, &asset),
+                _ => Err("Not found".to_string()),
+            };
+
+            match asset {
+                Ok((content_type, content)) => {
+                    let mut headers = vec![("Content-Type", content_type.to_string()), ("Cache-Control", "no-cache".to_string())];
+                    // Fonts are fetched with CORS, even from the same page
+                    headers.extend_from_slice(&cors);
+                    write_response(&mut stream, "200 OK", &headers, content).await;
+                }
+                Err(e) => write_response(&mut stream, "404 Not Found", &cors, e).await,
+            }
+        }
+        path if path.starts_with("/overlay/") => {
+            let page = percent_decode(&path["/overlay/".len()..])
+                .ok_or_else(|| "Invalid theme name".to_string())
+                // A theme that doesn't parse would only fail once messages come in
+                .and_then(|theme| server.templates().get(&theme).and_then(|_| render_overlay_page(&theme)));
+
+            match page {
+                Ok(page) => {
+                    let headers = [("Content-Type", "text/html; charset=utf-8".to_string())];
+                    write_response(&mut stream, "200 OK", &headers, &page).await;
+                }
+                Err(e) => write_response(&mut stream, "404 Not Found", &cors, &e).await,
+            }
+        }
+        // The platform badges of the rendered messages point to the same paths as in the app
+        path if path.starts_with("/overlay-assets/") || path.starts_with("/icons/brands/") => {
+            match overlay_asset(&path[path.rfind('/').unwrap() + 1..]) {
+                Some((content_type, content)) => {
+                    let headers = [("Content-Type", content_type.to_string()), ("Cache-Control", "max-age=3600".to_string())];
+                    write_response(&mut stream, "200 OK", &headers, content).await;
+                }
+                None => write_response(&mut stream, "404 Not Found", &cors, "Not found").await,
+            }
+        }
+        _ => write_response(&mut stream, "404 Not Found", &cors, "Not found").await,
+    }
+}
+
+async fn stream_events(
+    server: Arc<WebSocketServer>,
+    mut stream: TcpStream,
+    cors: Vec<(&str, String)>,
+    subscription: Subscription,
+    since: Option<u64>,
+    template: Option<Arc<ThemeTemplates>>,
+) {
+    let mut head = String::from("HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n");
+    for (name, value) in &cors {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    let queue = server.register_client(since).await;
+    let mut rx = queue.rx;
+    let mut shutdown_signal = server.shutdown_receiver();
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+
+    let mut initial = head;
+    for event in queue.replay.iter().filter(|event| subscription.matches(&event.envelope)) {
+        initial.push_str(&sse_frame(event, template.as_deref()));
+    }
+    if stream.write_all(initial.as_bytes()).await.is_err() {
+        return;
+    }
+
+    loop {
+        let frame = tokio::select! {
+            event = rx.recv() => match event {
+                Some(event) if event.envelope.seq > queue.last_seq && subscription.matches(&event.envelope) => {
+                    sse_frame(&event, template.as_deref())
+                }
+                Some(_) => continue,
+                None => break,
+            },
+            // Comments keep proxies from closing the connection, and tell us when the client is gone
+            _ = heartbeat.tick() => ": ping\n\n".to_string(),
+            _ = queue.disconnect.notified() => break,
+            _ = WebSocketServer::stopping(&mut shutdown_signal) => break,
+        };
+
+        match tokio::time::timeout(SEND_TIMEOUT, stream.write_all(frame.as_bytes())).await {
+            Ok(Ok(_)) => {}
+            _ => break,
+        }
+    }
+
+    let _ = stream.shutdown().await;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chat::websocket::config::WebSocketConfig;
+    use crate::chat::websocket::protocol::Envelope;
+    use std::time::Duration;
+
+    #[test]
+    fn parses_requests_and_filters() {
+        let request = parse_request_head(
+            b"GET /events?events=follow,%20redemption&platforms=twitch HTTP/1.1\r\nHost: localhost:9888\r\nlast-event-id: 42\r\n\r\n",
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/events");
+        assert_eq!(request.header("Last-Event-ID"), Some("42"));
+        assert!(!request.is_websocket_upgrade());
+        assert_eq!(
+            request.subscription(),
+            Subscription {
+                platforms: vec!["twitch".to_string()],
+                channels: vec![],
+                events: vec!["follow".to_string(), "redemption".to_string()],
+            }
+        );
+
+        let upgrade = parse_request_head(b"GET / HTTP/1.1\r\nUpgrade: WebSocket\r\nConnection: Upgrade\r\n\r\n").unwrap().unwrap();
+        assert!(upgrade.is_websocket_upgrade());
+
+        assert!(parse_request_head(b"GET / HTTP/1.1\r\nHost: incomplete").unwrap().is_none());
+        assert_eq!(percent_decode("my%20theme").as_deref(), Some("my theme"));
+        assert_eq!(percent_decode("%2e%2e%2Fsecrets").as_deref(), Some("../secrets"));
+        assert_eq!(percent_decode("bad%zz"), None);
+        assert!(parse_request_head(b"\x16\x03\x01 garbage\r\n\r\n").is_err());
+        assert!(parse_request_head(b"GET / HTTP/1.1\r\nX-Name: \xff\r\n\r\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn overlay_pages_load_without_the_token() {
+        let config = WebSocketConfig {
+            port: 0,
+            port_fallback_attempts: 0,
+            token: Some("secret".to_string()),
+            ..WebSocketConfig::default()
+        };
+        let server = Arc::new(WebSocketServer::new(config));
+        let listener = server.bind().await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let running = Arc::clone(&server);
+        tokio::spawn(async move { running.run(listener).await.unwrap() });
+
+        let status = |path: &'static str| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, addr);
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = vec![0; 12];
+            stream.read_exact(&mut response).await.unwrap();
+            String::from_utf8(response).unwrap()
+        };
+
+        assert_eq!(status("/overlay-assets/tailwind.js").await, "HTTP/1.1 200");
+        assert_eq!(status("/icons/brands/twitch_glitch.svg").await, "HTTP/1.1 200");
+        // Past the token check, there's just no such theme
+        assert_eq!(status("/overlay/missing%20theme").await, "HTTP/1.1 404");
+        assert_eq!(status("/events").await, "HTTP/1.1 403");
+        assert_eq!(status("/api/status").await, "HTTP/1.1 403");
+        assert_eq!(status("/api/status?token=secret").await, "HTTP/1.1 200");
+        assert_eq!(status("/events?token=secret").await, "HTTP/1.1 200");
+
+        server.close().await;
+    }
+
+    #[tokio::test]
+    async fn reads_requests_sent_in_pieces() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = tokio::spawn(async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(b"GET /events?theme=mine HT").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            stream.write_all(b"TP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+            stream
+        });
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let (request, head) = read_request(&mut stream).await.unwrap();
+        assert_eq!((request.path.as_str(), request.query_param("theme").as_deref()), ("/events", Some("mine")));
+
+        // The handshake gets the head back before anything else the client sends
+        let _client = client.await.unwrap();
+        let mut buffered = BufferedStream::new(head, stream);
+        let mut replayed = vec![0; 52];
+        buffered.read_exact(&mut replayed).await.unwrap();
+        assert_eq!(replayed, b"GET /events?theme=mine HTTP/1.1\r\nHost: localhost\r\n\r\n");
+    }
+
+    #[tokio::test]
+    async fn serves_status_messages_and_events() {
+        let config = WebSocketConfig { port: 0, port_fallback_attempts: 0, ..WebSocketConfig::default() };
+        let server = Arc::new(WebSocketServer::new(config));
+        let listener = server.bind().await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let running = Arc::clone(&server);
+        tokio::spawn(async move { running.run(listener).await.unwrap() });
+
+        server.broadcast(Envelope::new("twitch", None, "follow", json!({ "user_login": "someone" }))).await;
+
+        let get = |path: &'static str| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, addr);
+            stream.write_all(request.as_bytes()).await.unwrap();
+            stream
+        };
+
+        let mut response = String::new();
+        get("/api/status").await.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\"running\":true"));
+
+        response.clear();
+        get("/api/messages?events=follow").await.read_to_string(&mut response).await.unwrap();
+        assert!(response.contains("\"user_login\":\"someone\""));
+
+        response.clear();
+        get("/nothing").await.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 404"));
+
+        // The stream replays the follow, then gets the live message
+        let mut events = get("/events?events=follow,message").await;
+        server.broadcast(Envelope::new("twitch", None, "room_state", json!({}))).await;
+        server.broadcast(Envelope::new("twitch", None, "message", json!({ "message": "hi" }))).await;
+
+        let mut received = String::new();
+        let mut buffer = [0; 4096];
+        while !received.contains("event: message") {
+            let len = tokio::time::timeout(Duration::from_secs(5), events.read(&mut buffer)).await.unwrap().unwrap();
+            assert!(len > 0, "stream closed early: {}", received);
+            received.push_str(&String::from_utf8_lossy(&buffer[..len]));
+        }
+        assert!(received.contains("text/event-stream"));
+        assert!(received.contains("id: 1\nevent: follow\n"));
+        assert!(!received.contains("room_state"));
+
+        server.close().await;
+    }
+}
@@ -1,2 +1,6 @@
 pub(crate) mod ws_server;
-pub(crate) mod start_ws;
\ No newline at end of file
+pub(crate) mod start_ws;
+pub(crate) mod config;
+pub(crate) mod protocol;
+pub(crate) mod history;
+pub(crate) mod http;
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Bumped whenever a change could break existing overlays
+pub(crate) const PROTOCOL_VERSION: u32 = 1;
+pub(crate) const CAPABILITIES: &[&str] = &["subscribe", "ping", "history"];
+
+/// Every event sent to the overlays. It keeps the old "platform", "event" and "data" fields,
+/// so overlays written before the protocol existed keep working.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct Envelope {
+    #[serde(rename = "type")]
+    pub(crate) message_type: String,
+    pub(crate) version: u32,
+    /// Set by the server when broadcasting, clients pass the last one they saw to "replay"
+    #[serde(default)]
+    pub(crate) seq: u64,
+    pub(crate) platform: String,
+    /// Twitch channel or YouTube video the event comes from, None for events that aren't tied to one
+    pub(crate) channel: Option<String>,
+    pub(crate) event: String,
+    pub(crate) data: Value,
+}
+
+impl Envelope {
+    pub(crate) fn new(platform: &str, channel: Option<&str>, event: &str, data: Value) -> Self {
+        Self {
+            message_type: "event".to_string(),
+            version: PROTOCOL_VERSION,
+            seq: 0,
+            platform: platform.to_string(),
+            channel: channel.map(|channel| channel.to_string()),
+            event: event.to_string(),
+            data,
+        }
+    }
+}
+
+/// Messages the server sends on its own, outside of the events
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum ServerMessage {
+    Hello {
+        version: u32,
+        capabilities: Vec<String>,
+    },
+    Subscribed {
+        subscription: Subscription,
+    },
+    Pong {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        nonce: Option<Value>,
+    },
+    /// Sent after the replayed events, anything with a higher seq is live
+    ReplayDone {
+        last_seq: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl ServerMessage {
+    pub(crate) fn hello() -> Self {
+        Self::Hello {
+            version: PROTOCOL_VERSION,
+            capabilities: CAPABILITIES.iter().map(|capability| capability.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum ClientMessage {
+    Subscribe(Subscription),
+    Ping {
+        #[serde(default)]
+        nonce: Option<Value>,
+    },
+    /// Asks for the buffered events again, only the ones after `since` when it's set
+    Replay {
+        #[serde(default)]
+        since: Option<u64>,
+    },
+}
+
+/// Filters what a client receives, an empty list lets everything through.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub(crate) struct Subscription {
+    pub(crate) platforms: Vec<String>,
+    pub(crate) channels: Vec<String>,
+    pub(crate) events: Vec<String>,
+}
+
+impl Subscription {
+    pub(crate) fn matches(&self, envelope: &Envelope) -> bool {
+        let allows = |filter: &Vec<String>, value: &str| {
+            filter.is_empty() || filter.iter().any(|allowed| allowed.eq_ignore_ascii_case(value))
+        };
+
+        allows(&self.platforms, &envelope.platform)
+            && allows(&self.events, &envelope.event)
+            // Events without a channel (e.g. EventSub ones) aren't filtered by channel
+            && envelope.channel.as_deref().map(|channel| allows(&self.channels, channel)).unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn subscriptions_filter_events() {
+        let message = Envelope::new("twitch", Some("SomeChannel"), "message", json!({}));
+        let follow = Envelope::new("twitch", None, "follow", json!({}));
+        let youtube = Envelope::new("youtube", Some("dQw4w9WgXcQ"), "message", json!({}));
+
+        let everything = Subscription::default();
+        assert!(everything.matches(&message) && everything.matches(&follow) && everything.matches(&youtube));
+
+        let chat_box = Subscription {
+            platforms: vec![],
+            channels: vec!["somechannel".to_string()],
+            events: vec!["message".to_string()],
+        };
+        assert!(chat_box.matches(&message));
+        assert!(!chat_box.matches(&follow));
+        assert!(!chat_box.matches(&youtube));
+
+        let alerts = Subscription {
+            platforms: vec!["twitch".to_string()],
+            channels: vec!["otherchannel".to_string()],
+            events: vec!["follow".to_string()],
+        };
+        assert!(alerts.matches(&follow));
+        assert!(!alerts.matches(&message));
+    }
+
+    #[test]
+    fn envelope_keeps_the_old_fields() {
+        let envelope = Envelope::new("youtube", None, "message", json!({ "id": "1" }));
+        let serialized = serde_json::to_value(&envelope).unwrap();
+
+        assert_eq!(serialized["type"], "event");
+        assert_eq!(serialized["platform"], "youtube");
+        assert_eq!(serialized["event"], "message");
+        assert_eq!(serialized["data"]["id"], "1");
+
+        let parsed: ClientMessage = serde_json::from_str(r#"{"type": "subscribe", "events": ["message"]}"#).unwrap();
+        assert!(matches!(parsed, ClientMessage::Subscribe(subscription) if subscription.events == vec!["message"]));
+    }
+}
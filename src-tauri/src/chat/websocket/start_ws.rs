@@ -1,20 +1,70 @@
+use crate::chat::websocket::config::{load_websocket_config, WebSocketConfig};
 use crate::chat::websocket::ws_server::WebSocketServer;
-use std::sync::Arc;
-use tauri::{AppHandle, Manager};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::task::JoinHandle;
+
+/// Server of the running chat, a new one is created every time the chat starts
+#[derive(Default)]
+pub(crate) struct ChatServer {
+    current: Mutex<Option<Arc<WebSocketServer>>>,
+}
+
+impl ChatServer {
+    /// Binds a new server and runs it in the background, the handle resolves once it stopped.
+    pub(crate) async fn start(&self, config: WebSocketConfig) -> Result<(Arc<WebSocketServer>, JoinHandle<Result<(), String>>), String> {
+        let ws_server = Arc::new(WebSocketServer::new(config));
+        let listener = ws_server.bind().await?;
+
+        let ws_server_clone = Arc::clone(&ws_server);
+        let run_handle = tokio::spawn(async move { ws_server_clone.run(listener).await.map_err(|e| e.to_string()) });
+
+        *self.current.lock().unwrap() = Some(ws_server.clone());
+        Ok((ws_server, run_handle))
+    }
+
+    /// Stops the running server, returns once its port is free.
+    pub(crate) async fn stop(&self) {
+        let ws_server = self.current.lock().unwrap().take();
+        if let Some(ws_server) = ws_server {
+            ws_server.close().await;
+        }
+    }
+}
+
+pub(crate) fn current_server(app: &AppHandle) -> Option<Arc<WebSocketServer>> {
+    app.state::<ChatServer>().current.lock().unwrap().clone()
+}
 
 // WebSocket server initialization logic
-pub(crate) async fn initialize_websocket_server(app: AppHandle) -> Arc<WebSocketServer> {
-    let ws_server = Arc::new(WebSocketServer::new());
-    let ws_server_clone = Arc::clone(&ws_server);
+pub(crate) async fn initialize_websocket_server(app: AppHandle) -> Result<Arc<WebSocketServer>, String> {
+    // Bind before spawning so united_chat_init knows right away if we couldn't start
+    let (ws_server, run_handle) = match app.state::<ChatServer>().start(load_websocket_config(&app)).await {
+        Ok(started) => started,
+        Err(e) => {
+            // Returned to united_chat_init, which hands it to the UI
+            eprintln!("Error starting WebSocket server: {}", e);
+            return Err(e);
+        }
+    };
+
+    let address = ws_server.local_addr().await.map(|addr| addr.to_string()).unwrap_or_default();
+    // To every listener, the overlay windows open with the server
+    app.emit("chat::server_started", json!({ "address": address }))
+        .unwrap_or_else(|e| eprintln!("Failed to emit server start: {}", e));
 
+    let app_clone = app.clone();
     tokio::spawn(async move {
-        if let Err(e) = ws_server_clone.run("localhost:9888").await {
-            panic!("Error starting WebSocket server: {}", e);
-        } else {
-            println!("WebSocket server started successfully");
+        match run_handle.await {
+            Ok(Err(e)) => {
+                eprintln!("Error running WebSocket server: {}", e);
+                app_clone.emit_to("main", "chat::server_error", json!({ "error": e }))
+                    .unwrap_or_else(|e| eprintln!("Failed to emit server error: {}", e));
+            }
+            _ => println!("WebSocket server stopped"),
         }
     });
 
-    app.manage(ws_server.clone());
-    ws_server
-}
\ No newline at end of file
+    Ok(ws_server)
+}
@@ -1,130 +1,678 @@
+use crate::chat::websocket::config::{SlowClientPolicy, WebSocketConfig};
+use crate::chat::websocket::history::EventHistory;
+use crate::chat::websocket::http::{handle_http, read_request, BufferedStream};
+use crate::chat::websocket::protocol::{ClientMessage, Envelope, ServerMessage, Subscription};
+use crate::misc::editor::template::TemplateCache;
 use futures::{SinkExt, StreamExt};
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::net::TcpListener;
-use tokio::sync::{watch, Mutex};
-use tokio_tungstenite::accept_async;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{watch, Mutex, Notify};
+use tokio_tungstenite::accept_hdr_async;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
 use tokio_tungstenite::tungstenite::protocol::Message;
 
-type Tx = tokio::sync::mpsc::UnboundedSender<Message>;
-type Rx = tokio::sync::mpsc::UnboundedReceiver<Message>;
+// Clients that don't answer our pings in this time are disconnected
+pub(crate) const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(90);
+// A client whose socket doesn't accept a message in this time is treated as gone
+pub(crate) const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+// Handshakes run on their own task, this only frees the task of a client that never finishes it
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// Clients that didn't pass their filters on the URL get this long to subscribe before the history is replayed to them
+const SUBSCRIBE_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// An event serialized once and shared by every client.
+pub struct OutgoingEvent {
+    pub(crate) envelope: Envelope,
+    pub(crate) text: Arc<str>,
+}
+
+impl OutgoingEvent {
+    pub(crate) fn new(envelope: Envelope) -> Result<Self, serde_json::Error> {
+        let text = serde_json::to_string(&envelope)?.into();
+        Ok(Self { envelope, text })
+    }
+}
+
+type Tx = tokio::sync::mpsc::Sender<Arc<OutgoingEvent>>;
+/// The handshake reads the request head again from the bytes we already read to route it
+type ClientStream = tokio_tungstenite::WebSocketStream<BufferedStream>;
+pub(crate) type Rx = tokio::sync::mpsc::Receiver<Arc<OutgoingEvent>>;
+
+struct Client {
+    tx: Tx,
+    /// Tells the connection to close right away, instead of going through what's left in its queue
+    disconnect: Arc<Notify>,
+}
+
+/// What a new connection starts with
+pub(crate) struct ClientQueue {
+    pub(crate) rx: Rx,
+    pub(crate) disconnect: Arc<Notify>,
+    pub(crate) replay: Vec<Arc<OutgoingEvent>>,
+    pub(crate) last_seq: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ServerMetrics {
+    pub clients: usize,
+    /// Events that didn't fit in a slow client's queue
+    pub dropped_messages: u64,
+    pub slow_clients_disconnected: u64,
+}
 
 pub struct WebSocketServer {
-    clients: Arc<Mutex<Vec<Tx>>>,
-    client_addresses: Arc<Mutex<HashSet<String>>>,
-    shutdown_signal: watch::Sender<()>, // Added shutdown signal
+    clients: Arc<Mutex<Vec<Client>>>,
+    /// Set to true once, a closed server can't be started again, a new one is created instead
+    shutdown_signal: watch::Sender<bool>,
+    /// True while `run` holds the listener
+    running: watch::Sender<bool>,
+    config: WebSocketConfig,
+    local_addr: Mutex<Option<SocketAddr>>,
+    history: Arc<Mutex<EventHistory>>,
+    dropped_messages: AtomicU64,
+    slow_clients_disconnected: AtomicU64,
+    /// Themes of the overlays this server renders
+    templates: TemplateCache,
 }
 
 impl WebSocketServer {
-    pub fn new() -> Self {
-        let (shutdown_signal, _) = watch::channel(());
+    pub fn new(config: WebSocketConfig) -> Self {
+        let (shutdown_signal, _) = watch::channel(false);
+        let (running, _) = watch::channel(false);
+        let history = EventHistory::new(config.history_size, Duration::from_secs(config.history_max_age_seconds));
         Self {
             clients: Arc::new(Mutex::new(Vec::new())),
-            client_addresses: Arc::new(Mutex::new(HashSet::new())),
             shutdown_signal,
+            running,
+            config,
+            local_addr: Mutex::new(None),
+            history: Arc::new(Mutex::new(history)),
+            dropped_messages: AtomicU64::new(0),
+            slow_clients_disconnected: AtomicU64::new(0),
+            templates: TemplateCache::default(),
         }
     }
 
-    pub async fn run(&self, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let listener = TcpListener::bind(addr).await?;
-        println!("WebSocket server running on {}", addr);
+    /// Address the server is listening on, None when it isn't running
+    pub async fn local_addr(&self) -> Option<SocketAddr> {
+        *self.local_addr.lock().await
+    }
+
+    /// Binds to the configured port, moving on to the next ones if it's already taken.
+    pub async fn bind(&self) -> Result<TcpListener, String> {
+        let first_port = self.config.port;
+        let last_port = first_port.saturating_add(self.config.port_fallback_attempts);
 
+        for port in first_port..=last_port {
+            let addr = format!("{}:{}", self.config.bind_address, port);
+            match TcpListener::bind(&addr).await {
+                Ok(listener) => {
+                    if port != first_port {
+                        println!("Port {} is taken, using {} instead", first_port, port);
+                    }
+                    *self.local_addr.lock().await = listener.local_addr().ok();
+                    return Ok(listener);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => continue,
+                Err(e) => return Err(format!("Failed to bind to {}: {}", addr, e)),
+            }
+        }
+
+        Err(format!(
+            "Ports {} to {} are all in use on {}",
+            first_port, last_port, self.config.bind_address
+        ))
+    }
+
+    pub(crate) fn config(&self) -> &WebSocketConfig {
+        &self.config
+    }
+
+    pub(crate) fn templates(&self) -> &TemplateCache {
+        &self.templates
+    }
+
+    /// Serves WebSocket clients, and the SSE stream and JSON endpoints for plain HTTP requests.
+    pub async fn run(self: Arc<Self>, listener: TcpListener) -> Result<(), Box<dyn std::error::Error>> {
+        let port = listener.local_addr()?.port();
+        println!("WebSocket server running on {}", listener.local_addr()?);
+
+        self.running.send_replace(true);
         let mut shutdown_receiver = self.shutdown_signal.subscribe();
 
         loop {
             tokio::select! {
-                Ok((stream, addr)) = listener.accept() => {
+                accepted = listener.accept() => {
+                    let (mut stream, addr) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            eprintln!("Failed to accept connection: {}", e);
+                            continue;
+                        }
+                    };
                     println!("Accepted connection from {}", addr);
-                    let addr_str = addr.to_string();
-                    let mut client_addresses = self.client_addresses.lock().await;
-
-                    if client_addresses.contains(&addr_str) {
-                        eprintln!("Client already connected: {}", addr_str);
-                        continue;
-                    }
-
-                    client_addresses.insert(addr_str.clone());
 
-                    // Attempt to accept the WebSocket connection
-                    match accept_async(stream).await {
-                        Ok(ws_stream) => {
-                            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-                            self.clients.lock().await.push(tx);
+                    // Only our own pages and tools holding the token may connect
+                    let config = self.config.clone();
+                    let check_client = move |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+                        let origin = request.headers().get("Origin").and_then(|origin| origin.to_str().ok());
+                        let token = request.uri().query().and_then(|query| {
+                            url::form_urlencoded::parse(query.as_bytes())
+                                .find(|(key, _)| key == "token")
+                                .map(|(_, value)| value.into_owned())
+                        });
 
-                            let clients = Arc::clone(&self.clients);
-                            let client_addresses = Arc::clone(&self.client_addresses);
-                            tokio::spawn(Self::handle_connection(ws_stream, rx, clients, client_addresses, addr_str, shutdown_receiver.clone()));
+                        if config.is_client_allowed(origin, token.as_deref(), port) {
+                            Ok(response)
+                        } else {
+                            eprintln!("Rejected WebSocket client from origin {:?}", origin);
+                            let mut error = ErrorResponse::new(Some("Origin not allowed".to_string()));
+                            *error.status_mut() = StatusCode::FORBIDDEN;
+                            Err(error)
                         }
-                        Err(e) => {
-                            eprintln!("Failed to accept WebSocket connection: {}", e);
-                            let mut client_addresses = self.client_addresses.lock().await;
-                            client_addresses.remove(&addr_str);
+                    };
+
+                    // The handshake happens on the client's own task, so a slow one doesn't hold up the others
+                    let server = Arc::clone(&self);
+                    tokio::spawn(async move {
+                        let (request, head) = match tokio::time::timeout(HANDSHAKE_TIMEOUT, read_request(&mut stream)).await {
+                            Ok(Ok(request)) => request,
+                            Ok(Err(e)) => {
+                                eprintln!("Invalid request from {}: {}", addr, e);
+                                return;
+                            }
+                            Err(_) => {
+                                eprintln!("Request from {} timed out", addr);
+                                return;
+                            }
+                        };
+                        if !request.is_websocket_upgrade() {
+                            handle_http(server, stream, request, port).await;
+                            return;
                         }
-                    }
+                        let subscription = Some(request.subscription()).filter(|subscription| *subscription != Subscription::default());
+
+                        let ws_stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, accept_hdr_async(BufferedStream::new(head, stream), check_client)).await {
+                            Ok(Ok(ws_stream)) => ws_stream,
+                            Ok(Err(e)) => {
+                                eprintln!("Failed to accept WebSocket connection: {}", e);
+                                return;
+                            }
+                            Err(_) => {
+                                eprintln!("WebSocket handshake with {} timed out", addr);
+                                return;
+                            }
+                        };
+
+                        let queue = server.register_client(None).await;
+                        let clients = Arc::clone(&server.clients);
+                        let history = Arc::clone(&server.history);
+                        Self::handle_connection(ws_stream, queue, subscription, clients, history, addr, server.shutdown_receiver()).await;
+                    });
                 },
-                _ = shutdown_receiver.changed() => {
+                _ = Self::stopping(&mut shutdown_receiver) => {
                     println!("Shutting down the WebSocket server...");
                     break;
                 }
             }
         }
 
+        // Free the port before saying we're done, so a new server can bind to it right away
+        drop(listener);
+        *self.local_addr.lock().await = None;
+        self.running.send_replace(false);
         Ok(())
     }
 
+    pub(crate) fn shutdown_receiver(&self) -> watch::Receiver<bool> {
+        self.shutdown_signal.subscribe()
+    }
+
+    /// Resolves once `close` was called, or right away if it already was
+    pub(crate) async fn stopping(shutdown_signal: &mut watch::Receiver<bool>) {
+        let _ = shutdown_signal.wait_for(|stopping| *stopping).await;
+    }
+
+    /// Adds a client to the broadcast list, along with the buffered events after `since` it should get first.
+    pub(crate) async fn register_client(&self, since: Option<u64>) -> ClientQueue {
+        let (tx, rx) = tokio::sync::mpsc::channel(self.config.client_queue_size.max(1));
+        let disconnect = Arc::new(Notify::new());
+
+        // Holding the history lock while registering, so nothing is broadcasted between the replay and the live events
+        let mut history = self.history.lock().await;
+        self.clients.lock().await.push(Client { tx, disconnect: Arc::clone(&disconnect) });
+        ClientQueue { rx, disconnect, replay: history.since(since), last_seq: history.last_seq() }
+    }
+
+    /// Buffered events after `since`, oldest first
+    pub(crate) async fn recent_events(&self, since: Option<u64>) -> Vec<Arc<OutgoingEvent>> {
+        self.history.lock().await.since(since)
+    }
+
     async fn handle_connection(
-        mut ws_stream: tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>,
-        mut rx: Rx,
-        clients: Arc<Mutex<Vec<Tx>>>,
-        client_addresses: Arc<Mutex<HashSet<String>>>,
-        addr_str: String,
-        mut shutdown_signal: watch::Receiver<()>,
+        mut ws_stream: ClientStream,
+        queue: ClientQueue,
+        url_subscription: Option<Subscription>,
+        clients: Arc<Mutex<Vec<Client>>>,
+        history: Arc<Mutex<EventHistory>>,
+        addr: SocketAddr,
+        mut shutdown_signal: watch::Receiver<bool>,
     ) {
-        loop {
-            tokio::select! {
-                Some(msg) = ws_stream.next() => {
-                    if let Ok(msg) = msg {
-                        if msg.is_close() {
+        let ClientQueue { mut rx, disconnect, replay, last_seq } = queue;
+        let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+        // Events up to this one were already sent by a replay
+        let mut last_replayed = last_seq;
+        let mut last_seen = Instant::now();
+
+        let mut greeted = Self::send_server_message(&mut ws_stream, &ServerMessage::hello()).await.is_ok();
+        // The history is replayed with the client's filters, so it waits for them unless they came with the URL.
+        // Live events wait too, they would otherwise arrive before the older replayed ones.
+        let mut pending_replay = Some(replay);
+        let replay_deadline = tokio::time::sleep(SUBSCRIBE_GRACE_PERIOD);
+        tokio::pin!(replay_deadline);
+        // Every client gets everything until it subscribes to something more specific
+        let mut subscription = match url_subscription {
+            Some(subscription) => {
+                let replay = pending_replay.take().unwrap_or_default();
+                greeted = greeted && Self::replay(&mut ws_stream, &subscription, replay, last_seq).await.is_ok();
+                subscription
+            }
+            None => Subscription::default(),
+        };
+
+        if !greeted {
+            eprintln!("Failed to greet {}", addr);
+        } else {
+            loop {
+                tokio::select! {
+                    Some(msg) = ws_stream.next() => {
+                        let msg = match msg {
+                            Ok(msg) => msg,
+                            Err(_) => break,
+                        };
+                        last_seen = Instant::now();
+
+                        let reply = match msg {
+                            Message::Close(_) => break,
+                            Message::Text(text) => match serde_json::from_str::<ClientMessage>(&text) {
+                                Ok(ClientMessage::Subscribe(new_subscription)) => {
+                                    subscription = new_subscription;
+                                    let reply = ServerMessage::Subscribed { subscription: subscription.clone() };
+                                    if let Some(replay) = pending_replay.take() {
+                                        let replayed = Self::send_server_message(&mut ws_stream, &reply).await.is_ok()
+                                            && Self::replay(&mut ws_stream, &subscription, replay, last_seq).await.is_ok();
+                                        if !replayed {
+                                            break;
+                                        }
+                                        continue;
+                                    }
+                                    reply
+                                }
+                                Ok(ClientMessage::Ping { nonce }) => ServerMessage::Pong { nonce },
+                                Ok(ClientMessage::Replay { since }) => {
+                                    // This replaces the one waiting for the subscription
+                                    pending_replay = None;
+                                    let (events, last_seq) = {
+                                        let mut history = history.lock().await;
+                                        (history.since(since), history.last_seq())
+                                    };
+                                    if Self::replay(&mut ws_stream, &subscription, events, last_seq).await.is_err() {
+                                        break;
+                                    }
+                                    // Some of the replayed events may still be queued, they're skipped
+                                    last_replayed = last_seq;
+                                    continue;
+                                }
+                                Err(e) => ServerMessage::Error { message: format!("Invalid message: {}", e) },
+                            },
+                            // Pings and pongs are answered by tungstenite itself
+                            _ => continue,
+                        };
+
+                        if let Err(e) = Self::send_server_message(&mut ws_stream, &reply).await {
+                            eprintln!("Error sending message: {}", e);
                             break;
                         }
-                    }
-                },
-                Some(msg) = rx.recv() => {
-                    if let Err(e) = ws_stream.send(msg).await {
-                        eprintln!("Error sending message: {}", e);
+                    },
+                    _ = &mut replay_deadline, if pending_replay.is_some() => {
+                        let replay = pending_replay.take().unwrap_or_default();
+                        if Self::replay(&mut ws_stream, &subscription, replay, last_seq).await.is_err() {
+                            break;
+                        }
+                    },
+                    event = rx.recv(), if pending_replay.is_none() => {
+                        // The server dropped us
+                        let Some(event) = event else {
+                            break;
+                        };
+                        if event.envelope.seq <= last_replayed || !subscription.matches(&event.envelope) {
+                            continue;
+                        }
+                        if let Err(e) = Self::send_event(&mut ws_stream, &event).await {
+                            eprintln!("Error sending message to {}: {}", addr, e);
+                            break;
+                        }
+                    },
+                    _ = disconnect.notified() => {
+                        println!("Disconnecting slow client {}", addr);
+                        let _ = ws_stream.close(None).await;
+                        break;
+                    },
+                    _ = heartbeat.tick() => {
+                        if last_seen.elapsed() > CLIENT_TIMEOUT {
+                            println!("Client {} stopped responding", addr);
+                            break;
+                        }
+                        if ws_stream.send(Message::Ping(Vec::new())).await.is_err() {
+                            break;
+                        }
+                    },
+                    _ = Self::stopping(&mut shutdown_signal) => {
+                        println!("Shutting down connection for {}", addr);
+                        let _ = ws_stream.close(None).await; // Close the WebSocket connection
                         break;
                     }
-                },
-                _ = shutdown_signal.changed() => {
-                    println!("Shutting down connection for {}", addr_str);
-                    let _ = ws_stream.close(None).await; // Close the WebSocket connection
-                    break;
                 }
             }
         }
 
+        // Dropping the receiver lets the next broadcast clean this client up
+        drop(rx);
         let mut clients = clients.lock().await;
-        clients.retain(|client| !client.is_closed());
+        clients.retain(|client| !client.tx.is_closed());
+    }
+
+    async fn send_server_message(
+        ws_stream: &mut ClientStream,
+        message: &ServerMessage,
+    ) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+        let text = serde_json::to_string(message).unwrap();
+        ws_stream.send(Message::Text(text)).await
+    }
+
+    async fn send_event(
+        ws_stream: &mut ClientStream,
+        event: &OutgoingEvent,
+    ) -> Result<(), String> {
+        let send = ws_stream.send(Message::Text(event.text.to_string()));
+        match tokio::time::timeout(SEND_TIMEOUT, send).await {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err("timed out".into()),
+        }
+    }
+
+    /// Sends the buffered events the client is subscribed to, followed by the "replay_done" marker.
+    async fn replay(
+        ws_stream: &mut ClientStream,
+        subscription: &Subscription,
+        events: Vec<Arc<OutgoingEvent>>,
+        last_seq: u64,
+    ) -> Result<(), String> {
+        for event in events.iter().filter(|event| subscription.matches(&event.envelope)) {
+            Self::send_event(ws_stream, event).await?;
+        }
+        Self::send_server_message(ws_stream, &ServerMessage::ReplayDone { last_seq })
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Sends an event to every client subscribed to it, and keeps it for the ones connecting later.
+    pub async fn broadcast(&self, mut envelope: Envelope) {
+        let mut history = self.history.lock().await;
+        envelope.seq = history.next_seq();
+
+        let event = match OutgoingEvent::new(envelope) {
+            Ok(event) => Arc::new(event),
+            Err(e) => {
+                eprintln!("Failed to serialize event: {}", e);
+                return;
+            }
+        };
+        history.push(Arc::clone(&event));
+        self.send_to_clients(event).await;
+    }
+
+    /// Sends an event to the connected clients only, it isn't kept for replays. Used for notices like theme reloads
+    /// that would be wrong to repeat to a client that reconnects.
+    pub async fn notify(&self, mut envelope: Envelope) {
+        // Holding the history lock keeps the seqs in order with the broadcasts
+        let mut history = self.history.lock().await;
+        envelope.seq = history.next_seq();
 
-        let mut client_addresses = client_addresses.lock().await;
-        client_addresses.remove(&addr_str);
+        match OutgoingEvent::new(envelope) {
+            Ok(event) => self.send_to_clients(Arc::new(event)).await,
+            Err(e) => eprintln!("Failed to serialize event: {}", e),
+        }
     }
 
-    pub async fn broadcast(&self, msg: Message) {
+    async fn send_to_clients(&self, event: Arc<OutgoingEvent>) {
+        // Never waits on a client, a full queue means it's falling behind
         let mut clients = self.clients.lock().await;
-        clients.retain(|client| {
-            if let Err(e) = client.send(msg.clone()) {
-                eprintln!("Error broadcasting message: {}", e);
-                false // Remove the client if sending fails
-            } else {
-                true // Keep the client if sending succeeds
+        clients.retain(|client| match client.tx.try_send(Arc::clone(&event)) {
+            Ok(_) => true,
+            Err(TrySendError::Closed(_)) => false, // Remove the client if it's gone
+            Err(TrySendError::Full(_)) => {
+                self.dropped_messages.fetch_add(1, Ordering::Relaxed);
+                match self.config.slow_client_policy {
+                    SlowClientPolicy::Drop => true,
+                    SlowClientPolicy::Disconnect => {
+                        self.slow_clients_disconnected.fetch_add(1, Ordering::Relaxed);
+                        client.disconnect.notify_one();
+                        false
+                    }
+                }
             }
         });
     }
 
+    pub async fn metrics(&self) -> ServerMetrics {
+        ServerMetrics {
+            clients: self.clients.lock().await.len(),
+            dropped_messages: self.dropped_messages.load(Ordering::Relaxed),
+            slow_clients_disconnected: self.slow_clients_disconnected.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Removes events from the history, so deleted messages don't show up again on a replay.
+    pub async fn remove_from_history(&self, predicate: impl Fn(&Envelope) -> bool) {
+        self.history.lock().await.remove(predicate);
+    }
+
+    /// Stops the server and its connections, returns once the port is free again.
     pub async fn close(&self) {
-        self.shutdown_signal.send(()).unwrap();
-        let mut clients = self.clients.lock().await;
-        clients.clear();
+        self.shutdown_signal.send_replace(true);
+        self.clients.lock().await.clear();
+
+        let mut running = self.running.subscribe();
+        let _ = running.wait_for(|running| !*running).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use tokio_tungstenite::connect_async;
+
+    fn test_config(policy: SlowClientPolicy, client_queue_size: usize) -> WebSocketConfig {
+        WebSocketConfig {
+            port: 0,
+            port_fallback_attempts: 0,
+            history_size: 0,
+            client_queue_size,
+            slow_client_policy: policy,
+            ..WebSocketConfig::default()
+        }
+    }
+
+    async fn add_stalled_client(server: &WebSocketServer) -> (Rx, Arc<Notify>) {
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let disconnect = Arc::new(Notify::new());
+        server.clients.lock().await.push(Client { tx, disconnect: Arc::clone(&disconnect) });
+        (rx, disconnect)
+    }
+
+    #[tokio::test]
+    async fn notices_are_not_replayed() {
+        let server = WebSocketServer::new(WebSocketConfig { history_size: 10, ..test_config(SlowClientPolicy::Drop, 10) });
+        let mut queue = server.register_client(None).await;
+        server.notify(Envelope::new("united_chat", None, "theme_changed", json!({ "theme": "mine" }))).await;
+        server.broadcast(Envelope::new("twitch", None, "message", json!({ "id": 1 }))).await;
+
+        assert_eq!(queue.rx.recv().await.unwrap().envelope.event, "theme_changed");
+        assert_eq!(queue.rx.recv().await.unwrap().envelope.seq, 2);
+        let replay = server.recent_events(None).await;
+        assert_eq!(replay.iter().map(|event| event.envelope.seq).collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn slow_clients_follow_the_policy() {
+        let server = WebSocketServer::new(test_config(SlowClientPolicy::Drop, 1));
+        let (_rx, _) = add_stalled_client(&server).await;
+        for i in 0..3 {
+            server.broadcast(Envelope::new("twitch", None, "message", json!({ "id": i }))).await;
+        }
+        let metrics = server.metrics().await;
+        assert_eq!((metrics.clients, metrics.dropped_messages, metrics.slow_clients_disconnected), (1, 2, 0));
+
+        let server = WebSocketServer::new(test_config(SlowClientPolicy::Disconnect, 1));
+        let (_rx, disconnect) = add_stalled_client(&server).await;
+        for i in 0..3 {
+            server.broadcast(Envelope::new("twitch", None, "message", json!({ "id": i }))).await;
+        }
+        let metrics = server.metrics().await;
+        assert_eq!((metrics.clients, metrics.dropped_messages, metrics.slow_clients_disconnected), (0, 1, 1));
+        // The permit is stored, so the connection closes as soon as it looks at it
+        tokio::time::timeout(Duration::from_secs(1), disconnect.notified()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn replays_with_the_subscription_of_the_client() {
+        let server = Arc::new(WebSocketServer::new(WebSocketConfig { history_size: 10, ..test_config(SlowClientPolicy::Drop, 16) }));
+        let listener = server.bind().await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let running = Arc::clone(&server);
+        tokio::spawn(async move { running.run(listener).await.unwrap() });
+        server.broadcast(Envelope::new("twitch", Some("channel"), "message", json!({ "id": "twitch" }))).await;
+        server.broadcast(Envelope::new("youtube", Some("video"), "message", json!({ "id": "youtube" }))).await;
+
+        let mut received = Vec::new();
+        let (mut ws_stream, _) = connect_async(format!("ws://{}", addr)).await.unwrap();
+        ws_stream.send(Message::Text(json!({ "type": "subscribe", "platforms": ["youtube"] }).to_string())).await.unwrap();
+        while let Some(Ok(Message::Text(text))) = ws_stream.next().await {
+            let message: serde_json::Value = serde_json::from_str(&text).unwrap();
+            received.push(message["data"]["id"].as_str().unwrap_or(message["type"].as_str().unwrap()).to_string());
+            if message["type"] == "replay_done" {
+                break;
+            }
+        }
+        assert_eq!(received, vec!["hello", "subscribed", "youtube", "replay_done"]);
+
+        server.close().await;
+    }
+
+    #[tokio::test]
+    async fn restarts_on_the_same_port() {
+        // Find a free port, every server below must get exactly this one back
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let config = WebSocketConfig { port, ..test_config(SlowClientPolicy::Drop, 16) };
+
+        for round in 0..5 {
+            let server = Arc::new(WebSocketServer::new(config.clone()));
+            let listener = server.bind().await.unwrap();
+            assert_eq!(listener.local_addr().unwrap().port(), port);
+            let running = Arc::clone(&server);
+            let run_handle = tokio::spawn(async move { running.run(listener).await.unwrap() });
+
+            // A client that never finishes its handshake doesn't keep the others out
+            let _stalled = tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+
+            let (mut ws_stream, _) = connect_async(format!("ws://127.0.0.1:{}/?platforms=twitch", port)).await.unwrap();
+            for _ in 0..2 {
+                ws_stream.next().await.unwrap().unwrap();
+            }
+            while server.metrics().await.clients == 0 {
+                tokio::task::yield_now().await;
+            }
+            server.broadcast(Envelope::new("twitch", None, "message", json!({ "round": round }))).await;
+            match ws_stream.next().await {
+                Some(Ok(Message::Text(text))) => assert!(text.contains(&format!("\"round\":{}", round))),
+                other => panic!("Expected the event, got {:?}", other),
+            }
+
+            tokio::time::timeout(Duration::from_secs(5), server.close()).await.unwrap();
+            run_handle.await.unwrap();
+            assert_eq!(server.local_addr().await, None);
+
+            // The client is told the server went away
+            loop {
+                match ws_stream.next().await {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                }
+            }
+        }
+    }
+
+    /// Run with `cargo test --release broadcast_benchmark -- --ignored --nocapture`
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn broadcast_benchmark() {
+        const CLIENTS: usize = 300;
+        const EVENTS: usize = 1000;
+
+        let server = Arc::new(WebSocketServer::new(test_config(SlowClientPolicy::Drop, EVENTS)));
+        let listener = server.bind().await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let running = Arc::clone(&server);
+        tokio::spawn(async move { running.run(listener).await.unwrap() });
+
+        let mut readers = Vec::new();
+        for _ in 0..CLIENTS {
+            let (mut ws_stream, _) = connect_async(format!("ws://{}/?platforms=twitch", addr)).await.unwrap();
+            // Hello and the (empty) replay
+            for _ in 0..2 {
+                ws_stream.next().await.unwrap().unwrap();
+            }
+            readers.push(tokio::spawn(async move {
+                let mut received = 0;
+                while received < EVENTS {
+                    match ws_stream.next().await {
+                        Some(Ok(Message::Text(_))) => received += 1,
+                        Some(Ok(_)) => {}
+                        _ => break,
+                    }
+                }
+                received
+            }));
+        }
+
+        let started = Instant::now();
+        let message = json!({ "message": "x".repeat(200) });
+        for _ in 0..EVENTS {
+            server.broadcast(Envelope::new("twitch", Some("channel"), "message", message.clone())).await;
+        }
+        let broadcasted = started.elapsed();
+
+        for reader in readers {
+            assert_eq!(reader.await.unwrap(), EVENTS);
+        }
+        println!(
+            "{} events to {} clients: broadcast in {:?}, delivered in {:?}, {:?}",
+            EVENTS,
+            CLIENTS,
+            broadcasted,
+            started.elapsed(),
+            server.metrics().await
+        );
+
+        server.close().await;
     }
 }
@@ -1,6 +1,7 @@
 #[macro_use]
 pub(crate) mod polling;
-mod structs;
+pub(crate) mod structs;
 
 #[macro_use]
 pub(crate) mod state_manager;
+pub(crate) mod send_message;
@@ -1,12 +1,13 @@
+use crate::chat::websocket::protocol::Envelope;
 use crate::chat::websocket::ws_server::WebSocketServer;
 use crate::chat::youtube::structs::youtube_response::YoutubeResponse;
+use crate::misc::editor::template::{emote_image, escape_html};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::VecDeque;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use tokio_tungstenite::tungstenite::Message;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct VideoInfo {
@@ -146,8 +147,9 @@ fn parse_message_type(data: &Vec<Value>) -> Result<Vec<YoutubeResponse>, ()> {
     let processed_data: Vec<Value> = serde_json::from_str(&json_string).map_err(|_| ())?;
 
     for item in processed_data {
+        // Super chats are messages with an amount, their text can be empty
         if let Some(message) = item.get("item")
-            .and_then(|i| i.get("liveChatTextMessageRenderer"))
+            .and_then(|i| i.get("liveChatTextMessageRenderer").or_else(|| i.get("liveChatPaidMessageRenderer")))
             .and_then(|m| m.as_object()) {
             let author_name = message.get("authorName")
                 .and_then(|a| a.get("simpleText"))
@@ -178,7 +180,9 @@ fn parse_message_type(data: &Vec<Value>) -> Result<Vec<YoutubeResponse>, ()> {
                 .iter()
                 .filter_map(|item| {
                     if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
-                        Some(text.to_string())
+                        let mut escaped = String::new();
+                        escape_html(text, &mut escaped);
+                        Some(escaped)
                     } else if let Some(emoji) = item.get("emoji").and_then(|e| e.as_object()) {
                         let emoji_url = emoji.get("image")
                             .and_then(|i| i.get("thumbnails"))
@@ -194,7 +198,7 @@ fn parse_message_type(data: &Vec<Value>) -> Result<Vec<YoutubeResponse>, ()> {
                             .and_then(|l| l.as_str())
                             .unwrap_or("Unknown Emoji");
 
-                        Some(format!("<img id=\"{}\" class=\"w-6 h-6\" src=\"{}\" alt=\"{}\" />", emoji_name, emoji_url, emoji_name))
+                        Some(emote_image(emoji_name, emoji_url, Some("w-6 h-6")))
                     } else {
                         None
                     }
@@ -220,6 +224,11 @@ fn parse_message_type(data: &Vec<Value>) -> Result<Vec<YoutubeResponse>, ()> {
                 .unwrap_or("Unknown ID")
                 .to_string();
 
+            let amount = message.get("purchaseAmountText")
+                .and_then(|a| a.get("simpleText"))
+                .and_then(|a| a.as_str())
+                .map(|a| a.to_string());
+
             let response = YoutubeResponse {
                 id,
                 author_id,
@@ -229,6 +238,7 @@ fn parse_message_type(data: &Vec<Value>) -> Result<Vec<YoutubeResponse>, ()> {
                 message_emotes: Vec::new(),
                 timestamp,
                 tracking_params,
+                amount,
             };
 
             responses.push(response);
@@ -307,7 +317,7 @@ pub(crate) async fn youtube_polling_cmd(
     stop_flag: Arc<AtomicBool>,
     ws_server: Arc<WebSocketServer>,
 ) {
-    let video = get_video(live_id).await.unwrap();
+    let video = get_video(live_id.clone()).await.unwrap();
     println!("Starting YouTube live chat client");
     let mut previous_messages = PreviousMessages {
         message_ids: VecDeque::new(),
@@ -336,12 +346,8 @@ pub(crate) async fn youtube_polling_cmd(
                 // Process the messages
                 for message in data {
                     if !previous_messages.message_ids.contains(&message.id) {
-                        let ws_response = json!({
-                                    "platform": "youtube",
-                                    "data": message
-                                });
                         ws_server
-                            .broadcast(Message::Text(serde_json::to_string(&ws_response).unwrap()))
+                            .broadcast(Envelope::new("youtube", Some(&live_id), message.event(), json!(message)))
                             .await;
 
                         // Add the new message ID to previous messages
@@ -0,0 +1,125 @@
+use keyring::Entry;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const RELINK_ERROR: &str = "The YouTube token expired, relink YouTube";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct YoutubeAuth {
+    pub(crate) access_token: String,
+    /// Local timestamp (ms) the access token stops working at, Google's last an hour
+    #[serde(default)]
+    pub(crate) expires_at: Option<i64>,
+    /// Gets a new access token without linking the account again
+    #[serde(default)]
+    pub(crate) refresh_token: Option<String>,
+}
+
+impl YoutubeAuth {
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+}
+
+pub(crate) fn get_youtube_token() -> Result<String, String> {
+    let entry = Entry::new("united-chat", "youtube-auth").map_err(|e| e.to_string())?;
+    let auth = entry
+        .get_password()
+        .map_err(|_| "YouTube account is not linked".to_string())?;
+    let parsed: YoutubeAuth = serde_json::from_str(&auth).map_err(|e| e.to_string())?;
+
+    if parsed.is_expired(chrono::Local::now().timestamp_millis()) {
+        return Err(RELINK_ERROR.into());
+    }
+    Ok(parsed.access_token)
+}
+
+/// The error of a failed request, a rejected token means the account has to be linked again.
+async fn api_error(response: reqwest::Response, context: &str) -> String {
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return RELINK_ERROR.into();
+    }
+    let resp = response.text().await.unwrap_or_default();
+    format!("{}: {}", context, resp)
+}
+
+/// The innertube API we use for reading doesn't let us write, so sending goes through the Data API,
+/// which needs the live chat id of the broadcast instead of the video id.
+pub(crate) async fn get_live_chat_id(token: &str, video_id: &str) -> Result<String, String> {
+    let response = reqwest::Client::new()
+        .get("https://www.googleapis.com/youtube/v3/videos")
+        .query(&[("part", "liveStreamingDetails"), ("id", video_id)])
+        .bearer_auth(token)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !response.status().is_success() {
+        return Err(api_error(response, "Failed to get live chat id").await);
+    }
+
+    let json_response: Value = response.json().await.map_err(|e| e.to_string())?;
+    json_response["items"][0]["liveStreamingDetails"]["activeLiveChatId"]
+        .as_str()
+        .map(|id| id.to_string())
+        .ok_or(format!("Video {} has no active live chat", video_id))
+}
+
+pub(crate) async fn send_youtube_message(token: &str, live_chat_id: &str, message: &str) -> Result<(), String> {
+    let response = reqwest::Client::new()
+        .post("https://www.googleapis.com/youtube/v3/liveChat/messages?part=snippet")
+        .bearer_auth(token)
+        .json(&json!({
+            "snippet": {
+                "liveChatId": live_chat_id,
+                "type": "textMessageEvent",
+                "textMessageDetails": {
+                    "messageText": message,
+                }
+            }
+        }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(api_error(response, "Failed to send message").await)
+    }
+}
+
+/// Stores the OAuth token used to send messages to YouTube, it needs the `youtube.force-ssl` scope.
+/// `expires_in` is in seconds, as the OAuth response has it.
+#[tauri::command]
+pub(crate) async fn link_youtube_account(
+    access_token: String,
+    expires_in: Option<u64>,
+    refresh_token: Option<String>,
+) -> Result<bool, String> {
+    let entry = Entry::new("united-chat", "youtube-auth").map_err(|e| e.to_string())?;
+    let expires_at = expires_in.map(|seconds| chrono::Local::now().timestamp_millis() + seconds as i64 * 1000);
+    let auth = YoutubeAuth { access_token, expires_at, refresh_token };
+
+    entry
+        .set_password(&serde_json::to_string(&auth).unwrap())
+        .map_err(|e| e.to_string())?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tokens_expire() {
+        let auth: YoutubeAuth = serde_json::from_str(r#"{"access_token": "token", "expires_at": 1000}"#).unwrap();
+        assert!(!auth.is_expired(999));
+        assert!(auth.is_expired(1000));
+
+        // Linked before the expiry was stored
+        let auth: YoutubeAuth = serde_json::from_str(r#"{"access_token": "token"}"#).unwrap();
+        assert!(!auth.is_expired(i64::MAX));
+    }
+}
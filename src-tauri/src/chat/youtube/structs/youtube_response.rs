@@ -10,4 +10,18 @@ pub(crate) struct YoutubeResponse {
     pub(crate) message_emotes: Vec<(String, String)>,
     pub(crate) timestamp: String,
     pub(crate) tracking_params: String,
+    /// What was paid for a super chat as YouTube shows it, e.g. "$5.00", None for the other messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) amount: Option<String>,
+}
+
+impl YoutubeResponse {
+    /// Event the message is sent to the overlays as.
+    pub(crate) fn event(&self) -> &'static str {
+        if self.amount.is_some() {
+            "super_chat"
+        } else {
+            "message"
+        }
+    }
 }
\ No newline at end of file
@@ -2,19 +2,49 @@ mod chat;
 mod misc;
 
 use crate::chat::initialize::{united_chat_init, united_chat_stop, UnitedChat};
+use crate::chat::mock_chat::{get_mock_chat, start_mock_chat, stop_mock_chat, MockChat};
+use crate::chat::send_message::{send_chat_message, ChatSenders};
+use crate::chat::websocket::config::{get_websocket_config, get_websocket_status, set_websocket_config};
+use crate::chat::websocket::start_ws::ChatServer;
 use crate::chat::twitch::auth::twitch_auth;
+use crate::chat::twitch::eventsub::config::{get_eventsub_topics, set_eventsub_topics};
 use crate::chat::twitch::get_user::get_user;
+use crate::chat::twitch::redemptions::{cancel_redemption, fulfill_redemption, get_redemption_queue};
+use crate::chat::twitch::room_state::{get_room_state, TwitchRoomStates};
 use crate::misc::qol::linking_ais::twitch_linking;
 use crate::misc::setup::initialize_database;
 use chat::twitch::auth::{skip_twitch_auth, start_twitch_link, twitch_deauth};
 use chat::youtube::polling::{get_live_chat_cmd, get_video_cmd};
+use chat::youtube::send_message::link_youtube_account;
 use chat::youtube::state_manager::{
     delete_video_from_db, get_all_videos, get_video_from_db, store_new_livestream,
     update_video, update_video_metadata, StoredVideos,
 };
+use misc::editor::event_templates::{list_theme_templates, remove_theme_template, save_theme_template};
+use misc::editor::theme_lint::lint_theme;
 use misc::editor::get_app_url::{hide_webchat_window, open_webchat_window};
 use misc::editor::get_theme::{get_theme, get_themes};
+use misc::editor::overlay::get_overlay_url;
+use misc::editor::overlay_windows::{
+    close_overlay_window, create_overlay_window, delete_overlay_window, get_overlay_shortcut, list_overlay_windows,
+    open_overlay_window, set_overlay_shortcut, shortcut_plugin, toggle_overlay_interaction, update_overlay_window,
+    OverlayInteraction,
+};
 use misc::editor::save_theme::save_theme;
+use misc::editor::template::{get_theme_placeholders, render_theme_preview};
+use misc::editor::theme_assets::{add_theme_asset, list_theme_assets, remove_theme_asset};
+use misc::editor::theme_drafts::{
+    discard_theme_draft, get_theme_draft, list_theme_drafts, publish_theme_draft, save_theme_draft,
+};
+use misc::editor::theme_history::{
+    diff_theme_revisions, get_theme_revision, list_theme_revisions, restore_theme_revision,
+};
+use misc::editor::theme_package::{export_theme, import_theme};
+use misc::editor::theme_variables::{get_theme_variables, reset_theme_variables, set_theme_variables};
+use misc::editor::theme_registry::{
+    delete_theme, duplicate_theme, get_active_theme, list_themes, rename_theme, set_active_theme, set_theme_preview,
+    update_theme_metadata,
+};
 use misc::qol::check_if_unsaved::check_if_unsaved;
 use misc::setup::{setup_complete, SetupState};
 use std::collections::HashMap;
@@ -61,12 +91,18 @@ pub fn run() {
             }
         }))
         .plugin(tauri_plugin_localhost::Builder::new(9889).build())
+        .plugin(shortcut_plugin())
         .manage(Mutex::new(SetupState {
             frontend_task: false,
             backend_task: false,
         }))
         .manage(Mutex::new(StoredVideos::default()))
         .manage(UnitedChat::default())
+        .manage(ChatServer::default())
+        .manage(ChatSenders::default())
+        .manage(TwitchRoomStates::default())
+        .manage(MockChat::default())
+        .manage(OverlayInteraction::default())
         .setup(|app| {
             app.deep_link().register("unitedchat").unwrap();
 
@@ -90,14 +126,61 @@ pub fn run() {
             skip_twitch_auth,
             twitch_deauth,
             get_user,
+            get_room_state,
+            get_eventsub_topics,
+            set_eventsub_topics,
+            get_redemption_queue,
+            fulfill_redemption,
+            cancel_redemption,
             // Editor
             get_theme,
             get_themes,
             save_theme,
+            render_theme_preview,
+            get_theme_placeholders,
+            export_theme,
+            import_theme,
+            list_theme_assets,
+            add_theme_asset,
+            remove_theme_asset,
+            list_theme_revisions,
+            get_theme_revision,
+            diff_theme_revisions,
+            restore_theme_revision,
+            save_theme_draft,
+            get_theme_draft,
+            list_theme_drafts,
+            discard_theme_draft,
+            publish_theme_draft,
+            list_themes,
+            rename_theme,
+            duplicate_theme,
+            delete_theme,
+            get_active_theme,
+            set_active_theme,
+            update_theme_metadata,
+            set_theme_preview,
+            get_theme_variables,
+            set_theme_variables,
+            reset_theme_variables,
+            list_theme_templates,
+            save_theme_template,
+            remove_theme_template,
+            lint_theme,
             check_if_unsaved,
             // WebChat Window
             open_webchat_window,
             hide_webchat_window,
+            get_overlay_url,
+            list_overlay_windows,
+            create_overlay_window,
+            update_overlay_window,
+            open_overlay_window,
+            close_overlay_window,
+            delete_overlay_window,
+            toggle_overlay_interaction,
+            get_overlay_shortcut,
+            set_overlay_shortcut,
             // YouTube
             get_video_cmd,
             get_live_chat_cmd,
@@ -107,9 +190,19 @@ pub fn run() {
             delete_video_from_db,
             update_video_metadata,
             update_video,
+            link_youtube_account,
             // Chat Start/Stop
             united_chat_init,
             united_chat_stop,
+            send_chat_message,
+            // Mock chat, to preview themes offline
+            start_mock_chat,
+            stop_mock_chat,
+            get_mock_chat,
+            // Chat Server
+            get_websocket_config,
+            set_websocket_config,
+            get_websocket_status,
             // Account Linking After initial Setup
             twitch_linking
         ])
@@ -1,3 +1,5 @@
+use crate::misc::editor::theme_variables::ThemeVariable;
+
 pub(crate) fn default() -> String {
     r#"<!-- You can use the most common bindings on this editor -->
 <!-- As an example: "CRTL + /" creates this comment line -->
@@ -62,7 +64,7 @@ pub(crate) fn default() -> String {
 
 <!-- Imagination is your limit, do whatever you want. -->
 <!-- After you're done and like what you're seeing, click "Save" to save the theme -->
-<!-- if you don't, you'll lose everything. (PS: AutoSaving is planned) -->
+<!-- Until then, your changes are autosaved as a draft and come back when you reopen the editor. -->
 <!-- If you're having problems with line breaking messages, please use this example to format your styling accordingly. -->
 <!--
     ### Detailed Explanation of Word Breaking and Overflow Handling:
@@ -85,22 +87,23 @@ pub(crate) fn default() -> String {
 }
 
 pub(crate) fn sakura() -> String {
-    r#"<div id={id} class="flex flex-col items-start max-w-xs font-sans p-2">
+    r#"<!-- Colors, font, size and what's shown can be changed in the theme's variables, the values after the commas are the defaults -->
+<div id={id} class="flex flex-col items-start max-w-xs p-2" style="font-family: var(--font, ui-sans-serif, system-ui, sans-serif);">
     <div class="flex items-center mb-2 ml-2">
-        <div class="flex items-center bg-gradient-to-r from-pink-200 to-pink-300 px-3 py-1 rounded-full shadow-sm">
-            <span class="text-sm font-bold text-pink-700 tracking-wide">{user}</span>
+        <div class="flex items-center px-3 py-1 rounded-full shadow-sm" style="background: linear-gradient(to right, var(--name-background, #fbcfe8), var(--accent, #f9a8d4));">
+            <span class="font-bold tracking-wide" style="color: var(--name-color, #be185d); font-size: var(--font-size, 14px);">{user}</span>
         </div>
         <div class="flex ml-2 space-x-1">
             {platform}
             {badges}
         </div>
     </div>
-    <div class="relative bg-gradient-to-br from-pink-100 to-pink-200 text-gray-800 px-4 py-3 rounded-2xl border-2 border-pink-300 shadow-md min-w-[300px] max-w-[600px]">
-        <div class="absolute left-4 top-0 transform -translate-y-1/2 rotate-45 w-3 h-3 bg-pink-100 border-l-2 border-t-2 border-pink-300"></div>
-        <p class="text-sm leading-snug">
+    <div class="relative text-gray-800 px-4 py-3 rounded-2xl border-2 shadow-md min-w-[300px] max-w-[600px]" style="background: linear-gradient(to bottom right, var(--bubble, #fce7f3), var(--name-background, #fbcfe8)); border-color: var(--accent, #f9a8d4); border-style: var(--border-style, solid);">
+        <div class="absolute left-4 top-0 transform -translate-y-1/2 rotate-45 w-3 h-3 border-l-2 border-t-2" style="background: var(--bubble, #fce7f3); border-color: var(--accent, #f9a8d4); border-style: var(--border-style, solid);"></div>
+        <p class="leading-snug" style="color: var(--text-color, #1f2937); font-size: var(--font-size, 14px);">
             {formatedMessage}
         </p>
-        <div class="absolute -bottom-2 -right-2 text-pink-400">
+        <div class="absolute -bottom-2 -right-2" style="color: var(--cherry-color, #f472b6); display: var(--show-cherry, block);">
             <svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24" fill="none"
                  stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"
                  class="lucide lucide-cherry">
@@ -111,8 +114,25 @@ pub(crate) fn sakura() -> String {
             </svg>
         </div>
     </div>
-    <div class="text-xs text-gray-500 mt-1 ml-4">
-        2:30 PM
+    <div class="text-xs text-gray-500 mt-1 ml-4" style="display: var(--show-time, block);">
+        {timestamp}
     </div>
 </div>"#.to_string()
-}
\ No newline at end of file
+}
+
+/// The variables of the sakura theme, the same as a theme.json would declare them.
+pub(crate) fn sakura_variables() -> Vec<ThemeVariable> {
+    serde_json::from_str(r##"[
+    { "name": "accent", "label": "Accent", "type": "color", "default": "#f9a8d4" },
+    { "name": "name-background", "label": "Name background", "type": "color", "default": "#fbcfe8" },
+    { "name": "name-color", "label": "Name color", "type": "color", "default": "#be185d" },
+    { "name": "bubble", "label": "Message background", "type": "color", "default": "#fce7f3" },
+    { "name": "text-color", "label": "Message color", "type": "color", "default": "#1f2937" },
+    { "name": "cherry-color", "label": "Cherry color", "type": "color", "default": "#f472b6" },
+    { "name": "font", "label": "Font", "type": "font", "default": "ui-sans-serif, system-ui, sans-serif" },
+    { "name": "font-size", "label": "Font size", "type": "number", "default": 14, "min": 10, "max": 32, "step": 1, "unit": "px" },
+    { "name": "border-style", "label": "Border", "type": "enum", "default": "solid", "options": ["solid", "dashed", "dotted", "double"] },
+    { "name": "show-cherry", "label": "Show the cherry", "type": "boolean", "default": true, "on": "block", "off": "none" },
+    { "name": "show-time", "label": "Show the time", "type": "boolean", "default": true, "on": "block", "off": "none" }
+]"##).unwrap()
+}
@@ -0,0 +1,100 @@
+use crate::misc::editor::overlay::load_theme_files;
+use crate::misc::editor::save_theme::write_atomically;
+use crate::misc::editor::template::TEMPLATE_KINDS;
+use crate::misc::editor::theme_lint::{lint_template, reject_errors};
+use crate::misc::editor::theme_package::{is_valid_theme_name, themes_dir};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+/// Folder of a theme holding its templates for other events than chat messages, as `<kind>.html`.
+pub(crate) const TEMPLATES_DIR: &str = "templates";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct EventTemplate {
+    pub(crate) kind: String,
+    pub(crate) description: String,
+    /// None when the theme uses its index.html for it
+    pub(crate) html_code: Option<String>,
+}
+
+pub(crate) fn is_template_kind(kind: &str) -> bool {
+    TEMPLATE_KINDS.iter().any(|(known, _)| *known == kind)
+}
+
+/// Path of a template in a theme folder, None for unknown kinds. Messages are the theme's index.html.
+pub(crate) fn template_path(theme_dir: &Path, kind: &str) -> Option<PathBuf> {
+    match kind {
+        "message" => Some(theme_dir.join("index.html")),
+        kind if is_template_kind(kind) => Some(theme_dir.join(TEMPLATES_DIR).join(format!("{}.html", kind))),
+        _ => None,
+    }
+}
+
+/// The templates a theme folder has besides its index.html, as (kind, code).
+pub(crate) fn read_event_templates(theme_dir: &Path) -> Vec<(String, String)> {
+    TEMPLATE_KINDS
+        .iter()
+        .filter(|(kind, _)| *kind != "message")
+        .filter_map(|(kind, _)| {
+            let html_code = std::fs::read_to_string(template_path(theme_dir, kind)?).ok()?;
+            Some((kind.to_string(), html_code))
+        })
+        .collect()
+}
+
+pub(crate) fn load_event_templates(theme: &str) -> Vec<(String, String)> {
+    match themes_dir() {
+        Ok(themes_dir) if is_valid_theme_name(theme) => read_event_templates(&themes_dir.join(theme)),
+        _ => Vec::new(),
+    }
+}
+
+fn theme_template_path(theme: &str, kind: &str) -> Result<PathBuf, String> {
+    if !is_valid_theme_name(theme) {
+        return Err("Invalid theme name".into());
+    }
+    if kind == "message" {
+        return Err("Messages use the theme's index.html, save it with the theme".into());
+    }
+    template_path(&themes_dir()?.join(theme), kind).ok_or_else(|| format!("Unknown template: {}", kind))
+}
+
+/// Every kind of template, with the theme's code for the ones it has.
+#[tauri::command]
+pub(crate) async fn list_theme_templates(theme: String) -> Result<Vec<EventTemplate>, String> {
+    let (html_code, _) = load_theme_files(&theme)?;
+    let mut templates = load_event_templates(&theme);
+    templates.push(("message".to_string(), html_code));
+
+    Ok(TEMPLATE_KINDS
+        .iter()
+        .map(|(kind, description)| EventTemplate {
+            kind: kind.to_string(),
+            description: description.to_string(),
+            html_code: templates.iter().find(|(known, _)| known == kind).map(|(_, html_code)| html_code.clone()),
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub(crate) async fn save_theme_template(theme: String, kind: String, html_code: String, app: AppHandle) -> Result<(), String> {
+    let path = theme_template_path(&theme, &kind)?;
+    reject_errors(&lint_template(&html_code, &format!("{}/{}.html", TEMPLATES_DIR, kind)))?;
+
+    std::fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+    write_atomically(&path, &html_code).map_err(|e| e.to_string())?;
+    app.emit_to("main", "editor::theme_saved", Some(theme)).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Removes a template, the theme's index.html is used for its events again.
+#[tauri::command]
+pub(crate) async fn remove_theme_template(theme: String, kind: String, app: AppHandle) -> Result<(), String> {
+    let path = theme_template_path(&theme, &kind)?;
+    if path.exists() {
+        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+    }
+    app.emit_to("main", "editor::theme_saved", Some(theme)).map_err(|e| e.to_string())?;
+    Ok(())
+}
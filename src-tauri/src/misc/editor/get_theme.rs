@@ -1,5 +1,5 @@
 use crate::misc::editor::default_themes::{default, sakura};
-use crate::misc::editor::save_theme::ThemeState;
+use crate::misc::editor::theme_registry::ThemeRegistry;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::Write;
@@ -55,17 +55,17 @@ pub(crate) async fn get_theme(theme: String, app: AppHandle) -> Result<Theme, St
             css_code: "".to_string(),
         }),
         _ => {
-            let state = app.state::<Mutex<ThemeState>>();
-            let theme_state = state.lock().unwrap();
-
-            let theme = theme_state.themes.iter().find(|(name, _, _)| name == &theme);
-
-            match theme {
-                Some((_, html_path, css_path)) => {
-                    let html_code = std::fs::read_to_string(html_path).unwrap();
-                    let css_code = std::fs::read_to_string(css_path).unwrap();
+            let state = app.state::<Mutex<ThemeRegistry>>();
+            let registry = state.lock().unwrap();
+
+            match registry.get(&theme) {
+                Some(metadata) => {
+                    let html_code = std::fs::read_to_string(&metadata.html_path)
+                        .map_err(|e| format!("Failed to read {}: {}", metadata.html_path.display(), e))?;
+                    let css_code = std::fs::read_to_string(&metadata.css_path)
+                        .map_err(|e| format!("Failed to read {}: {}", metadata.css_path.display(), e))?;
                     Ok(Theme {
-                        name: theme.unwrap().0.clone(),
+                        name: metadata.name.clone(),
                         html_code,
                         css_code,
                     })
@@ -103,11 +103,12 @@ pub(crate) async fn get_themes(app: AppHandle) -> tauri::Result<Vec<(String, std
         return Ok(vec![("default".to_string(), default_theme_path.join("index.html"), default_theme_path.join("style.css"))]);
     }
 
-    // Get all folders from the themes directory and filter out the ones that are not directories
+    // Get all folders from the themes directory and filter out the ones that are not directories,
+    // hidden ones are imports that are still being unpacked
     let themes = std::fs::read_dir(&themes_path)?
         .filter_map(|entry| {
             entry.ok().and_then(|e| {
-                if e.path().is_dir() {
+                if e.path().is_dir() && !e.file_name().to_string_lossy().starts_with('.') {
                     Some(e)
                 } else {
                     None
@@ -3,4 +3,16 @@ pub(crate) mod get_theme;
 #[macro_use]
 pub(crate) mod get_app_url;
 pub(crate) mod save_theme;
+pub(crate) mod overlay;
+pub(crate) mod overlay_windows;
+pub(crate) mod template;
+pub(crate) mod theme_package;
+pub(crate) mod theme_assets;
+pub(crate) mod theme_history;
+pub(crate) mod theme_drafts;
+pub(crate) mod theme_registry;
+pub(crate) mod theme_watcher;
+pub(crate) mod theme_variables;
+pub(crate) mod event_templates;
+pub(crate) mod theme_lint;
 mod default_themes;
@@ -0,0 +1,228 @@
+use crate::chat::websocket::start_ws::current_server;
+use crate::misc::editor::default_themes::{default, sakura};
+use crate::misc::editor::template::{Template, EVENT_TEMPLATES};
+use crate::misc::editor::theme_package::{is_valid_theme_name, themes_dir};
+use crate::misc::editor::theme_variables::theme_variables_css;
+use std::collections::HashMap;
+use tauri::AppHandle;
+
+// Same assets the webchat window uses, embedded so the overlay works without the app's frontend server
+const TAILWIND_SCRIPT: &str = include_str!("../../../../public/styles/tailwind_complete.css");
+const TRANSITIONS_CSS: &str = include_str!("../../../../public/styles/webchat_transitions.css");
+const TWITCH_ICON: &str = include_str!("../../../../public/icons/brands/twitch_glitch.svg");
+const YOUTUBE_ICON: &str = include_str!("../../../../public/icons/brands/youtube-color.svg");
+
+/// Shows the messages rendered by the server with the theme, the same ones the webchat window would show.
+/// Options come from the page's query string, e.g. `?maxMessages=20&removalTimer=30&fadeOut=true`.
+const OVERLAY_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>United Chat - __THEME_NAME__</title>
+<base href="__THEME_BASE__">
+<script src="/overlay-assets/tailwind.js"></script>
+<style>
+html, body { background: transparent; margin: 0; overflow: hidden; }
+__TRANSITIONS_CSS__
+</style>
+<style>
+__THEME_CSS__
+</style>
+</head>
+<body>
+<div id="message-container" class="bg-transparent flex flex-col"></div>
+<script>
+const THEME = __THEME_JSON__;
+const RENDERED_EVENTS = __RENDERED_EVENTS__;
+const params = new URLSearchParams(location.search);
+const maxMessages = Number(params.get("maxMessages") || 50);
+const removalTimer = Number(params.get("removalTimer") || 0);
+const fadeOut = params.get("fadeOut") === "true";
+const transition = params.get("messageTransition") || "";
+const scaling = Number(params.get("scaling") || 0);
+const opacity = Number(params.get("opacity") || 1);
+
+const container = document.getElementById("message-container");
+for (const [option, property] of [["maxWidth", "maxWidth"], ["maxHeight", "maxHeight"], ["width", "width"], ["height", "height"]]) {
+    if (params.get(option)) container.style[property] = params.get(option) + "px";
+}
+if (scaling) container.style.transform = `scale(${scaling})`;
+if (opacity < 1) container.style.opacity = String(opacity);
+
+const transitionClass = {"slide in": "slide-from-right", "slide bottom": "slide-from-bottom", "typewriter": "typewriter"}[transition] || "";
+
+function remove(element) {
+    if (!element) return;
+    if (!fadeOut) return element.remove();
+    element.classList.add("fade-out");
+    element.addEventListener("transitionend", () => element.remove(), {once: true});
+}
+
+function add(envelope) {
+    const message = envelope.data;
+    // Events other than messages don't always have an id
+    const id = String(message.id ?? `event-${envelope.seq}`);
+    if (!envelope.html || container.querySelector(`[data-id="${CSS.escape(id)}"]`)) return;
+
+    const element = document.createElement("div");
+    element.className = `message event-${envelope.event} flex items-start ${transitionClass}`;
+    element.dataset.id = id;
+    element.dataset.platform = envelope.platform;
+    element.dataset.userId = ((message.tags || []).find(([name]) => name === "user-id") || [])[1] || "";
+    element.innerHTML = envelope.html;
+    container.appendChild(element);
+
+    while (container.children.length > maxMessages) container.firstElementChild.remove();
+    if (removalTimer > 0) setTimeout(() => remove(element), removalTimer * 1000);
+}
+
+const query = new URLSearchParams({theme: THEME, events: [...RENDERED_EVENTS, "message_deleted", "messages_cleared"].join(",")});
+for (const filter of ["channels", "platforms", "token"]) {
+    if (params.get(filter)) query.set(filter, params.get(filter));
+}
+// Reconnects on its own, the server replays what was missed and duplicates are skipped
+const events = new EventSource(`/events?${query}`);
+for (const name of RENDERED_EVENTS) {
+    events.addEventListener(name, event => add(JSON.parse(event.data)));
+}
+events.addEventListener("message_deleted", event => {
+    const data = JSON.parse(event.data).data;
+    remove(container.querySelector(`[data-id="${CSS.escape(data.id)}"]`));
+});
+events.addEventListener("messages_cleared", event => {
+    const {platform, data} = JSON.parse(event.data);
+    for (const element of [...container.children]) {
+        if (element.dataset.platform !== platform) continue;
+        if (!data.user_id || element.dataset.userId === data.user_id) element.remove();
+    }
+});
+
+// Reloads when the theme is edited, a broken version is skipped and the current one stays until it's fixed.
+// The messages come back with the replay.
+const changes = new URLSearchParams({events: "theme_changed", platforms: "united_chat"});
+if (params.get("token")) changes.set("token", params.get("token"));
+new EventSource(`/events?${changes}`).addEventListener("theme_changed", async event => {
+    if (JSON.parse(event.data).data.theme !== THEME) return;
+    const response = await fetch(location.href, {cache: "no-store"}).catch(() => null);
+    if (response && response.ok) location.reload();
+});
+</script>
+</body>
+</html>
+"#;
+
+/// Reads a saved theme, the built-in ones are used as they are when they were never saved.
+pub(crate) fn load_theme_files(theme: &str) -> Result<(String, String), String> {
+    if !is_valid_theme_name(theme) {
+        return Err("Invalid theme name".into());
+    }
+
+    let theme_path = themes_dir()?.join(theme);
+
+    let html_code = std::fs::read_to_string(theme_path.join("index.html")).unwrap_or_default();
+    let css_code = std::fs::read_to_string(theme_path.join("style.css")).unwrap_or_default();
+
+    if !html_code.trim().is_empty() {
+        return Ok((html_code, css_code));
+    }
+
+    match theme {
+        "default" => Ok((default(), css_code)),
+        "sakura" => Ok((sakura(), css_code)),
+        _ => Err("Theme not found".into()),
+    }
+}
+
+/// Builds the overlay page of a theme, served at `/overlay/<theme>`.
+pub(crate) fn render_overlay_page(theme: &str) -> Result<String, String> {
+    let (_, css_code) = load_theme_files(theme)?;
+    // The theme's variables come first, so its CSS can use them
+    build_overlay_page(theme, &format!("{}{}", theme_variables_css(theme), css_code))
+}
+
+fn build_overlay_page(theme: &str, css_code: &str) -> Result<String, String> {
+    // Neither can be allowed to close the tag they're in
+    let theme_json = serde_json::to_string(theme).map_err(|e| e.to_string())?.replace("</", "<\\/");
+    let css_code = css_code.replace("</style", "<\\/style");
+    let theme_name = theme.replace('<', "&lt;").replace('>', "&gt;");
+    let rendered_events: Vec<&str> = EVENT_TEMPLATES.iter().map(|(event, _)| *event).collect();
+    // So the theme can use its assets as "assets/<name>", from the HTML and the CSS
+    let mut base = url::Url::parse("http://localhost/overlay/").map_err(|e| e.to_string())?;
+    base.path_segments_mut()
+        .map_err(|_| "Invalid overlay URL".to_string())?
+        .pop_if_empty()
+        .push(theme)
+        .push("");
+
+    Ok(OVERLAY_PAGE
+        .replace("__THEME_BASE__", base.path())
+        .replace("__TRANSITIONS_CSS__", TRANSITIONS_CSS)
+        .replace("__THEME_CSS__", &css_code)
+        .replace("__THEME_NAME__", &theme_name)
+        .replace("__RENDERED_EVENTS__", &serde_json::to_string(&rendered_events).map_err(|e| e.to_string())?)
+        .replace("__THEME_JSON__", &theme_json))
+}
+
+/// Files the overlay pages load, served at `/overlay-assets/<name>` as (content type, content).
+pub(crate) fn overlay_asset(name: &str) -> Option<(&'static str, &'static str)> {
+    match name {
+        "tailwind.js" => Some(("application/javascript", TAILWIND_SCRIPT)),
+        "twitch_glitch.svg" => Some(("image/svg+xml", TWITCH_ICON)),
+        "youtube-color.svg" => Some(("image/svg+xml", YOUTUBE_ICON)),
+        _ => None,
+    }
+}
+
+/// URL of a theme's overlay page on the running server, the options end up in the query string.
+pub(crate) async fn overlay_url(app: &AppHandle, theme: &str, options: &[(String, String)]) -> Result<String, String> {
+    let (html_code, _) = load_theme_files(theme)?;
+    Template::parse(&html_code).map_err(|e| e.to_string())?;
+
+    let ws_server = current_server(app).ok_or("The chat server isn't running")?;
+    let address = ws_server.local_addr().await.ok_or("The chat server isn't running")?;
+
+    let mut url = url::Url::parse(&format!("http://localhost:{}/overlay", address.port())).map_err(|e| e.to_string())?;
+    url.path_segments_mut()
+        .map_err(|_| "Invalid overlay URL".to_string())?
+        .push(theme);
+    if !options.is_empty() {
+        url.query_pairs_mut().extend_pairs(options.iter());
+    }
+    // The page passes it on to the events it loads
+    if let Some(token) = ws_server.config().token.as_deref().filter(|token| !token.is_empty()) {
+        url.query_pairs_mut().append_pair("token", token);
+    }
+
+    Ok(url.to_string())
+}
+
+/// URL to add a theme as an OBS browser source, the options end up in the query string.
+#[tauri::command]
+pub(crate) async fn get_overlay_url(
+    app: AppHandle,
+    theme: String,
+    options: Option<HashMap<String, String>>,
+) -> Result<String, String> {
+    let options: Vec<(String, String)> = options.unwrap_or_default().into_iter().collect();
+    overlay_url(&app, &theme, &options).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn overlay_page_embeds_the_theme_safely() {
+        let page = build_overlay_page("<mine></script>", "p { color: red; } /* </style> */").unwrap();
+
+        // Neither the theme name nor the CSS can close the tag they're in
+        assert!(page.contains("const THEME = \"<mine><\\/script>\";"));
+        assert!(page.contains("/* <\\/style> */"));
+        assert!(page.contains("United Chat - &lt;mine&gt;&lt;/script&gt;"));
+        assert!(page.contains("<base href=\"/overlay/%3Cmine%3E%3C%2Fscript%3E/\">"));
+        assert!(page.contains(".slide-from-right"));
+
+        assert!(load_theme_files("../secrets").is_err());
+        assert!(overlay_asset("twitch_glitch.svg").is_some());
+    }
+}
@@ -0,0 +1,666 @@
+use crate::chat::websocket::start_ws::current_server;
+use crate::misc::editor::overlay::{load_theme_files, overlay_url};
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Emitter, Listener, LogicalPosition, LogicalSize, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder, WindowEvent, Wry};
+use tokio::sync::mpsc;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+const OVERLAYS_TREE: &str = "overlay_windows";
+const SETTINGS_TREE: &str = "settings";
+const LABEL_PREFIX: &str = "overlay-";
+const PLATFORMS: &[&str] = &["twitch", "youtube"];
+// Below this an overlay is easy to lose track of
+const MIN_OPACITY: f64 = 0.1;
+// Moving or resizing a window sends a stream of events, it's only saved once it stays put this long
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+const SHORTCUT_KEY: &str = "overlay_shortcut";
+const LAST_ID_KEY: &str = "last_overlay_id";
+/// Toggles whether the click-through overlays take clicks, until it's changed
+pub(crate) const DEFAULT_SHORTCUT: &str = "CommandOrControl+Shift+O";
+
+fn default_width() -> f64 {
+    400.0
+}
+
+fn default_height() -> f64 {
+    600.0
+}
+
+fn default_opacity() -> f64 {
+    1.0
+}
+
+/// What an overlay window shows and where.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct OverlaySettings {
+    pub(crate) name: String,
+    pub(crate) theme: String,
+    /// Only these platforms' events, all of them when empty
+    #[serde(default)]
+    pub(crate) platforms: Vec<String>,
+    /// Only these channels' events, all of them when empty
+    #[serde(default)]
+    pub(crate) channels: Vec<String>,
+    /// Options of the overlay page, e.g. `maxMessages` or `fadeOut`
+    #[serde(default)]
+    pub(crate) options: BTreeMap<String, String>,
+    #[serde(default = "default_width")]
+    pub(crate) width: f64,
+    #[serde(default = "default_height")]
+    pub(crate) height: f64,
+    /// Logical position on the screen, the window is centered when there's none
+    #[serde(default)]
+    pub(crate) x: Option<f64>,
+    #[serde(default)]
+    pub(crate) y: Option<f64>,
+    /// Stays above the other windows, games included
+    #[serde(default)]
+    pub(crate) always_on_top: bool,
+    /// Clicks go through to the window below, the shortcut makes it interactive again
+    #[serde(default)]
+    pub(crate) click_through: bool,
+    /// Of the chat, the window itself is transparent
+    #[serde(default = "default_opacity")]
+    pub(crate) opacity: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct OverlayWindow {
+    pub(crate) id: u64,
+    /// Open windows are opened again with the chat, closing one turns this off
+    pub(crate) open: bool,
+    #[serde(flatten)]
+    pub(crate) settings: OverlaySettings,
+}
+
+impl OverlaySettings {
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("The overlay needs a name".into());
+        }
+        load_theme_files(&self.theme)?;
+        if let Some(platform) = self.platforms.iter().find(|platform| !PLATFORMS.contains(&platform.as_str())) {
+            return Err(format!("Unknown platform: {}", platform));
+        }
+        if !(self.width > 0.0 && self.height > 0.0) {
+            return Err("The overlay's size has to be positive".into());
+        }
+        if !(MIN_OPACITY..=1.0).contains(&self.opacity) {
+            return Err(format!("The opacity has to be between {} and 1", MIN_OPACITY));
+        }
+        Ok(())
+    }
+
+    /// Query string of the overlay page, the filters are options like the others.
+    pub(crate) fn page_options(&self) -> Vec<(String, String)> {
+        let mut options: Vec<(String, String)> =
+            self.options.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+        if !self.platforms.is_empty() {
+            options.push(("platforms".to_string(), self.platforms.join(",")));
+        }
+        if !self.channels.is_empty() {
+            options.push(("channels".to_string(), self.channels.join(",")));
+        }
+        if self.opacity < 1.0 {
+            options.push(("opacity".to_string(), self.opacity.to_string()));
+        }
+        options
+    }
+}
+
+/// Whether the shortcut made the click-through overlays take clicks again
+#[derive(Default)]
+pub(crate) struct OverlayInteraction {
+    interactive: AtomicBool,
+}
+
+fn window_label(id: u64) -> String {
+    format!("{}{}", LABEL_PREFIX, id)
+}
+
+fn overlays_tree(app: &AppHandle) -> Result<sled::Tree, String> {
+    let db: Arc<Db> = app.state::<Arc<Db>>().deref().clone();
+    db.open_tree(OVERLAYS_TREE).map_err(|e| e.to_string())
+}
+
+fn settings_tree(app: &AppHandle) -> Result<sled::Tree, String> {
+    let db: Arc<Db> = app.state::<Arc<Db>>().deref().clone();
+    db.open_tree(SETTINGS_TREE).map_err(|e| e.to_string())
+}
+
+/// Every overlay window, oldest first.
+pub(crate) fn read_overlays(tree: &sled::Tree) -> Result<Vec<OverlayWindow>, String> {
+    let mut overlays = Vec::new();
+    for entry in tree.iter() {
+        let (_, value) = entry.map_err(|e| e.to_string())?;
+        match serde_json::from_slice::<OverlayWindow>(&value) {
+            Ok(overlay) => overlays.push(overlay),
+            Err(e) => eprintln!("Skipping invalid overlay window: {}", e),
+        }
+    }
+    Ok(overlays)
+}
+
+pub(crate) fn read_overlay(tree: &sled::Tree, id: u64) -> Result<OverlayWindow, String> {
+    let value = tree.get(id.to_be_bytes()).map_err(|e| e.to_string())?.ok_or("Overlay window not found")?;
+    serde_json::from_slice(&value).map_err(|e| e.to_string())
+}
+
+pub(crate) fn store_overlay(tree: &sled::Tree, overlay: &OverlayWindow) -> Result<(), String> {
+    let value = serde_json::to_vec(overlay).map_err(|e| e.to_string())?;
+    // Ids are big endian so the windows are listed in the order they were created
+    tree.insert(overlay.id.to_be_bytes(), value).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Reserves the id of a new overlay window, the counter is bumped in one step so windows added at the same time
+/// can't get the same id. It carries on from the windows saved before there was a counter.
+fn next_overlay_id(tree: &sled::Tree, settings_tree: &sled::Tree) -> Result<u64, String> {
+    let id_of = |bytes: &[u8]| <[u8; 8]>::try_from(bytes).ok().map(u64::from_be_bytes);
+    let last_saved = tree.last().map_err(|e| e.to_string())?.and_then(|(key, _)| id_of(&key)).unwrap_or(0);
+
+    let id = settings_tree
+        .update_and_fetch(LAST_ID_KEY, |last| {
+            let last = last.and_then(id_of).unwrap_or(0).max(last_saved);
+            Some((last + 1).to_be_bytes().to_vec())
+        })
+        .map_err(|e| e.to_string())?;
+    id.as_deref().and_then(id_of).ok_or_else(|| "Failed to reserve an overlay window id".into())
+}
+
+/// Stores a new overlay window, with the id after the last one.
+pub(crate) fn add_overlay(tree: &sled::Tree, settings_tree: &sled::Tree, settings: OverlaySettings) -> Result<OverlayWindow, String> {
+    let overlay = OverlayWindow { id: next_overlay_id(tree, settings_tree)?, open: true, settings };
+    store_overlay(tree, &overlay)?;
+    Ok(overlay)
+}
+
+fn update_stored(app: &AppHandle, id: u64, update: impl FnOnce(&mut OverlayWindow)) {
+    let result = overlays_tree(app).and_then(|tree| {
+        let mut overlay = read_overlay(&tree, id)?;
+        update(&mut overlay);
+        store_overlay(&tree, &overlay)
+    });
+    if let Err(e) = result {
+        eprintln!("Failed to update overlay window {}: {}", id, e);
+    }
+}
+
+/// Where a window was moved to or what it was resized to, in logical pixels.
+#[derive(Default)]
+struct WindowGeometry {
+    position: Option<(f64, f64)>,
+    size: Option<(f64, f64)>,
+}
+
+/// Saves the window's geometry once it stopped changing, the task ends with the window.
+fn save_geometry_later(app: &AppHandle, id: u64) -> mpsc::UnboundedSender<WindowGeometry> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<WindowGeometry>();
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        while let Some(mut geometry) = rx.recv().await {
+            // Keep the latest of each until nothing changed for a while
+            let mut closed = false;
+            loop {
+                match tokio::time::timeout(SAVE_DELAY, rx.recv()).await {
+                    Ok(Some(change)) => {
+                        geometry.position = change.position.or(geometry.position);
+                        geometry.size = change.size.or(geometry.size);
+                    }
+                    Ok(None) => {
+                        closed = true;
+                        break;
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            update_stored(&app, id, |overlay| {
+                if let Some((x, y)) = geometry.position {
+                    overlay.settings.x = Some(x);
+                    overlay.settings.y = Some(y);
+                }
+                if let Some((width, height)) = geometry.size {
+                    overlay.settings.width = width;
+                    overlay.settings.height = height;
+                }
+            });
+            if closed {
+                break;
+            }
+        }
+    });
+    tx
+}
+
+/// Keeps the stored size and position up to date when the window is moved or resized.
+fn track_window(app: &AppHandle, window: &WebviewWindow, id: u64) {
+    let app = app.clone();
+    let scale_window = window.clone();
+    let geometry = save_geometry_later(&app, id);
+    window.on_window_event(move |event| {
+        let scale_factor = scale_window.scale_factor().unwrap_or(1.0);
+        match event {
+            WindowEvent::Moved(position) => {
+                let position: LogicalPosition<f64> = position.to_logical(scale_factor);
+                let _ = geometry.send(WindowGeometry { position: Some((position.x, position.y)), size: None });
+            }
+            // Minimized windows are resized to nothing
+            WindowEvent::Resized(size) if size.width > 0 && size.height > 0 => {
+                let size: LogicalSize<f64> = size.to_logical(scale_factor);
+                let _ = geometry.send(WindowGeometry { position: None, size: Some((size.width, size.height)) });
+            }
+            WindowEvent::CloseRequested { .. } => {
+                update_stored(&app, id, |overlay| overlay.open = false);
+                app.emit_to("main", "overlay::window_closed", id)
+                    .unwrap_or_else(|e| eprintln!("Failed to emit overlay window close: {}", e));
+            }
+            _ => {}
+        }
+    });
+}
+
+/// Pins the window and lets clicks through as set. Click-through windows lose their title bar, it's back while
+/// they're interactive so they can be moved.
+fn apply_behaviors(app: &AppHandle, window: &WebviewWindow, settings: &OverlaySettings) -> Result<(), String> {
+    let interactive = app.state::<OverlayInteraction>().interactive.load(Ordering::Relaxed);
+    let click_through = settings.click_through && !interactive;
+
+    window.set_always_on_top(settings.always_on_top).map_err(|e| e.to_string())?;
+    window.set_decorations(!click_through).map_err(|e| e.to_string())?;
+    window.set_ignore_cursor_events(click_through).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Opens the overlay's window, or points the open one to its current settings. The chat server has to be running.
+async fn show_overlay(app: &AppHandle, overlay: &OverlayWindow) -> Result<(), String> {
+    let settings = &overlay.settings;
+    let url = overlay_url(app, &settings.theme, &settings.page_options()).await?;
+    let url: url::Url = url.parse().map_err(|e: url::ParseError| e.to_string())?;
+    let title = format!("United Chat - {}", settings.name);
+
+    if let Some(mut window) = app.get_webview_window(&window_label(overlay.id)) {
+        window.set_title(&title).map_err(|e| e.to_string())?;
+        window.set_size(LogicalSize::new(settings.width, settings.height)).map_err(|e| e.to_string())?;
+        if let (Some(x), Some(y)) = (settings.x, settings.y) {
+            window.set_position(LogicalPosition::new(x, y)).map_err(|e| e.to_string())?;
+        }
+        window.navigate(url).map_err(|e| e.to_string())?;
+        apply_behaviors(app, &window, settings)?;
+        return window.show().map_err(|e| e.to_string());
+    }
+
+    let mut builder = WebviewWindowBuilder::new(app, window_label(overlay.id), WebviewUrl::External(url))
+        .title(title)
+        .transparent(true)
+        .resizable(true)
+        .always_on_top(settings.always_on_top)
+        // Click-through windows shouldn't take the focus from the game when they open
+        .focused(!settings.click_through)
+        .inner_size(settings.width, settings.height);
+    builder = match (settings.x, settings.y) {
+        (Some(x), Some(y)) => builder.position(x, y),
+        _ => builder.center(),
+    };
+    let window = builder.build().map_err(|e| e.to_string())?;
+    apply_behaviors(app, &window, settings)?;
+    track_window(app, &window, overlay.id);
+    Ok(())
+}
+
+/// Makes the click-through overlays take clicks, or lets clicks through them again. Returns whether they're interactive.
+pub(crate) fn toggle_interaction(app: &AppHandle) -> bool {
+    let interactive = !app.state::<OverlayInteraction>().interactive.fetch_xor(true, Ordering::Relaxed);
+    let overlays = overlays_tree(app).and_then(|tree| read_overlays(&tree)).unwrap_or_else(|e| {
+        eprintln!("Failed to read the overlay windows: {}", e);
+        Vec::new()
+    });
+    for overlay in overlays.iter().filter(|overlay| overlay.settings.click_through) {
+        if let Some(window) = app.get_webview_window(&window_label(overlay.id)) {
+            if let Err(e) = apply_behaviors(app, &window, &overlay.settings) {
+                eprintln!("Failed to update overlay window {}: {}", overlay.settings.name, e);
+            }
+        }
+    }
+
+    app.emit_to("main", "overlay::interaction_changed", interactive)
+        .unwrap_or_else(|e| eprintln!("Failed to emit overlay interaction: {}", e));
+    interactive
+}
+
+/// Global shortcuts, the only one toggles the overlays' interaction.
+pub(crate) fn shortcut_plugin() -> TauriPlugin<Wry> {
+    tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(|app, _, event| {
+            if event.state() == ShortcutState::Pressed {
+                toggle_interaction(app);
+            }
+        })
+        .build()
+}
+
+/// The shortcut toggling the overlays' interaction, None when it's turned off.
+fn overlay_shortcut(tree: &sled::Tree) -> Result<Option<String>, String> {
+    match tree.get(SHORTCUT_KEY).map_err(|e| e.to_string())? {
+        // Stored empty when it's turned off
+        Some(value) => Ok(Some(String::from_utf8_lossy(&value).into_owned()).filter(|shortcut| !shortcut.is_empty())),
+        None => Ok(Some(DEFAULT_SHORTCUT.to_string())),
+    }
+}
+
+fn parse_shortcut(shortcut: &str) -> Result<Shortcut, String> {
+    shortcut.parse::<Shortcut>().map_err(|e| format!("Invalid shortcut {}: {}", shortcut, e))
+}
+
+/// Registers the saved shortcut, at launch.
+pub(crate) fn register_overlay_shortcut(app: &AppHandle) {
+    let result = settings_tree(app).and_then(|tree| overlay_shortcut(&tree)).and_then(|shortcut| match shortcut {
+        Some(shortcut) => app.global_shortcut().register(parse_shortcut(&shortcut)?).map_err(|e| e.to_string()),
+        None => Ok(()),
+    });
+    if let Err(e) = result {
+        eprintln!("The overlay shortcut isn't available: {}", e);
+    }
+}
+
+/// Opens the windows that were open last time, or points them to the new server when the chat restarts.
+pub(crate) async fn restore_overlay_windows(app: &AppHandle) {
+    let overlays = match overlays_tree(app).and_then(|tree| read_overlays(&tree)) {
+        Ok(overlays) => overlays,
+        Err(e) => {
+            eprintln!("Failed to read the overlay windows: {}", e);
+            return;
+        }
+    };
+    for overlay in overlays.iter().filter(|overlay| overlay.open) {
+        if let Err(e) = show_overlay(app, overlay).await {
+            eprintln!("Failed to open overlay window {}: {}", overlay.settings.name, e);
+        }
+    }
+}
+
+/// Closes the windows when the chat stops, they stay marked as open to come back with it.
+fn hide_overlay_windows(app: &AppHandle) {
+    for (label, window) in app.webview_windows() {
+        if label.starts_with(LABEL_PREFIX) {
+            if let Err(e) = window.destroy() {
+                eprintln!("Failed to close overlay window {}: {}", label, e);
+            }
+        }
+    }
+}
+
+/// Opens the overlay windows whenever the chat server starts and closes them when it stops, from the app's setup.
+pub(crate) fn follow_chat_server(app: &AppHandle) {
+    let started_app = app.clone();
+    app.listen("chat::server_started", move |_| {
+        let app = started_app.clone();
+        tauri::async_runtime::spawn(async move { restore_overlay_windows(&app).await });
+    });
+    let stopped_app = app.clone();
+    app.listen("chat::server_stopped", move |_| hide_overlay_windows(&stopped_app));
+
+    // In case the chat was started before
+    if current_server(app).is_some() {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move { restore_overlay_windows(&app).await });
+    }
+}
+
+/// Points the overlays showing `name` to `new_name`, or removes them when `new_name` is None. Returns the ones changed.
+pub(crate) fn retheme_overlays(tree: &sled::Tree, name: &str, new_name: Option<&str>) -> Result<Vec<OverlayWindow>, String> {
+    let mut changed = Vec::new();
+    for mut overlay in read_overlays(tree)?.into_iter().filter(|overlay| overlay.settings.theme == name) {
+        match new_name {
+            Some(new_name) => {
+                overlay.settings.theme = new_name.to_string();
+                store_overlay(tree, &overlay)?;
+            }
+            None => {
+                tree.remove(overlay.id.to_be_bytes()).map_err(|e| e.to_string())?;
+            }
+        }
+        changed.push(overlay);
+    }
+    Ok(changed)
+}
+
+/// The overlays of a renamed theme follow it, their open windows too.
+pub(crate) async fn rename_overlay_theme(app: &AppHandle, name: &str, new_name: &str) -> Result<(), String> {
+    for overlay in retheme_overlays(&overlays_tree(app)?, name, Some(new_name))? {
+        if app.get_webview_window(&window_label(overlay.id)).is_some() {
+            show_overlay(app, &overlay).await?;
+        }
+    }
+    Ok(())
+}
+
+/// The overlays of a deleted theme have nothing left to show, they're removed with it.
+pub(crate) fn remove_overlay_theme(app: &AppHandle, name: &str) -> Result<(), String> {
+    for overlay in retheme_overlays(&overlays_tree(app)?, name, None)? {
+        if let Some(window) = app.get_webview_window(&window_label(overlay.id)) {
+            window.destroy().map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Opens it right away when the chat is running, it's opened with the chat otherwise.
+async fn open_if_running(app: &AppHandle, overlay: &OverlayWindow) -> Result<(), String> {
+    if current_server(app).is_some() {
+        show_overlay(app, overlay).await?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) async fn list_overlay_windows(app: AppHandle) -> Result<Vec<OverlayWindow>, String> {
+    read_overlays(&overlays_tree(&app)?)
+}
+
+#[tauri::command]
+pub(crate) async fn create_overlay_window(settings: OverlaySettings, app: AppHandle) -> Result<OverlayWindow, String> {
+    settings.validate()?;
+    let overlay = add_overlay(&overlays_tree(&app)?, &settings_tree(&app)?, settings)?;
+    open_if_running(&app, &overlay).await?;
+    Ok(overlay)
+}
+
+/// Changes an overlay's theme, filters, size or position, its window follows if it's open.
+#[tauri::command]
+pub(crate) async fn update_overlay_window(id: u64, settings: OverlaySettings, app: AppHandle) -> Result<OverlayWindow, String> {
+    settings.validate()?;
+    let tree = overlays_tree(&app)?;
+    let mut overlay = read_overlay(&tree, id)?;
+    overlay.settings = settings;
+    store_overlay(&tree, &overlay)?;
+
+    if app.get_webview_window(&window_label(id)).is_some() {
+        show_overlay(&app, &overlay).await?;
+    }
+    Ok(overlay)
+}
+
+#[tauri::command]
+pub(crate) async fn open_overlay_window(id: u64, app: AppHandle) -> Result<OverlayWindow, String> {
+    let tree = overlays_tree(&app)?;
+    let mut overlay = read_overlay(&tree, id)?;
+    overlay.open = true;
+    store_overlay(&tree, &overlay)?;
+    open_if_running(&app, &overlay).await?;
+    Ok(overlay)
+}
+
+/// Closes the window, it stays closed on the next launch.
+#[tauri::command]
+pub(crate) async fn close_overlay_window(id: u64, app: AppHandle) -> Result<OverlayWindow, String> {
+    let tree = overlays_tree(&app)?;
+    let mut overlay = read_overlay(&tree, id)?;
+    overlay.open = false;
+    store_overlay(&tree, &overlay)?;
+
+    if let Some(window) = app.get_webview_window(&window_label(id)) {
+        window.destroy().map_err(|e| e.to_string())?;
+    }
+    Ok(overlay)
+}
+
+#[tauri::command]
+pub(crate) async fn toggle_overlay_interaction(app: AppHandle) -> bool {
+    toggle_interaction(&app)
+}
+
+#[tauri::command]
+pub(crate) async fn get_overlay_shortcut(app: AppHandle) -> Result<Option<String>, String> {
+    overlay_shortcut(&settings_tree(&app)?)
+}
+
+/// Changes the shortcut toggling the overlays' interaction, None turns it off.
+#[tauri::command]
+pub(crate) async fn set_overlay_shortcut(shortcut: Option<String>, app: AppHandle) -> Result<(), String> {
+    let shortcut = shortcut.filter(|shortcut| !shortcut.trim().is_empty());
+    let parsed = shortcut.as_deref().map(parse_shortcut).transpose()?;
+    let tree = settings_tree(&app)?;
+    let previous = overlay_shortcut(&tree)?;
+
+    if shortcut != previous {
+        // The new one first, the old one keeps working if it can't be registered (taken by another app, most likely)
+        if let Some(parsed) = parsed {
+            app.global_shortcut().register(parsed).map_err(|e| e.to_string())?;
+        }
+        if let Some(previous) = previous {
+            let unregistered = parse_shortcut(&previous)
+                .and_then(|parsed_previous| app.global_shortcut().unregister(parsed_previous).map_err(|e| e.to_string()));
+            if let Err(e) = unregistered {
+                eprintln!("Failed to unregister {}: {}", previous, e);
+            }
+        }
+    }
+    tree.insert(SHORTCUT_KEY, shortcut.unwrap_or_default().as_bytes()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) async fn delete_overlay_window(id: u64, app: AppHandle) -> Result<(), String> {
+    overlays_tree(&app)?.remove(id.to_be_bytes()).map_err(|e| e.to_string())?;
+    if let Some(window) = app.get_webview_window(&window_label(id)) {
+        window.destroy().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn settings(name: &str) -> OverlaySettings {
+        serde_json::from_value(serde_json::json!({ "name": name, "theme": "default" })).unwrap()
+    }
+
+    #[test]
+    fn overlays_are_kept_in_order() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree(OVERLAYS_TREE).unwrap();
+        let settings_tree = db.open_tree(SETTINGS_TREE).unwrap();
+
+        let first = add_overlay(&tree, &settings_tree, settings("Chat")).unwrap();
+        let mut second = add_overlay(&tree, &settings_tree, settings("Alerts")).unwrap();
+        assert_eq!((first.id, second.id), (1, 2));
+        assert_eq!((second.settings.width, second.settings.height), (400.0, 600.0));
+
+        second.open = false;
+        second.settings.x = Some(-1280.0);
+        store_overlay(&tree, &second).unwrap();
+        assert_eq!(read_overlays(&tree).unwrap(), vec![first, second]);
+    }
+
+    #[test]
+    fn overlay_ids_are_never_reused() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree(OVERLAYS_TREE).unwrap();
+        let settings_tree = db.open_tree(SETTINGS_TREE).unwrap();
+        // Saved before the counter existed
+        store_overlay(&tree, &OverlayWindow { id: 3, open: false, settings: settings("Old") }).unwrap();
+
+        let ids: Vec<u64> = std::thread::scope(|scope| {
+            let adding: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| add_overlay(&tree, &settings_tree, settings("Chat")).unwrap().id))
+                .collect();
+            adding.into_iter().map(|thread| thread.join().unwrap()).collect()
+        });
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(sorted, (4..12).collect::<Vec<_>>());
+
+        // Nor are the ones of removed windows
+        tree.remove(11u64.to_be_bytes()).unwrap();
+        assert_eq!(add_overlay(&tree, &settings_tree, settings("Chat")).unwrap().id, 12);
+        assert_eq!(read_overlays(&tree).unwrap().len(), 9);
+    }
+
+    #[test]
+    fn overlays_follow_their_theme() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree(OVERLAYS_TREE).unwrap();
+        let settings_tree = db.open_tree(SETTINGS_TREE).unwrap();
+        let chat = add_overlay(&tree, &settings_tree, settings("Chat")).unwrap();
+        let mut alerts = settings("Alerts");
+        alerts.theme = "sakura".into();
+        let alerts = add_overlay(&tree, &settings_tree, alerts).unwrap();
+
+        assert_eq!(retheme_overlays(&tree, "default", Some("mine")).unwrap().len(), 1);
+        assert_eq!(read_overlay(&tree, chat.id).unwrap().settings.theme, "mine");
+        assert_eq!(read_overlay(&tree, alerts.id).unwrap().settings.theme, "sakura");
+
+        assert_eq!(retheme_overlays(&tree, "mine", None).unwrap().len(), 1);
+        assert!(read_overlay(&tree, chat.id).is_err());
+        assert_eq!(read_overlays(&tree).unwrap(), vec![alerts]);
+    }
+
+    #[test]
+    fn shortcut_defaults_until_changed() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree(SETTINGS_TREE).unwrap();
+
+        assert_eq!(overlay_shortcut(&tree).unwrap(), Some(DEFAULT_SHORTCUT.to_string()));
+        tree.insert(SHORTCUT_KEY, "Alt+F9".as_bytes()).unwrap();
+        assert_eq!(overlay_shortcut(&tree).unwrap(), Some("Alt+F9".to_string()));
+        tree.insert(SHORTCUT_KEY, "".as_bytes()).unwrap();
+        assert_eq!(overlay_shortcut(&tree).unwrap(), None);
+    }
+
+    #[test]
+    fn filters_are_page_options() {
+        let mut overlay = settings("Chat");
+        overlay.platforms = vec!["twitch".into()];
+        overlay.channels = vec!["one".into(), "two".into()];
+        overlay.options.insert("maxMessages".into(), "20".into());
+
+        assert_eq!(
+            overlay.page_options(),
+            vec![
+                ("maxMessages".to_string(), "20".to_string()),
+                ("platforms".to_string(), "twitch".to_string()),
+                ("channels".to_string(), "one,two".to_string()),
+            ]
+        );
+        overlay.platforms.push("kick".into());
+        assert!(overlay.validate().is_err());
+
+        // Fully opaque is the page's default
+        let mut overlay = settings("Chat");
+        assert!(overlay.page_options().is_empty());
+        overlay.opacity = 0.5;
+        assert_eq!(overlay.page_options(), vec![("opacity".to_string(), "0.5".to_string())]);
+        overlay.opacity = 0.0;
+        assert!(overlay.validate().is_err());
+    }
+}
@@ -1,20 +1,57 @@
-use std::path::PathBuf;
+use crate::misc::editor::theme_drafts::discard_draft;
+use crate::misc::editor::theme_history::{record_initial_revision, record_save};
+use crate::misc::editor::theme_lint::{lint_theme_code, reject_errors, saved_theme_dir};
+use crate::misc::editor::theme_package::is_valid_theme_name;
+use crate::misc::editor::theme_registry::ThemeRegistry;
+use std::io::Write;
+use std::path::Path;
 use std::sync::Mutex;
 use tauri::{AppHandle, Emitter, Manager};
 
-pub(crate) struct ThemeState {
-    /// (Name, HTML path, CSS path)
-    pub(crate) themes: Vec<(String, PathBuf, PathBuf)>,
+/// Writes to a temporary file next to `path` first, then swaps it in,
+/// so a crash halfway through leaves either the old file or the new one, never half of it.
+pub(crate) fn write_atomically(path: &Path, content: &str) -> std::io::Result<()> {
+    write_files_atomically(&[(path, content)])
 }
 
-#[tauri::command]
-pub(crate) async fn save_theme(html_code: String, css_code: String, theme_name: String, app: AppHandle) -> tauri::Result<bool> {
+/// Like [write_atomically], but only swaps any of the files in once all of them were written,
+/// so files that go together (a theme's HTML and CSS) don't end up from different saves.
+pub(crate) fn write_files_atomically(files: &[(&Path, &str)]) -> std::io::Result<()> {
+    let temp_path = |path: &Path| {
+        let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("theme");
+        path.with_file_name(format!(".{}.tmp", file_name))
+    };
+    let remove_temp_files = || {
+        for (path, _) in files {
+            let _ = std::fs::remove_file(temp_path(path));
+        }
+    };
+
+    for (path, content) in files {
+        let written = std::fs::File::create(temp_path(path)).and_then(|mut file| {
+            file.write_all(content.as_bytes())?;
+            file.sync_all()
+        });
+        if let Err(e) = written {
+            remove_temp_files();
+            return Err(e);
+        }
+    }
+
+    for (path, _) in files {
+        std::fs::rename(temp_path(path), path).inspect_err(|_| remove_temp_files())?;
+    }
+    Ok(())
+}
+
+/// Writes the theme's files and adds it to the theme list if it's new.
+pub(crate) fn write_theme_files(app: &AppHandle, theme_name: &str, html_code: &str, css_code: &str) -> std::io::Result<()> {
     let themes_path = dirs::config_dir().ok_or("Failed to get config directory")
         .unwrap()
         .join("United Chat")
         .join("themes");
 
-    let folder = themes_path.join(theme_name.clone());
+    let folder = themes_path.join(theme_name);
     if !folder.exists() {
         std::fs::create_dir_all(&folder)?;
     }
@@ -22,37 +59,99 @@ pub(crate) async fn save_theme(html_code: String, css_code: String, theme_name:
     let html_path = folder.join("index.html");
     let css_path = folder.join("style.css");
 
-    // Save or update the HTML file
-    std::fs::write(&html_path, html_code)?;
-    // Save or update the CSS file
-    std::fs::write(&css_path, css_code)?;
+    // Save or update both files, the HTML and CSS of a theme are only ever swapped in together
+    write_files_atomically(&[(&html_path, html_code), (&css_path, css_code)])?;
 
     // Update the theme list
-    let state = app.state::<Mutex<ThemeState>>();
-    let mut theme_state = state.lock().unwrap();
+    let state = app.state::<Mutex<ThemeRegistry>>();
+    state.lock().unwrap().refresh(&themes_path, theme_name);
+
+    Ok(())
+}
 
-    let theme = theme_state.themes.iter().find(|(name, _, _)| name == &theme_name);
+/// Saves the theme's files, keeping the previous ones in the history. The draft, if any, is published with it.
+pub(crate) fn publish_theme(app: &AppHandle, theme_name: &str, html_code: &str, css_code: &str) -> std::io::Result<()> {
+    if !is_valid_theme_name(theme_name) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid theme name"));
+    }
 
-    if let Some((_, _, _)) = theme {
-        // Update the theme
-        let theme = theme_state.themes.iter().map(|(name, html, css)| {
-            if name == &theme_name {
-                (name.clone(), html_path.clone(), css_path.clone())
-            } else {
-                (name.clone(), html.clone(), css.clone())
-            }
-        }).collect();
+    let folder = dirs::config_dir().ok_or("Failed to get config directory")
+        .unwrap()
+        .join("United Chat")
+        .join("themes")
+        .join(theme_name);
 
-        theme_state.themes = theme;
-    } else {
-        println!("Adding new theme");
-        // Add the new theme
-        let mut themes = theme_state.themes.clone();
-        themes.push((theme_name.clone(), html_path.clone(), css_path.clone()));
+    // Themes saved before there was a history keep what they had, so this save can be undone too
+    let previous_html = std::fs::read_to_string(folder.join("index.html")).unwrap_or_default();
+    let previous_css = std::fs::read_to_string(folder.join("style.css")).unwrap_or_default();
+    if let Err(e) = record_initial_revision(app, theme_name, &previous_html, &previous_css) {
+        eprintln!("Failed to keep the previous version of {}: {}", theme_name, e);
+    }
 
-        theme_state.themes = themes;
+    write_theme_files(app, theme_name, html_code, css_code)?;
+
+    // The files are saved either way, losing a revision isn't worth failing the save
+    if let Err(e) = record_save(app, theme_name, html_code, css_code) {
+        eprintln!("Failed to store a revision of {}: {}", theme_name, e);
+    }
+    if let Err(e) = discard_draft(app, theme_name) {
+        eprintln!("Failed to remove the draft of {}: {}", theme_name, e);
     }
 
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) async fn save_theme(html_code: String, css_code: String, theme_name: String, app: AppHandle) -> tauri::Result<bool> {
+    if !is_valid_theme_name(&theme_name) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid theme name").into());
+    }
+
+    // Warnings are for the editor to show, only errors keep the theme from being saved
+    let diagnostics = lint_theme_code(&html_code, &css_code, saved_theme_dir(&theme_name).as_deref());
+    reject_errors(&diagnostics).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+    publish_theme(&app, &theme_name, &html_code, &css_code)?;
+
     app.emit_to("main", "editor::theme_saved", Some(theme_name))?;
     Ok(true)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn atomic_writes_replace_the_file() {
+        let dir = std::env::temp_dir().join(format!("united-chat-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("index.html");
+
+        write_atomically(&path, "old").unwrap();
+        write_atomically(&path, "new").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        // Nothing is left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn files_are_only_replaced_together() {
+        let dir = std::env::temp_dir().join(format!("united-chat-save-pair-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let html_path = dir.join("index.html");
+        let css_path = dir.join("style.css");
+        write_files_atomically(&[(&html_path, "old"), (&css_path, "old")]).unwrap();
+
+        // The second file can't be written, so neither is replaced
+        let missing = dir.join("missing/style.css");
+        assert!(write_files_atomically(&[(&html_path, "new"), (&missing, "new")]).is_err());
+        assert_eq!(std::fs::read_to_string(&html_path).unwrap(), "old");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        write_files_atomically(&[(&html_path, "new"), (&css_path, "new")]).unwrap();
+        assert_eq!(std::fs::read_to_string(&css_path).unwrap(), "new");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,768 @@
+use crate::chat::websocket::protocol::Envelope;
+use crate::misc::editor::event_templates::load_event_templates;
+use crate::misc::editor::overlay::load_theme_files;
+use chrono::{Local, TimeZone};
+use serde::Serialize;
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Placeholders a theme can use, with what they're replaced by.
+pub(crate) const PLACEHOLDERS: &[(&str, &str)] = &[
+    ("id", "Id of the message, set it on the main div so old messages can be removed"),
+    ("user", "Display name of the author"),
+    ("formatedMessage", "Message with its emotes as images"),
+    ("raw_message", "Message as it was received"),
+    ("color", "Color of the author's name, empty on YouTube"),
+    ("profile_picture", "Profile picture of the author (*WIP)"),
+    ("platform", "Badge of the platform the message comes from"),
+    ("platform_name", "Name of the platform, \"twitch\" or \"youtube\""),
+    ("channel", "Channel the message was sent in"),
+    ("badge_1", "URL of the first badge of the author"),
+    ("badge_2", "URL of the second badge of the author"),
+    ("badge_3", "URL of the third badge of the author"),
+    ("badges", "Badges of the author as images, can also be used with {#each}"),
+    ("badge_urls", "URLs of the badges of the author, to be used with {#each}"),
+    ("formatedBadges", "Badges of the author, laid out in a row"),
+    ("timestamp", "Time the message was sent at (HH:mm)"),
+    ("event", "Type of the event, e.g. \"message\" or \"raid\""),
+    ("amount", "Months of a subscription, viewers of a raid, what was paid for a super chat"),
+];
+
+/// Templates a theme can have besides its `index.html`, which is used for every event without its own template.
+pub(crate) const TEMPLATE_KINDS: &[(&str, &str)] = &[
+    ("message", "Chat messages, the theme's index.html"),
+    ("action", "/me messages"),
+    ("highlight", "Messages highlighted with channel points"),
+    ("subscription", "Subscriptions, resubscriptions and gifted subscriptions"),
+    ("raid", "Raids"),
+    ("super_chat", "YouTube super chats"),
+    ("system_notice", "Announcements and other notices from Twitch"),
+];
+
+/// (Event, template) of the events themes show, messages can also be actions or highlights.
+pub(crate) const EVENT_TEMPLATES: &[(&str, &str)] = &[
+    ("message", "message"),
+    ("subscription", "subscription"),
+    ("resubscription", "subscription"),
+    ("gift_subscription", "subscription"),
+    ("raid", "raid"),
+    ("super_chat", "super_chat"),
+    ("system_notice", "system_notice"),
+];
+
+// Rendered where a theme has {" "}, JSX habit from the editor's examples
+const BLANK: &str = "⠀";
+
+/// Value of a placeholder, text is escaped when rendered, HTML is inserted as it is.
+#[derive(Clone, Debug)]
+pub(crate) enum Value {
+    Text(String),
+    Html(String),
+    List(Vec<Value>),
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Text(text) | Value::Html(text) => !text.is_empty(),
+            Value::List(items) => !items.is_empty(),
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(text) | Value::Html(text) => Some(text),
+            Value::List(_) => None,
+        }
+    }
+
+    fn render(&self, output: &mut String) {
+        match self {
+            Value::Text(text) => escape_html(text, output),
+            Value::Html(html) => output.push_str(html),
+            Value::List(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        output.push(' ');
+                    }
+                    item.render(output);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) type Context = HashMap<String, Value>;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub(crate) struct TemplateError {
+    pub(crate) message: String,
+    /// Both start at 1
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+}
+
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (line {}, column {})", self.message, self.line, self.column)
+    }
+}
+
+#[derive(Debug)]
+enum Condition {
+    Truthy { name: String, negated: bool },
+    Equals { name: String, value: String, negated: bool },
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Placeholder(String),
+    If { condition: Condition, then: Vec<Node>, otherwise: Vec<Node> },
+    Each { list: String, item: String, body: Vec<Node> },
+}
+
+#[derive(Debug)]
+enum Tag<'a> {
+    Placeholder(&'a str),
+    Blank,
+    If(Condition),
+    Else,
+    EndIf,
+    Each { list: &'a str, item: &'a str },
+    EndEach,
+}
+
+/// A theme parsed once, then rendered for every message.
+///
+/// Besides `{placeholder}`, themes can use blocks:
+/// - `{#if badges}...{:else}...{/if}`, also `{#if !color}` and `{#if platform_name == "twitch"}`
+/// - `{#each badges as badge}...{badge}...{/each}`
+///
+/// HTML comments are left out, braces that aren't one of these (CSS, scripts) are kept as they are.
+#[derive(Debug)]
+pub(crate) struct Template {
+    nodes: Vec<Node>,
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+    /// Loop variables of the {#each} blocks we're in
+    scope: Vec<String>,
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().map(|c| c.is_ascii_alphabetic() || c == '_').unwrap_or(false)
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_condition(expression: &str) -> Option<Condition> {
+    for (operator, negated) in [("==", false), ("!=", true)] {
+        if let Some((name, value)) = expression.split_once(operator) {
+            let name = name.trim();
+            let value = value.trim();
+            let quoted = value.len() >= 2
+                && ((value.starts_with('"') && value.ends_with('"')) || (value.starts_with('\'') && value.ends_with('\'')));
+            if !is_identifier(name) || !quoted {
+                return None;
+            }
+            return Some(Condition::Equals {
+                name: name.to_string(),
+                value: value[1..value.len() - 1].to_string(),
+                negated,
+            });
+        }
+    }
+
+    let (name, negated) = match expression.strip_prefix('!') {
+        Some(name) => (name.trim(), true),
+        None => (expression, false),
+    };
+    is_identifier(name).then(|| Condition::Truthy { name: name.to_string(), negated })
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: impl Into<String>, at: usize) -> TemplateError {
+        let before = &self.source[..at];
+        let line = before.matches('\n').count() + 1;
+        let column = before[before.rfind('\n').map(|i| i + 1).unwrap_or(0)..].chars().count() + 1;
+        TemplateError { message: message.into(), line, column }
+    }
+
+    /// The tag starting at `start` (on a `{`), with its length. None when the braces aren't ours.
+    fn tag_at(&self, start: usize) -> Result<Option<(Tag<'a>, usize)>, TemplateError> {
+        let rest = &self.source[start + 1..];
+        let Some(end) = rest.find(['}', '{', '\n']).filter(|&end| rest[end..].starts_with('}')) else {
+            return Ok(None);
+        };
+        let body = &rest[..end];
+        let len = end + 2;
+
+        let tag = if body == "\" \"" {
+            Tag::Blank
+        } else if is_identifier(body) {
+            Tag::Placeholder(body)
+        } else if let Some(expression) = body.strip_prefix("#if ") {
+            match parse_condition(expression.trim()) {
+                Some(condition) => Tag::If(condition),
+                None => return Err(self.error(format!("Invalid condition \"{}\"", expression.trim()), start)),
+            }
+        } else if let Some(expression) = body.strip_prefix("#each ") {
+            match expression.split_once(" as ").map(|(list, item)| (list.trim(), item.trim())) {
+                Some((list, item)) if is_identifier(list) && is_identifier(item) => Tag::Each { list, item },
+                _ => {
+                    return Err(self.error(
+                        format!("Invalid loop \"{}\", expected {{#each list as item}}", expression.trim()),
+                        start,
+                    ))
+                }
+            }
+        } else {
+            match body.trim() {
+                ":else" => Tag::Else,
+                "/if" => Tag::EndIf,
+                "/each" => Tag::EndEach,
+                _ => return Ok(None),
+            }
+        };
+
+        Ok(Some((tag, len)))
+    }
+
+    fn check_name(&self, name: &str, at: usize) -> Result<(), TemplateError> {
+        let known = PLACEHOLDERS.iter().any(|(placeholder, _)| *placeholder == name)
+            || self.scope.iter().any(|item| item == name);
+        if known {
+            Ok(())
+        } else {
+            Err(self.error(format!("Unknown placeholder {{{}}}", name), at))
+        }
+    }
+
+    /// Parses until the end of the source or of the current block, returning the tag that ended it.
+    fn parse_nodes(&mut self, nodes: &mut Vec<Node>) -> Result<Option<(Tag<'a>, usize)>, TemplateError> {
+        let mut text = String::new();
+
+        while self.position < self.source.len() {
+            let rest = &self.source[self.position..];
+            let Some(next) = rest.find(['{', '<']) else {
+                text.push_str(rest);
+                self.position = self.source.len();
+                break;
+            };
+            text.push_str(&rest[..next]);
+            let start = self.position + next;
+
+            if rest[next..].starts_with("<!--") {
+                self.position = match self.source[start..].find("-->") {
+                    Some(end) => start + end + 3,
+                    None => self.source.len(),
+                };
+                continue;
+            }
+            if rest[next..].starts_with('<') {
+                text.push('<');
+                self.position = start + 1;
+                continue;
+            }
+
+            let Some((tag, len)) = self.tag_at(start)? else {
+                text.push('{');
+                self.position = start + 1;
+                continue;
+            };
+            self.position = start + len;
+
+            match tag {
+                Tag::Blank => text.push_str(BLANK),
+                Tag::Placeholder(name) => {
+                    self.check_name(name, start)?;
+                    if !text.is_empty() {
+                        nodes.push(Node::Text(std::mem::take(&mut text)));
+                    }
+                    nodes.push(Node::Placeholder(name.to_string()));
+                }
+                Tag::If(condition) => {
+                    let name = match &condition {
+                        Condition::Truthy { name, .. } | Condition::Equals { name, .. } => name,
+                    };
+                    self.check_name(name, start)?;
+                    if !text.is_empty() {
+                        nodes.push(Node::Text(std::mem::take(&mut text)));
+                    }
+
+                    let mut then = Vec::new();
+                    let mut otherwise = Vec::new();
+                    match self.parse_nodes(&mut then)? {
+                        Some((Tag::EndIf, _)) => {}
+                        Some((Tag::Else, _)) => match self.parse_nodes(&mut otherwise)? {
+                            Some((Tag::EndIf, _)) => {}
+                            Some((_, at)) => return Err(self.error("Expected {/if}", at)),
+                            None => return Err(self.error("{#if} is never closed", start)),
+                        },
+                        Some((_, at)) => return Err(self.error("Expected {:else} or {/if}", at)),
+                        None => return Err(self.error("{#if} is never closed", start)),
+                    }
+                    nodes.push(Node::If { condition, then, otherwise });
+                }
+                Tag::Each { list, item } => {
+                    self.check_name(list, start)?;
+                    if !text.is_empty() {
+                        nodes.push(Node::Text(std::mem::take(&mut text)));
+                    }
+
+                    let mut body = Vec::new();
+                    self.scope.push(item.to_string());
+                    let end = self.parse_nodes(&mut body)?;
+                    self.scope.pop();
+                    match end {
+                        Some((Tag::EndEach, _)) => {}
+                        Some((_, at)) => return Err(self.error("Expected {/each}", at)),
+                        None => return Err(self.error("{#each} is never closed", start)),
+                    }
+                    nodes.push(Node::Each { list: list.to_string(), item: item.to_string(), body });
+                }
+                end @ (Tag::Else | Tag::EndIf | Tag::EndEach) => {
+                    if !text.is_empty() {
+                        nodes.push(Node::Text(text));
+                    }
+                    return Ok(Some((end, start)));
+                }
+            }
+        }
+
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+        Ok(None)
+    }
+}
+
+impl Template {
+    /// Parses a theme's HTML, failing on unknown placeholders and unbalanced blocks.
+    pub(crate) fn parse(source: &str) -> Result<Self, TemplateError> {
+        let mut parser = Parser { source, position: 0, scope: Vec::new() };
+        let mut nodes = Vec::new();
+
+        match parser.parse_nodes(&mut nodes)? {
+            None => Ok(Self { nodes }),
+            Some((Tag::Else, at)) => Err(parser.error("{:else} outside of an {#if}", at)),
+            Some((Tag::EndEach, at)) => Err(parser.error("{/each} without an {#each}", at)),
+            Some((_, at)) => Err(parser.error("{/if} without an {#if}", at)),
+        }
+    }
+
+    /// Placeholders the theme uses, loop variables aside.
+    pub(crate) fn placeholders(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        collect_placeholders(&self.nodes, &mut Vec::new(), &mut names);
+        names
+    }
+
+    pub(crate) fn render(&self, context: &Context) -> String {
+        let mut output = String::new();
+        let mut scope = Vec::new();
+        render_nodes(&self.nodes, context, &mut scope, &mut output);
+        output.trim().to_string()
+    }
+}
+
+fn collect_placeholders<'a>(nodes: &'a [Node], scope: &mut Vec<&'a str>, names: &mut Vec<String>) {
+    let add = |name: &str, scope: &[&str], names: &mut Vec<String>| {
+        if !scope.contains(&name) && !names.iter().any(|known| known == name) {
+            names.push(name.to_string());
+        }
+    };
+
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Placeholder(name) => add(name, scope, names),
+            Node::If { condition, then, otherwise } => {
+                match condition {
+                    Condition::Truthy { name, .. } | Condition::Equals { name, .. } => add(name, scope, names),
+                }
+                collect_placeholders(then, scope, names);
+                collect_placeholders(otherwise, scope, names);
+            }
+            Node::Each { list, item, body } => {
+                add(list, scope, names);
+                scope.push(item);
+                collect_placeholders(body, scope, names);
+                scope.pop();
+            }
+        }
+    }
+}
+
+fn lookup<'a>(name: &str, context: &'a Context, scope: &[(&str, &'a Value)]) -> Option<&'a Value> {
+    scope
+        .iter()
+        .rev()
+        .find(|(item, _)| *item == name)
+        .map(|(_, value)| *value)
+        .or_else(|| context.get(name))
+}
+
+fn render_nodes<'a>(nodes: &'a [Node], context: &'a Context, scope: &mut Vec<(&'a str, &'a Value)>, output: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Placeholder(name) => {
+                if let Some(value) = lookup(name, context, scope) {
+                    value.render(output);
+                }
+            }
+            Node::If { condition, then, otherwise } => {
+                let matches = match condition {
+                    Condition::Truthy { name, negated } => {
+                        lookup(name, context, scope).map(Value::is_truthy).unwrap_or(false) != *negated
+                    }
+                    Condition::Equals { name, value, negated } => {
+                        (lookup(name, context, scope).and_then(Value::as_str) == Some(value.as_str())) != *negated
+                    }
+                };
+                render_nodes(if matches { then } else { otherwise }, context, scope, output);
+            }
+            Node::Each { list, item, body } => {
+                let items = match lookup(list, context, scope) {
+                    Some(Value::List(items)) => items.as_slice(),
+                    Some(value) => std::slice::from_ref(value),
+                    None => &[],
+                };
+                for value in items {
+                    scope.push((item, value));
+                    render_nodes(body, context, scope, output);
+                    scope.pop();
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn escape_html(text: &str, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            _ => output.push(c),
+        }
+    }
+}
+
+fn platform_badge(platform: &str) -> String {
+    let icon = match platform {
+        "twitch" => "twitch_glitch.svg",
+        "youtube" => "youtube-color.svg",
+        _ => return String::new(),
+    };
+    format!("<img src='/icons/brands/{}' alt='{}' class='w-6 h-6 max-w-[24px] max-h-[24px]'/>", icon, platform)
+}
+
+fn badge_image(url: &str) -> String {
+    let mut escaped = String::new();
+    escape_html(url, &mut escaped);
+    format!("<img src='{}' alt='badge' class='w-6 h-6 max-w-[24px] max-h-[24px]'/>", escaped)
+}
+
+/// Image of an emote in a chat message, its name and URL come from the chat so they're escaped.
+pub(crate) fn emote_image(name: &str, url: &str, class: Option<&str>) -> String {
+    let (mut name_escaped, mut url_escaped) = (String::new(), String::new());
+    escape_html(name, &mut name_escaped);
+    escape_html(url, &mut url_escaped);
+    match class {
+        Some(class) => format!("<img id=\"{0}\" class=\"{2}\" src=\"{1}\" alt=\"{0}\" />", name_escaped, url_escaped, class),
+        None => format!("<img id=\"{0}\" src=\"{1}\" alt=\"{0}\" />", name_escaped, url_escaped),
+    }
+}
+
+/// Values of a chat message, the same ones the webchat window fills in.
+pub(crate) fn message_context(platform: &str, channel: Option<&str>, message: &Json) -> Context {
+    let text = |value: &Json| Value::Text(value.as_str().unwrap_or_default().to_string());
+    let twitch = platform == "twitch";
+
+    let badges: Vec<String> = message[if twitch { "user_badges" } else { "author_badges" }]
+        .as_array()
+        .map(|badges| badges.iter().filter_map(|badge| badge.as_str()).map(String::from).collect())
+        .unwrap_or_default();
+    // Twitch sends milliseconds, YouTube microseconds as a string
+    let timestamp = if twitch {
+        message["timestamp"].as_i64()
+    } else {
+        message["timestamp"].as_str().and_then(|timestamp| timestamp.parse::<i64>().ok()).map(|timestamp| timestamp / 1000)
+    };
+    let formated_badges = if badges.is_empty() {
+        String::new()
+    } else {
+        let images: Vec<String> = badges.iter().map(|badge| badge_image(badge)).collect();
+        format!("<span class='flex flex-row items-center space-x-1'>{}</span>", images.join(""))
+    };
+
+    let mut context = Context::new();
+    context.insert("id".into(), text(&message["id"]));
+    context.insert("user".into(), text(&message[if twitch { "display_name" } else { "author_name" }]));
+    context.insert("formatedMessage".into(), Value::Html(message["message"].as_str().unwrap_or_default().to_string()));
+    context.insert("raw_message".into(), text(if twitch { &message["raw_data"]["raw_message"] } else { &message["message"] }));
+    context.insert("color".into(), if twitch { text(&message["user_color"]) } else { Value::Text(String::new()) });
+    context.insert("profile_picture".into(), Value::Text(String::new()));
+    context.insert("platform".into(), Value::Html(platform_badge(platform)));
+    context.insert("platform_name".into(), Value::Text(platform.to_string()));
+    context.insert("channel".into(), Value::Text(channel.unwrap_or_default().to_string()));
+    for i in 0..3 {
+        context.insert(format!("badge_{}", i + 1), Value::Text(badges.get(i).cloned().unwrap_or_default()));
+    }
+    context.insert("badges".into(), Value::List(badges.iter().map(|badge| Value::Html(badge_image(badge))).collect()));
+    context.insert("badge_urls".into(), Value::List(badges.iter().cloned().map(Value::Text).collect()));
+    context.insert("formatedBadges".into(), Value::Html(formated_badges));
+    context.insert(
+        "timestamp".into(),
+        Value::Text(
+            timestamp
+                .and_then(|timestamp| Local.timestamp_millis_opt(timestamp).single())
+                .map(|time| time.format("%H:%M").to_string())
+                .unwrap_or_default(),
+        ),
+    );
+    context
+}
+
+/// Values of any event, the message ones plus what the other events have.
+pub(crate) fn event_context(envelope: &Envelope) -> Context {
+    let data = &envelope.data;
+    let mut context = message_context(&envelope.platform, envelope.channel.as_deref(), data);
+
+    // Events that aren't chat messages name the user their own way
+    let user_empty = matches!(context.get("user"), Some(Value::Text(user)) if user.is_empty());
+    if user_empty {
+        let user = ["user_name", "from_broadcaster_user_name", "display_name", "author_name"]
+            .iter()
+            .find_map(|field| data[field].as_str());
+        context.insert("user".into(), Value::Text(user.unwrap_or_default().to_string()));
+    }
+    let amount = ["amount", "bits", "viewers", "cumulative_months", "months"]
+        .iter()
+        .map(|field| &data[field])
+        .find(|amount| !amount.is_null())
+        .map(|amount| amount.as_str().map(String::from).unwrap_or_else(|| amount.to_string()));
+    context.insert("amount".into(), Value::Text(amount.unwrap_or_default()));
+    context.insert("event".into(), Value::Text(envelope.event.clone()));
+    // Only the chat messages and super chats are built as HTML, with their text escaped
+    if !matches!(envelope.event.as_str(), "message" | "super_chat") {
+        let text = data["message"].as_str().or_else(|| data["message"]["text"].as_str());
+        context.insert("formatedMessage".into(), Value::Text(text.unwrap_or_default().to_string()));
+    }
+    context
+}
+
+/// The template an event is rendered with, None for events themes don't show.
+pub(crate) fn template_kind(envelope: &Envelope) -> Option<&'static str> {
+    match envelope.event.as_str() {
+        "message" => {
+            let data = &envelope.data;
+            let tag = |name: &str| {
+                data["tags"]
+                    .as_array()
+                    .and_then(|tags| tags.iter().find(|tag| tag[0] == name))
+                    .and_then(|tag| tag[1].as_str())
+                    .map(String::from)
+            };
+            if data["action"].as_bool().unwrap_or(false) {
+                Some("action")
+            } else if tag("msg-id").as_deref() == Some("highlighted-message") {
+                Some("highlight")
+            } else {
+                Some("message")
+            }
+        }
+        event => EVENT_TEMPLATES.iter().find(|(known, _)| *known == event).map(|(_, kind)| *kind),
+    }
+}
+
+/// A theme's templates, `index.html` and the ones in its `templates/` folder.
+pub(crate) struct ThemeTemplates {
+    base: Template,
+    kinds: HashMap<String, Template>,
+}
+
+impl ThemeTemplates {
+    /// Parses them all, an error says which template it's in.
+    pub(crate) fn parse(html_code: &str, kinds: &[(String, String)]) -> Result<Self, String> {
+        let base = Template::parse(html_code).map_err(|e| e.to_string())?;
+        let kinds = kinds
+            .iter()
+            .map(|(kind, source)| match Template::parse(source) {
+                Ok(template) => Ok((kind.clone(), template)),
+                Err(e) => Err(format!("{} template: {}", kind, e)),
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { base, kinds })
+    }
+
+    /// The kind's own template, or the base one
+    pub(crate) fn get(&self, kind: &str) -> &Template {
+        self.kinds.get(kind).unwrap_or(&self.base)
+    }
+}
+
+/// Renders a chat event with a theme, None for events themes don't have a template for.
+pub(crate) fn render_event(templates: &ThemeTemplates, envelope: &Envelope) -> Option<String> {
+    let kind = template_kind(envelope)?;
+    Some(templates.get(kind).render(&event_context(envelope)))
+}
+
+/// Parsed themes, parsed again only when their files change.
+#[derive(Default)]
+pub(crate) struct TemplateCache {
+    templates: Mutex<HashMap<String, (String, Arc<ThemeTemplates>)>>,
+}
+
+impl TemplateCache {
+    pub(crate) fn get(&self, theme: &str) -> Result<Arc<ThemeTemplates>, String> {
+        let (html_code, _) = load_theme_files(theme)?;
+        let kinds = load_event_templates(theme);
+        // Every template's code, to tell when one of them changed
+        let mut sources = html_code.clone();
+        for (kind, source) in &kinds {
+            sources.push_str(&format!("\0{}\0{}", kind, source));
+        }
+
+        let mut templates = self.templates.lock().unwrap();
+        if let Some((cached, template)) = templates.get(theme) {
+            if *cached == sources {
+                return Ok(Arc::clone(template));
+            }
+        }
+
+        let template = Arc::new(ThemeTemplates::parse(&html_code, &kinds)?);
+        templates.insert(theme.to_string(), (sources, Arc::clone(&template)));
+        Ok(template)
+    }
+}
+
+/// Renders a message with the editor's current code, so the preview looks like the overlay will.
+#[tauri::command]
+pub(crate) async fn render_theme_preview(html_code: String, platform: String, message: Json) -> Result<String, String> {
+    let template = Template::parse(&html_code).map_err(|e| e.to_string())?;
+    Ok(template.render(&message_context(&platform, None, &message)))
+}
+
+/// Placeholders themes can use, for the editor's autocompletion.
+#[tauri::command]
+pub(crate) fn get_theme_placeholders() -> Vec<(&'static str, &'static str)> {
+    PLACEHOLDERS.to_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chat::twitch::helpers::message_processor::parse_privmsg;
+    use crate::chat::twitch::user_notices::{parse_notice, parse_user_notice};
+    use crate::misc::editor::default_themes::{default, sakura};
+    use serde_json::json;
+
+    fn message(badges: &[&str]) -> Json {
+        json!({
+            "id": "abc",
+            "timestamp": 0,
+            "display_name": "<Viewer>",
+            "user_color": "#FF0000",
+            "user_badges": badges,
+            "message": "Hello <img src='emote.png'/>",
+            "raw_data": { "raw_message": "Hello Kappa" },
+        })
+    }
+
+    #[test]
+    fn renders_placeholders_and_blocks() {
+        let template = Template::parse(
+            "<div id={id}>{#if badges}{#each badge_urls as url}<i>{url}</i>{/each}{:else}none{/if}\
+             {#if platform_name == \"twitch\"}<b style=\"color: {color}\">{user}</b>{/if}{\" \"}{formatedMessage}</div>",
+        )
+        .unwrap();
+
+        let context = message_context("twitch", Some("channel"), &message(&["a.png", "b.png"]));
+        assert_eq!(
+            template.render(&context),
+            "<div id=abc><i>a.png</i><i>b.png</i><b style=\"color: #FF0000\">&lt;Viewer&gt;</b>⠀Hello <img src='emote.png'/></div>"
+        );
+
+        let context = message_context("youtube", None, &json!({ "id": "x", "author_name": "Y", "author_badges": [], "message": "hi", "timestamp": "0" }));
+        assert_eq!(template.render(&context), "<div id=x>none⠀hi</div>");
+
+        assert_eq!(
+            template.placeholders(),
+            vec!["id", "badges", "badge_urls", "platform_name", "color", "user", "formatedMessage"]
+        );
+
+        let envelope = Envelope::new("twitch", None, "message_deleted", json!({ "id": "abc" }));
+        assert_eq!(render_event(&ThemeTemplates::parse("{user}", &[]).unwrap(), &envelope), None);
+    }
+
+    #[test]
+    fn picks_the_template_of_the_event() {
+        let kinds = vec![
+            ("action".to_string(), "<i>{user} {formatedMessage}</i>".to_string()),
+            ("raid".to_string(), "<b>{user} raids with {amount}</b>".to_string()),
+        ];
+        let templates = ThemeTemplates::parse("<p>{user}: {formatedMessage}</p>", &kinds).unwrap();
+
+        let render = |event: &str, data: Json| render_event(&templates, &Envelope::new("twitch", None, event, data));
+
+        let action = parse_privmsg("@display-name=Viewer;id=abc :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #channel :\u{1}ACTION waves\u{1}").unwrap();
+        assert_eq!(render("message", json!(action)).as_deref(), Some("<i>Viewer waves</i>"));
+        let raid = parse_user_notice("@display-name=Raider;id=def;msg-id=raid;msg-param-viewerCount=42 :tmi.twitch.tv USERNOTICE #channel").unwrap();
+        assert_eq!(render_event(&templates, &raid).as_deref(), Some("<b>Raider raids with 42</b>"));
+        // Without their own template, they fall back to index.html
+        let mut highlight = message(&[]);
+        highlight["tags"] = json!([["msg-id", "highlighted-message"]]);
+        assert_eq!(render("message", highlight).as_deref(), Some("<p>&lt;Viewer&gt;: Hello <img src='emote.png'/></p>"));
+        assert_eq!(render("follow", json!({})), None);
+
+        let templates = ThemeTemplates::parse(
+            "<p>{user}: {formatedMessage}</p>",
+            &[
+                ("super_chat".to_string(), "<b>{user} sent {amount}: {formatedMessage}</b>".to_string()),
+                ("system_notice".to_string(), "<i>{formatedMessage}</i>".to_string()),
+            ],
+        )
+        .unwrap();
+        let super_chat = json!({ "id": "x", "author_name": "Y", "author_badges": [], "message": "a &amp; b", "timestamp": "0", "amount": "$5.00" });
+        let super_chat = Envelope::new("youtube", None, "super_chat", super_chat);
+        assert_eq!(template_kind(&super_chat), Some("super_chat"));
+        assert_eq!(render_event(&templates, &super_chat).as_deref(), Some("<b>Y sent $5.00: a &amp; b</b>"));
+        let notice = parse_notice("@msg-id=slow_on :tmi.twitch.tv NOTICE #channel :Slow <mode>").unwrap();
+        assert_eq!(template_kind(&notice), Some("system_notice"));
+        assert_eq!(render_event(&templates, &notice).as_deref(), Some("<i>Slow &lt;mode&gt;</i>"));
+        let announcement = parse_user_notice("@display-name=Mod;msg-id=announcement :tmi.twitch.tv USERNOTICE #channel :hi").unwrap();
+        assert_eq!(render_event(&templates, &announcement).as_deref(), Some("<i>hi</i>"));
+        // Themes without them show both with index.html
+        let templates = ThemeTemplates::parse("<p>{user}: {formatedMessage}</p>", &[]).unwrap();
+        assert_eq!(render_event(&templates, &super_chat).as_deref(), Some("<p>Y: a &amp; b</p>"));
+        assert_eq!(render_event(&templates, &notice).as_deref(), Some("<p>: Slow &lt;mode&gt;</p>"));
+
+        let error = ThemeTemplates::parse("{user}", &[("raid".to_string(), "{#if user}".to_string())]).err().unwrap();
+        assert!(error.starts_with("raid template:"));
+    }
+
+    #[test]
+    fn reports_errors_with_their_position() {
+        let error = Template::parse("<div>\n  {user} {usr}\n</div>").unwrap_err();
+        assert_eq!(error.message, "Unknown placeholder {usr}");
+        assert_eq!((error.line, error.column), (2, 10));
+
+        assert!(Template::parse("{#if badges}open").unwrap_err().message.contains("never closed"));
+        assert!(Template::parse("{/each}").is_err());
+        assert!(Template::parse("{#each badges as badge}{/each}{badge}").is_err());
+
+        // CSS and comments don't get in the way, nor do the built-in themes
+        assert!(Template::parse("<style>p { color: red; }</style><!-- {whatever} -->").is_ok());
+        assert!(Template::parse(&default()).is_ok());
+        assert!(Template::parse(&sakura()).is_ok());
+    }
+}
@@ -0,0 +1,243 @@
+use crate::misc::editor::theme_package::{is_valid_theme_name, themes_dir};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+/// Folder of a theme holding its fonts, images and sounds, referenced from the theme as `assets/<name>`.
+pub(crate) const ASSETS_DIR: &str = "assets";
+// Big enough for a background video-like GIF or an alert sound
+const MAX_ASSET_SIZE: u64 = 20 * 1024 * 1024;
+
+/// (Extension, content type, kind)
+const ASSET_TYPES: &[(&str, &str, &str)] = &[
+    ("png", "image/png", "image"),
+    ("jpg", "image/jpeg", "image"),
+    ("jpeg", "image/jpeg", "image"),
+    ("gif", "image/gif", "image"),
+    ("webp", "image/webp", "image"),
+    ("svg", "image/svg+xml", "image"),
+    ("woff", "font/woff", "font"),
+    ("woff2", "font/woff2", "font"),
+    ("ttf", "font/ttf", "font"),
+    ("otf", "font/otf", "font"),
+    ("mp3", "audio/mpeg", "sound"),
+    ("ogg", "audio/ogg", "sound"),
+    ("wav", "audio/wav", "sound"),
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct ThemeAsset {
+    /// Path inside the assets folder, e.g. "fonts/Inter.woff2"
+    pub(crate) name: String,
+    pub(crate) size: u64,
+    /// "image", "font" or "sound"
+    pub(crate) kind: String,
+    /// What to put in the theme's HTML or CSS to use it
+    pub(crate) url: String,
+}
+
+fn asset_type(path: &Path) -> Option<&'static (&'static str, &'static str, &'static str)> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    ASSET_TYPES.iter().find(|(known, _, _)| *known == extension)
+}
+
+pub(crate) fn is_asset(path: &Path) -> bool {
+    asset_type(path).is_some()
+}
+
+pub(crate) fn content_type(path: &Path) -> &'static str {
+    asset_type(path).map(|(_, content_type, _)| *content_type).unwrap_or("application/octet-stream")
+}
+
+/// Relative path with forward slashes, how assets are named in URLs, manifests and archives.
+pub(crate) fn asset_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Path of an asset of a theme, refusing anything that would end up outside of its assets folder.
+pub(crate) fn asset_path(theme: &str, name: &str) -> Result<PathBuf, String> {
+    theme_asset_path(&themes_dir()?, theme, name)
+}
+
+fn theme_asset_path(themes_dir: &Path, theme: &str, name: &str) -> Result<PathBuf, String> {
+    if !is_valid_theme_name(theme) {
+        return Err("Invalid theme name".into());
+    }
+
+    let relative = Path::new(name);
+    let only_names = relative.components().all(|component| matches!(component, Component::Normal(_)));
+    if name.is_empty() || name.contains('\\') || !only_names {
+        return Err(format!("Invalid asset name: {}", name));
+    }
+    if !is_asset(relative) {
+        return Err(format!("Unsupported asset type: {}", name));
+    }
+
+    Ok(themes_dir.join(theme).join(ASSETS_DIR).join(relative))
+}
+
+/// Asset files under `dir`, relative to `root`.
+pub(crate) fn collect_assets(dir: &Path, root: &Path, assets: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_assets(&path, root, assets)?;
+        } else if is_asset(&path) {
+            assets.push(path.strip_prefix(root).unwrap_or(&path).to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+fn list_assets(assets_dir: &Path) -> Result<Vec<ThemeAsset>, String> {
+    let mut paths = Vec::new();
+    collect_assets(assets_dir, assets_dir, &mut paths).map_err(|e| e.to_string())?;
+    paths.sort();
+
+    Ok(paths
+        .into_iter()
+        .map(|path| {
+            let name = asset_name(&path);
+            ThemeAsset {
+                size: std::fs::metadata(assets_dir.join(&path)).map(|metadata| metadata.len()).unwrap_or(0),
+                kind: asset_type(&path).map(|(_, _, kind)| kind.to_string()).unwrap_or_default(),
+                url: format!("{}/{}", ASSETS_DIR, name),
+                name,
+            }
+        })
+        .collect())
+}
+
+/// Reads an asset to serve it at `/overlay/<theme>/assets/<name>`, as (content type, content).
+pub(crate) fn read_asset(theme: &str, name: &str) -> Result<(&'static str, Vec<u8>), String> {
+    let path = asset_path(theme, name)?;
+    let content = std::fs::read(&path).map_err(|_| format!("Asset not found: {}", name))?;
+    Ok((content_type(&path), content))
+}
+
+#[tauri::command]
+pub(crate) async fn list_theme_assets(theme: String) -> Result<Vec<ThemeAsset>, String> {
+    if !is_valid_theme_name(&theme) {
+        return Err("Invalid theme name".into());
+    }
+    list_assets(&themes_dir()?.join(&theme).join(ASSETS_DIR))
+}
+
+/// Copies a file into the theme's assets, replacing the one with the same name. `name` defaults to the file's name.
+#[tauri::command]
+pub(crate) async fn add_theme_asset(theme: String, source: String, name: Option<String>) -> Result<ThemeAsset, String> {
+    let source = PathBuf::from(source);
+    let name = match name {
+        Some(name) => name,
+        None => source
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or("Invalid file name")?
+            .to_string(),
+    };
+    copy_asset(&themes_dir()?, &theme, &source, &name)
+}
+
+fn copy_asset(themes_dir: &Path, theme: &str, source: &Path, name: &str) -> Result<ThemeAsset, String> {
+    let destination = theme_asset_path(themes_dir, theme, name)?;
+    // Creating the assets folder would otherwise make a theme out of any name
+    if !themes_dir.join(theme).is_dir() {
+        return Err(format!("Theme not found: {}", theme));
+    }
+
+    let size = std::fs::metadata(source)
+        .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?
+        .len();
+    if size > MAX_ASSET_SIZE {
+        return Err(format!("Assets can't be bigger than {} MB", MAX_ASSET_SIZE / 1024 / 1024));
+    }
+
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    std::fs::copy(source, &destination).map_err(|e| format!("Failed to copy {}: {}", source.display(), e))?;
+
+    let name = asset_name(Path::new(name));
+    Ok(ThemeAsset {
+        size,
+        kind: asset_type(&destination).map(|(_, _, kind)| kind.to_string()).unwrap_or_default(),
+        url: format!("{}/{}", ASSETS_DIR, name),
+        name,
+    })
+}
+
+#[tauri::command]
+pub(crate) async fn remove_theme_asset(theme: String, name: String) -> Result<(), String> {
+    let path = asset_path(&theme, &name)?;
+    std::fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", name, e))?;
+
+    // Leave no empty folders behind, the assets folder itself included
+    let assets_dir = themes_dir()?.join(&theme).join(ASSETS_DIR);
+    let mut parent = path.parent();
+    while let Some(dir) = parent.filter(|dir| dir.starts_with(&assets_dir)) {
+        if std::fs::remove_dir(dir).is_err() {
+            break;
+        }
+        parent = dir.parent();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn asset_paths_stay_in_the_theme() {
+        let path = asset_path("mine", "fonts/Inter.woff2").unwrap();
+        assert!(path.ends_with("themes/mine/assets/fonts/Inter.woff2"));
+        assert_eq!(content_type(&path), "font/woff2");
+
+        assert!(asset_path("mine", "../index.html").is_err());
+        assert!(asset_path("mine", "/etc/passwd.png").is_err());
+        assert!(asset_path("mine", "..\\style.png").is_err());
+        assert!(asset_path("..", "image.png").is_err());
+        assert!(asset_path("mine", "script.js").is_err());
+
+        let dir = std::env::temp_dir().join(format!("united-chat-assets-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sounds")).unwrap();
+        std::fs::write(dir.join("sounds/alert.MP3"), b"sound").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"ignored").unwrap();
+
+        let assets = list_assets(&dir).unwrap();
+        assert_eq!(
+            assets,
+            vec![ThemeAsset {
+                name: "sounds/alert.MP3".into(),
+                size: 5,
+                kind: "sound".into(),
+                url: "assets/sounds/alert.MP3".into(),
+            }]
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn refuses_assets_for_missing_themes() {
+        let themes = std::env::temp_dir().join(format!("united-chat-asset-themes-{}", std::process::id()));
+        std::fs::create_dir_all(themes.join("mine")).unwrap();
+        let source = themes.join("image.png");
+        std::fs::write(&source, b"png").unwrap();
+
+        assert_eq!(copy_asset(&themes, "missing", &source, "image.png"), Err("Theme not found: missing".to_string()));
+        assert!(!themes.join("missing").exists());
+
+        let asset = copy_asset(&themes, "mine", &source, "images/image.png").unwrap();
+        assert_eq!((asset.url.as_str(), asset.size), ("assets/images/image.png", 3));
+        assert_eq!(std::fs::read(themes.join("mine/assets/images/image.png")).unwrap(), b"png");
+        let _ = std::fs::remove_dir_all(themes);
+    }
+}
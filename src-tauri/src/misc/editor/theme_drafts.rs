@@ -0,0 +1,162 @@
+use crate::misc::editor::overlay::load_theme_files;
+use crate::misc::editor::save_theme::publish_theme;
+use crate::misc::editor::theme_lint::{lint_theme_code, reject_errors, saved_theme_dir};
+use crate::misc::editor::theme_package::is_valid_theme_name;
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use std::ops::Deref;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+
+const DRAFTS_TREE: &str = "theme_drafts";
+
+/// What the editor had for a theme, autosaved without touching the published files.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct ThemeDraft {
+    pub(crate) theme: String,
+    pub(crate) html_code: String,
+    pub(crate) css_code: String,
+    /// Local timestamp (ms) of the last autosave
+    pub(crate) updated_at: i64,
+}
+
+fn drafts_tree(app: &AppHandle) -> Result<sled::Tree, String> {
+    let db: Arc<Db> = app.state::<Arc<Db>>().deref().clone();
+    db.open_tree(DRAFTS_TREE).map_err(|e| e.to_string())
+}
+
+pub(crate) fn read_draft(tree: &sled::Tree, theme: &str) -> Result<Option<ThemeDraft>, String> {
+    match tree.get(theme).map_err(|e| e.to_string())? {
+        Some(value) => serde_json::from_slice(&value).map(Some).map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+/// Stores the editor's content as the theme's draft. Content that's the same as the published theme isn't a draft,
+/// any previous one is dropped instead.
+pub(crate) fn store_draft(
+    tree: &sled::Tree,
+    theme: &str,
+    html_code: &str,
+    css_code: &str,
+    published: Option<&(String, String)>,
+) -> Result<Option<ThemeDraft>, String> {
+    if published.map(|(html, css)| html == html_code && css == css_code).unwrap_or(false) {
+        tree.remove(theme).map_err(|e| e.to_string())?;
+        return Ok(None);
+    }
+
+    if let Some(draft) = read_draft(tree, theme)? {
+        if draft.html_code == html_code && draft.css_code == css_code {
+            return Ok(Some(draft));
+        }
+    }
+
+    let draft = ThemeDraft {
+        theme: theme.to_string(),
+        html_code: html_code.to_string(),
+        css_code: css_code.to_string(),
+        updated_at: chrono::Local::now().timestamp_millis(),
+    };
+    let value = serde_json::to_vec(&draft).map_err(|e| e.to_string())?;
+    tree.insert(theme, value).map_err(|e| e.to_string())?;
+    // This is what's left after a crash, it has to be on disk
+    tree.flush().map_err(|e| e.to_string())?;
+    Ok(Some(draft))
+}
+
+pub(crate) fn discard_draft(app: &AppHandle, theme: &str) -> Result<(), String> {
+    drafts_tree(app)?.remove(theme).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub(crate) fn rename_draft(app: &AppHandle, from: &str, to: &str) -> Result<(), String> {
+    let tree = drafts_tree(app)?;
+    if let Some(mut draft) = read_draft(&tree, from)? {
+        draft.theme = to.to_string();
+        let value = serde_json::to_vec(&draft).map_err(|e| e.to_string())?;
+        tree.insert(to, value).map_err(|e| e.to_string())?;
+        tree.remove(from).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Autosaves the editor's content, meant to be called on an interval while editing.
+#[tauri::command]
+pub(crate) async fn save_theme_draft(
+    theme: String,
+    html_code: String,
+    css_code: String,
+    app: AppHandle,
+) -> Result<Option<ThemeDraft>, String> {
+    if !is_valid_theme_name(&theme) {
+        return Err("Invalid theme name".into());
+    }
+    let published = load_theme_files(&theme).ok();
+    store_draft(&drafts_tree(&app)?, &theme, &html_code, &css_code, published.as_ref())
+}
+
+/// The theme's draft, to restore what the editor had after a crash or a reload.
+#[tauri::command]
+pub(crate) async fn get_theme_draft(theme: String, app: AppHandle) -> Result<Option<ThemeDraft>, String> {
+    read_draft(&drafts_tree(&app)?, &theme)
+}
+
+/// Every theme with a draft, most recent first.
+#[tauri::command]
+pub(crate) async fn list_theme_drafts(app: AppHandle) -> Result<Vec<ThemeDraft>, String> {
+    let mut drafts = Vec::new();
+    for entry in drafts_tree(&app)?.iter() {
+        let (_, value) = entry.map_err(|e| e.to_string())?;
+        match serde_json::from_slice::<ThemeDraft>(&value) {
+            Ok(draft) => drafts.push(draft),
+            Err(e) => eprintln!("Skipping invalid theme draft: {}", e),
+        }
+    }
+    drafts.sort_by_key(|draft| std::cmp::Reverse(draft.updated_at));
+    Ok(drafts)
+}
+
+#[tauri::command]
+pub(crate) async fn discard_theme_draft(theme: String, app: AppHandle) -> Result<(), String> {
+    discard_draft(&app, &theme)
+}
+
+/// Saves the draft as the theme's files, same as saving from the editor.
+#[tauri::command]
+pub(crate) async fn publish_theme_draft(theme: String, app: AppHandle) -> Result<(), String> {
+    if !is_valid_theme_name(&theme) {
+        return Err("Invalid theme name".into());
+    }
+    let draft = read_draft(&drafts_tree(&app)?, &theme)?.ok_or("The theme has no draft")?;
+    reject_errors(&lint_theme_code(&draft.html_code, &draft.css_code, saved_theme_dir(&theme).as_deref()))?;
+    publish_theme(&app, &theme, &draft.html_code, &draft.css_code).map_err(|e| e.to_string())?;
+    app.emit_to("main", "editor::theme_saved", Some(theme)).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn drafts_only_keep_unpublished_changes() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree(DRAFTS_TREE).unwrap();
+        let published = ("<p>{user}</p>".to_string(), String::new());
+
+        assert_eq!(store_draft(&tree, "mine", "<p>{user}</p>", "", Some(&published)).unwrap(), None);
+
+        let draft = store_draft(&tree, "mine", "<b>{user}</b>", "", Some(&published)).unwrap().unwrap();
+        assert_eq!(read_draft(&tree, "mine").unwrap(), Some(draft.clone()));
+        // Autosaving the same content again keeps it as it was
+        assert_eq!(store_draft(&tree, "mine", "<b>{user}</b>", "", Some(&published)).unwrap(), Some(draft));
+
+        // Going back to what's published drops the draft
+        store_draft(&tree, "mine", "<p>{user}</p>", "", Some(&published)).unwrap();
+        assert_eq!(read_draft(&tree, "mine").unwrap(), None);
+
+        // A theme that was never published is all draft
+        assert!(store_draft(&tree, "new", "", "", None).unwrap().is_some());
+    }
+}
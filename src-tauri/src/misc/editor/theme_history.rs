@@ -0,0 +1,252 @@
+use crate::misc::editor::save_theme::publish_theme;
+use crate::misc::editor::theme_package::is_valid_theme_name;
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use sled::Db;
+use std::ops::Deref;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+
+const REVISIONS_TREE: &str = "theme_revisions";
+// Older revisions are dropped past this, per theme
+const MAX_REVISIONS: usize = 50;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct ThemeRevision {
+    pub(crate) id: u64,
+    pub(crate) theme: String,
+    pub(crate) html_code: String,
+    pub(crate) css_code: String,
+    /// Local timestamp (ms) of the save
+    pub(crate) saved_at: i64,
+}
+
+/// A revision without its code, for listing them
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct RevisionSummary {
+    pub(crate) id: u64,
+    pub(crate) saved_at: i64,
+    pub(crate) html_size: usize,
+    pub(crate) css_size: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct ThemeDiff {
+    /// Unified diffs, empty when the file didn't change
+    pub(crate) html: String,
+    pub(crate) css: String,
+}
+
+fn revisions_tree(app: &AppHandle) -> Result<sled::Tree, String> {
+    let db: Arc<Db> = app.state::<Arc<Db>>().deref().clone();
+    db.open_tree(REVISIONS_TREE).map_err(|e| e.to_string())
+}
+
+/// Revisions are stored as "<theme>\0<id>", the id in big endian so they're sorted oldest first
+fn theme_prefix(theme: &str) -> Vec<u8> {
+    let mut prefix = theme.as_bytes().to_vec();
+    prefix.push(0);
+    prefix
+}
+
+fn revision_key(theme: &str, id: u64) -> Vec<u8> {
+    let mut key = theme_prefix(theme);
+    key.extend_from_slice(&id.to_be_bytes());
+    key
+}
+
+/// Revisions of a theme, oldest first.
+pub(crate) fn read_revisions(tree: &sled::Tree, theme: &str) -> Result<Vec<ThemeRevision>, String> {
+    let mut revisions = Vec::new();
+    for entry in tree.scan_prefix(theme_prefix(theme)) {
+        let (_, value) = entry.map_err(|e| e.to_string())?;
+        match serde_json::from_slice::<ThemeRevision>(&value) {
+            Ok(revision) => revisions.push(revision),
+            Err(e) => eprintln!("Skipping invalid theme revision: {}", e),
+        }
+    }
+    Ok(revisions)
+}
+
+pub(crate) fn read_revision(tree: &sled::Tree, theme: &str, id: u64) -> Result<ThemeRevision, String> {
+    let value = tree
+        .get(revision_key(theme, id))
+        .map_err(|e| e.to_string())?
+        .ok_or("Revision not found")?;
+    serde_json::from_slice(&value).map_err(|e| e.to_string())
+}
+
+/// Stores a save as a new revision, unless nothing changed since the last one. Returns the latest revision.
+pub(crate) fn record_revision(
+    tree: &sled::Tree,
+    theme: &str,
+    html_code: &str,
+    css_code: &str,
+) -> Result<ThemeRevision, String> {
+    let revisions = read_revisions(tree, theme)?;
+    if let Some(latest) = revisions.last() {
+        if latest.html_code == html_code && latest.css_code == css_code {
+            return Ok(latest.clone());
+        }
+    }
+
+    // Ids only have to grow, the timestamp can go backwards when the clock changes
+    let id = revisions.last().map(|latest| latest.id + 1).unwrap_or(1);
+    let revision = ThemeRevision {
+        id,
+        theme: theme.to_string(),
+        html_code: html_code.to_string(),
+        css_code: css_code.to_string(),
+        saved_at: chrono::Local::now().timestamp_millis(),
+    };
+    let value = serde_json::to_vec(&revision).map_err(|e| e.to_string())?;
+    tree.insert(revision_key(theme, id), value).map_err(|e| e.to_string())?;
+
+    let excess = (revisions.len() + 1).saturating_sub(MAX_REVISIONS);
+    for old in revisions.iter().take(excess) {
+        tree.remove(revision_key(theme, old.id)).map_err(|e| e.to_string())?;
+    }
+
+    tree.flush().map_err(|e| e.to_string())?;
+    Ok(revision)
+}
+
+/// Keeps the files as they were before the first save that goes through the history, so it can be restored too.
+pub(crate) fn record_initial_revision(app: &AppHandle, theme: &str, html_code: &str, css_code: &str) -> Result<(), String> {
+    let tree = revisions_tree(app)?;
+    if tree.scan_prefix(theme_prefix(theme)).next().is_none() && !html_code.trim().is_empty() {
+        record_revision(&tree, theme, html_code, css_code)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn record_save(app: &AppHandle, theme: &str, html_code: &str, css_code: &str) -> Result<ThemeRevision, String> {
+    record_revision(&revisions_tree(app)?, theme, html_code, css_code)
+}
+
+/// Moves a theme's revisions to its new name.
+pub(crate) fn move_revisions(tree: &sled::Tree, from: &str, to: &str) -> Result<(), String> {
+    for mut revision in read_revisions(tree, from)? {
+        tree.remove(revision_key(from, revision.id)).map_err(|e| e.to_string())?;
+        revision.theme = to.to_string();
+        let value = serde_json::to_vec(&revision).map_err(|e| e.to_string())?;
+        tree.insert(revision_key(to, revision.id), value).map_err(|e| e.to_string())?;
+    }
+    tree.flush().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub(crate) fn rename_revisions(app: &AppHandle, from: &str, to: &str) -> Result<(), String> {
+    move_revisions(&revisions_tree(app)?, from, to)
+}
+
+pub(crate) fn remove_revisions(app: &AppHandle, theme: &str) -> Result<(), String> {
+    let tree = revisions_tree(app)?;
+    for entry in tree.scan_prefix(theme_prefix(theme)) {
+        let (key, _) = entry.map_err(|e| e.to_string())?;
+        tree.remove(key).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let diff = TextDiff::from_lines(old, new);
+    if diff.ratio() == 1.0 {
+        return String::new();
+    }
+    diff.unified_diff().context_radius(3).header(old_name, new_name).to_string()
+}
+
+/// Newest first
+#[tauri::command]
+pub(crate) async fn list_theme_revisions(theme: String, app: AppHandle) -> Result<Vec<RevisionSummary>, String> {
+    let revisions = read_revisions(&revisions_tree(&app)?, &theme)?;
+    Ok(revisions
+        .iter()
+        .rev()
+        .map(|revision| RevisionSummary {
+            id: revision.id,
+            saved_at: revision.saved_at,
+            html_size: revision.html_code.len(),
+            css_size: revision.css_code.len(),
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub(crate) async fn get_theme_revision(theme: String, id: u64, app: AppHandle) -> Result<ThemeRevision, String> {
+    read_revision(&revisions_tree(&app)?, &theme, id)
+}
+
+/// Differences between two revisions, `to` defaults to the latest one.
+#[tauri::command]
+pub(crate) async fn diff_theme_revisions(theme: String, from: u64, to: Option<u64>, app: AppHandle) -> Result<ThemeDiff, String> {
+    let tree = revisions_tree(&app)?;
+    let old = read_revision(&tree, &theme, from)?;
+    let new = match to {
+        Some(id) => read_revision(&tree, &theme, id)?,
+        None => read_revisions(&tree, &theme)?.pop().ok_or("Revision not found")?,
+    };
+
+    let name = |revision: &ThemeRevision, file: &str| format!("{}@{}/{}", theme, revision.id, file);
+    Ok(ThemeDiff {
+        html: diff(&old.html_code, &new.html_code, &name(&old, "index.html"), &name(&new, "index.html")),
+        css: diff(&old.css_code, &new.css_code, &name(&old, "style.css"), &name(&new, "style.css")),
+    })
+}
+
+/// Puts a revision back as the theme's files, the restore is a new revision so it can be undone too.
+#[tauri::command]
+pub(crate) async fn restore_theme_revision(theme: String, id: u64, app: AppHandle) -> Result<ThemeRevision, String> {
+    if !is_valid_theme_name(&theme) {
+        return Err("Invalid theme name".into());
+    }
+    let tree = revisions_tree(&app)?;
+    let revision = read_revision(&tree, &theme, id)?;
+
+    publish_theme(&app, &theme, &revision.html_code, &revision.css_code).map_err(|e| e.to_string())?;
+    let restored = read_revisions(&tree, &theme)?.pop().ok_or("Revision not found")?;
+
+    app.emit_to("main", "editor::theme_saved", Some(theme)).map_err(|e| e.to_string())?;
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keeps_revisions_per_theme() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree(REVISIONS_TREE).unwrap();
+
+        let first = record_revision(&tree, "mine", "<p>{user}</p>\n", "").unwrap();
+        // Saving the same code again isn't a new revision
+        assert_eq!(record_revision(&tree, "mine", "<p>{user}</p>\n", "").unwrap(), first);
+        let second = record_revision(&tree, "mine", "<b>{user}</b>\n", "b {}\n").unwrap();
+        record_revision(&tree, "mine 2", "other", "").unwrap();
+
+        let revisions = read_revisions(&tree, "mine").unwrap();
+        assert_eq!(revisions.iter().map(|revision| revision.id).collect::<Vec<_>>(), vec![first.id, second.id]);
+        assert_eq!(read_revision(&tree, "mine", second.id).unwrap().css_code, "b {}\n");
+
+        let html = diff(&first.html_code, &second.html_code, "a", "b");
+        assert!(html.contains("-<p>{user}</p>") && html.contains("+<b>{user}</b>"));
+        assert_eq!(diff(&first.css_code, &first.css_code, "a", "b"), "");
+
+        for i in 0..MAX_REVISIONS {
+            record_revision(&tree, "mine", &format!("revision {}", i), "").unwrap();
+        }
+        let revisions = read_revisions(&tree, "mine").unwrap();
+        assert_eq!(revisions.len(), MAX_REVISIONS);
+        assert_eq!(revisions.last().unwrap().html_code, format!("revision {}", MAX_REVISIONS - 1));
+        assert_eq!(read_revisions(&tree, "mine 2").unwrap().len(), 1);
+
+        move_revisions(&tree, "mine", "renamed").unwrap();
+        assert!(read_revisions(&tree, "mine").unwrap().is_empty());
+        let revisions = read_revisions(&tree, "renamed").unwrap();
+        assert_eq!(revisions.len(), MAX_REVISIONS);
+        assert!(revisions.iter().all(|revision| revision.theme == "renamed"));
+        assert_eq!(read_revisions(&tree, "mine 2").unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,362 @@
+use crate::misc::editor::event_templates::{read_event_templates, TEMPLATES_DIR};
+use crate::misc::editor::template::Template;
+use crate::misc::editor::theme_assets::{asset_name, collect_assets, ASSETS_DIR};
+use crate::misc::editor::theme_package::{is_valid_theme_name, themes_dir, MAX_UNPACKED_SIZE};
+use serde::Serialize;
+use std::path::Path;
+
+// Assets past this are loaded by OBS on every overlay refresh, they're worth a warning
+const LARGE_ASSET_SIZE: u64 = 2 * 1024 * 1024;
+
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Severity {
+    /// The theme isn't saved
+    Error,
+    Warning,
+}
+
+/// A problem found in a theme, diagnostics about a whole file point at its start.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub(crate) struct Diagnostic {
+    pub(crate) severity: Severity,
+    pub(crate) message: String,
+    /// Relative to the theme folder, e.g. `index.html` or `templates/raid.html`
+    pub(crate) file: String,
+    /// Both start at 1
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+    }
+}
+
+struct Lint<'a> {
+    source: &'a str,
+    file: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Lint<'a> {
+    fn report(&mut self, severity: Severity, message: impl Into<String>, at: usize) {
+        let before = &self.source[..at];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        self.diagnostics.push(Diagnostic { severity, message: message.into(), file: self.file.to_string(), line, column });
+    }
+}
+
+/// End of the tag starting at `start`, skipping `>` in quoted attributes.
+fn tag_end(source: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in source[start..].char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(start + index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Value of an attribute in a tag's source, without its quotes.
+fn attribute<'t>(tag: &'t str, name: &str) -> Option<&'t str> {
+    let mut rest = tag.trim_start_matches('<');
+    rest = rest.trim_start_matches(|c: char| !c.is_whitespace() && c != '>');
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() || rest.starts_with('>') {
+            return None;
+        }
+        let name_end = rest.find(|c: char| c.is_whitespace() || c == '=' || c == '>').unwrap_or(rest.len());
+        let (attribute_name, after) = rest.split_at(name_end);
+        rest = after.trim_start();
+
+        let mut value = "";
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (found, remaining) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let end = after[1..].find(quote).map(|end| end + 1).unwrap_or(after.len());
+                    (&after[1..end], after.get(end + 1..).unwrap_or(""))
+                }
+                _ => after.split_at(after.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(after.len())),
+            };
+            value = found;
+            rest = remaining;
+        }
+        if attribute_name.eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+    }
+}
+
+fn is_external(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    url.starts_with("http://") || url.starts_with("https://") || url.starts_with("//")
+}
+
+/// Checks that tags are closed in order and that scripts are the theme's own.
+fn check_tags(lint: &mut Lint) {
+    let source = lint.source;
+    // ASCII lowercasing keeps the offsets the same
+    let lowered = source.to_ascii_lowercase();
+    let mut open: Vec<(String, usize)> = Vec::new();
+    let mut position = 0;
+
+    while let Some(found) = source[position..].find('<') {
+        let start = position + found;
+        let rest = &source[start..];
+        if rest.starts_with("<!--") {
+            match rest.find("-->") {
+                Some(end) => position = start + end + 3,
+                None => {
+                    lint.report(Severity::Warning, "This comment is never closed", start);
+                    return;
+                }
+            }
+            continue;
+        }
+
+        let closing = rest.starts_with("</");
+        let name_start = start + if closing { 2 } else { 1 };
+        let name: String = source[name_start..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect::<String>()
+            .to_ascii_lowercase();
+        // A lone `<` in text, or a doctype
+        if name.is_empty() {
+            position = start + 1;
+            continue;
+        }
+        let Some(end) = tag_end(source, name_start) else {
+            lint.report(Severity::Warning, format!("<{}> is missing its closing >", name), start);
+            return;
+        };
+        position = end + 1;
+
+        if closing {
+            match open.iter().rposition(|(tag, _)| *tag == name) {
+                Some(index) => {
+                    for (tag, at) in open.split_off(index + 1) {
+                        lint.report(Severity::Warning, format!("<{}> is never closed", tag), at);
+                    }
+                    open.pop();
+                }
+                None => lint.report(Severity::Warning, format!("</{}> doesn't close any tag", name), start),
+            }
+            continue;
+        }
+
+        let tag = &source[start..=end];
+        if name == "script" && attribute(tag, "src").is_some_and(is_external) {
+            lint.report(
+                Severity::Error,
+                "Scripts from other sites aren't allowed, add the file to the theme's assets instead",
+                start,
+            );
+        }
+        if VOID_ELEMENTS.contains(&name.as_str()) || tag.ends_with("/>") {
+            continue;
+        }
+        open.push((name.clone(), start));
+
+        // Their content isn't HTML, only their closing tag counts
+        if name == "script" || name == "style" {
+            match lowered[position..].find(&format!("</{}", name)) {
+                Some(found) => position += found,
+                None => break,
+            }
+        }
+    }
+
+    for (tag, at) in open {
+        lint.report(Severity::Warning, format!("<{}> is never closed", tag), at);
+    }
+}
+
+/// Checks that braces are balanced, comments and strings aside.
+fn check_css(lint: &mut Lint) {
+    let source = lint.source;
+    let mut open = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '/' if chars.peek().map(|(_, next)| *next) == Some('*') => match source[index + 2..].find("*/") {
+                Some(end) => {
+                    let after = index + 2 + end + 2;
+                    while chars.peek().is_some_and(|(next, _)| *next < after) {
+                        chars.next();
+                    }
+                }
+                None => {
+                    lint.report(Severity::Warning, "This comment is never closed", index);
+                    return;
+                }
+            },
+            '"' | '\'' => {
+                for (_, next) in chars.by_ref() {
+                    if next == c || next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '{' => open.push(index),
+            '}' if open.pop().is_none() => lint.report(Severity::Warning, "This } doesn't close any {", index),
+            _ => {}
+        }
+    }
+
+    for at in open {
+        lint.report(Severity::Warning, "This { is never closed", at);
+    }
+}
+
+/// Lints a template's HTML: its placeholders and blocks, then its tags.
+pub(crate) fn lint_template(html_code: &str, file: &str) -> Vec<Diagnostic> {
+    let mut lint = Lint { source: html_code, file, diagnostics: Vec::new() };
+    match Template::parse(html_code) {
+        Ok(template) if file == "index.html" && !template.placeholders().iter().any(|name| name == "id") => {
+            lint.report(
+                Severity::Warning,
+                "The template has no {id}, messages deleted in chat won't be removed from the overlay",
+                0,
+            );
+        }
+        Ok(_) => {}
+        Err(e) => lint.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            message: e.message,
+            file: file.to_string(),
+            line: e.line,
+            column: e.column,
+        }),
+    }
+    check_tags(&mut lint);
+    lint.diagnostics
+}
+
+pub(crate) fn lint_css(css_code: &str) -> Vec<Diagnostic> {
+    let mut lint = Lint { source: css_code, file: "style.css", diagnostics: Vec::new() };
+    check_css(&mut lint);
+    lint.diagnostics
+}
+
+/// Warns about assets big enough to slow the overlay down, or the theme past what can be exported.
+pub(crate) fn lint_assets(theme_dir: &Path) -> Vec<Diagnostic> {
+    let mut assets = Vec::new();
+    if collect_assets(&theme_dir.join(ASSETS_DIR), theme_dir, &mut assets).is_err() {
+        return Vec::new();
+    }
+
+    let mut diagnostics = Vec::new();
+    let mut total = 0;
+    for path in assets {
+        let size = std::fs::metadata(theme_dir.join(&path)).map(|metadata| metadata.len()).unwrap_or(0);
+        total += size;
+        if size > LARGE_ASSET_SIZE {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                message: format!("{:.1} MB, big assets slow down the overlay", size as f64 / 1024.0 / 1024.0),
+                file: format!("{}/{}", ASSETS_DIR, asset_name(&path)),
+                line: 1,
+                column: 1,
+            });
+        }
+    }
+    if total > MAX_UNPACKED_SIZE {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            message: format!("The assets are over {} MB, the theme can't be exported", MAX_UNPACKED_SIZE / 1024 / 1024),
+            file: ASSETS_DIR.to_string(),
+            line: 1,
+            column: 1,
+        });
+    }
+    diagnostics
+}
+
+/// Everything wrong with a theme, the saved one's event templates and assets included when it has a folder.
+pub(crate) fn lint_theme_code(html_code: &str, css_code: &str, theme_dir: Option<&Path>) -> Vec<Diagnostic> {
+    let mut diagnostics = lint_template(html_code, "index.html");
+    diagnostics.extend(lint_css(css_code));
+    if let Some(theme_dir) = theme_dir {
+        for (kind, template) in read_event_templates(theme_dir) {
+            diagnostics.extend(lint_template(&template, &format!("{}/{}.html", TEMPLATES_DIR, kind)));
+        }
+        diagnostics.extend(lint_assets(theme_dir));
+    }
+    diagnostics
+}
+
+/// Fails with the errors, if any, warnings don't keep a theme from being saved.
+pub(crate) fn reject_errors(diagnostics: &[Diagnostic]) -> Result<(), String> {
+    let errors: Vec<String> = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+pub(crate) fn saved_theme_dir(theme: &str) -> Option<std::path::PathBuf> {
+    let theme_dir = themes_dir().ok()?.join(theme);
+    (is_valid_theme_name(theme) && theme_dir.is_dir()).then_some(theme_dir)
+}
+
+/// Lints the editor's content, for it to show the diagnostics inline.
+#[tauri::command]
+pub(crate) async fn lint_theme(theme: Option<String>, html_code: String, css_code: String) -> Vec<Diagnostic> {
+    let theme_dir = theme.as_deref().and_then(saved_theme_dir);
+    lint_theme_code(&html_code, &css_code, theme_dir.as_deref())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::misc::editor::default_themes::{default, sakura};
+
+    #[test]
+    fn built_in_themes_are_clean() {
+        assert_eq!(lint_template(&default(), "index.html"), Vec::new());
+        assert_eq!(lint_template(&sakura(), "index.html"), Vec::new());
+    }
+
+    #[test]
+    fn reports_where_the_problems_are() {
+        let html = "<div id={id}>\n  <p>{user}\n</div>\n<script src=\"https://example.com/a.js\"></script>";
+        let diagnostics = lint_template(html, "index.html");
+        let found: Vec<(Severity, usize, usize)> =
+            diagnostics.iter().map(|diagnostic| (diagnostic.severity, diagnostic.line, diagnostic.column)).collect();
+        assert_eq!(found, vec![(Severity::Warning, 2, 3), (Severity::Error, 4, 1)]);
+        assert!(reject_errors(&diagnostics).is_err());
+
+        let diagnostics = lint_template("<p>{usr}</p><img src={profile_picture}>", "index.html");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].severity, diagnostics[0].line, diagnostics[0].column), (Severity::Error, 1, 4));
+
+        // Only messages need an id
+        let diagnostics = lint_template("<p>{user}</p>", "index.html");
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(reject_errors(&diagnostics).is_ok());
+        assert_eq!(lint_template("<p>{user}</p>", "templates/raid.html"), Vec::new());
+
+        let diagnostics = lint_css("p { color: red; }\n/* { */\n.a { content: \"}\"; ");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (3, 4));
+    }
+}
@@ -0,0 +1,465 @@
+use crate::misc::editor::event_templates::{is_template_kind, read_event_templates, TEMPLATES_DIR};
+use crate::misc::editor::overlay::load_theme_files;
+use crate::misc::editor::save_theme::write_atomically;
+use crate::misc::editor::theme_drafts::discard_draft;
+use crate::misc::editor::theme_history::remove_revisions;
+use crate::misc::editor::theme_registry::ThemeRegistry;
+use crate::misc::editor::template::{Template, PLACEHOLDERS};
+use crate::misc::editor::theme_assets::{asset_name, collect_assets, is_asset, ASSETS_DIR};
+use crate::misc::editor::theme_variables::{validate_variables, ThemeVariable};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub(crate) const MANIFEST_FILE: &str = "theme.json";
+// Guards against archives that unpack to far more than they weigh
+const MAX_FILES: usize = 200;
+pub(crate) const MAX_UNPACKED_SIZE: u64 = 50 * 1024 * 1024;
+
+/// `theme.json`, describes a packaged theme. Installed themes keep theirs as their metadata.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct ThemeManifest {
+    pub(crate) name: String,
+    /// Shown instead of the folder's name when set
+    #[serde(default)]
+    pub(crate) display_name: Option<String>,
+    #[serde(default)]
+    pub(crate) author: Option<String>,
+    #[serde(default = "default_version")]
+    pub(crate) version: String,
+    #[serde(default)]
+    pub(crate) description: Option<String>,
+    /// Path of the preview image in the archive
+    #[serde(default)]
+    pub(crate) preview: Option<String>,
+    /// Placeholders the theme needs, it's refused when this app doesn't know one of them
+    #[serde(default)]
+    pub(crate) placeholders: Vec<String>,
+    /// Fonts, images and sounds bundled under `assets/`
+    #[serde(default)]
+    pub(crate) assets: Vec<String>,
+    /// Local timestamp (ms) of when the theme was created or installed, not packaged
+    #[serde(default)]
+    pub(crate) created_at: Option<i64>,
+    /// Settings the theme's CSS uses, see `theme_variables`
+    #[serde(default)]
+    pub(crate) variables: Vec<ThemeVariable>,
+}
+
+impl Default for ThemeManifest {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            display_name: None,
+            author: None,
+            version: default_version(),
+            description: None,
+            preview: None,
+            placeholders: Vec::new(),
+            assets: Vec::new(),
+            created_at: None,
+            variables: Vec::new(),
+        }
+    }
+}
+
+fn default_version() -> String {
+    "1.0.0".to_string()
+}
+
+pub(crate) fn read_manifest(theme_dir: &Path) -> Option<ThemeManifest> {
+    let manifest = std::fs::read_to_string(theme_dir.join(MANIFEST_FILE)).ok()?;
+    serde_json::from_str(&manifest).ok()
+}
+
+pub(crate) fn write_manifest(theme_dir: &Path, manifest: &ThemeManifest) -> std::io::Result<()> {
+    write_atomically(&theme_dir.join(MANIFEST_FILE), &serde_json::to_string_pretty(manifest)?)
+}
+
+/// What to do when a theme with the same name is already installed
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ImportConflict {
+    #[default]
+    Fail,
+    Overwrite,
+    /// Installs it as "name (2)", "name (3)"...
+    Rename,
+}
+
+/// An archive's content, validated and ready to be written to a theme folder.
+#[derive(Debug)]
+pub(crate) struct ThemePackage {
+    pub(crate) manifest: ThemeManifest,
+    /// Relative paths, theme.json aside
+    pub(crate) files: Vec<(PathBuf, Vec<u8>)>,
+}
+
+/// The kind of a template in an archive, from its name, e.g. "templates/raid.html"
+fn event_template_kind(name: &str) -> Option<&str> {
+    name.strip_prefix(TEMPLATES_DIR)
+        .and_then(|name| name.strip_prefix('/'))
+        .and_then(|file| file.strip_suffix(".html"))
+        .filter(|kind| *kind != "message" && is_template_kind(kind))
+}
+
+pub(crate) fn is_valid_theme_name(name: &str) -> bool {
+    !name.trim().is_empty() && !name.contains(['/', '\\']) && !name.contains("..") && name.len() <= 64
+}
+
+pub(crate) fn themes_dir() -> Result<PathBuf, String> {
+    Ok(dirs::config_dir()
+        .ok_or("Failed to get config directory")?
+        .join("United Chat")
+        .join("themes"))
+}
+
+/// Writes a theme folder as an archive. The folder's own theme.json, when there is one, provides what can't be
+/// worked out from the files (author, description...).
+pub(crate) fn write_package<W: Write + Seek>(
+    theme_dir: &Path,
+    name: &str,
+    html_code: &str,
+    css_code: &str,
+    writer: W,
+) -> Result<ThemeManifest, String> {
+    let template = Template::parse(html_code).map_err(|e| format!("The theme has an error: {}", e))?;
+
+    let mut manifest = read_manifest(theme_dir).unwrap_or_default();
+    manifest.name = name.to_string();
+    manifest.created_at = None;
+    manifest.placeholders = template.placeholders();
+    let event_templates = read_event_templates(theme_dir);
+    for (kind, html_code) in &event_templates {
+        let template = Template::parse(html_code).map_err(|e| format!("The {} template has an error: {}", kind, e))?;
+        for placeholder in template.placeholders() {
+            if !manifest.placeholders.contains(&placeholder) {
+                manifest.placeholders.push(placeholder);
+            }
+        }
+    }
+
+    let mut assets = Vec::new();
+    collect_assets(&theme_dir.join(ASSETS_DIR), theme_dir, &mut assets).map_err(|e| e.to_string())?;
+    assets.sort();
+    manifest.assets = assets.iter().map(|asset| asset_name(asset)).collect();
+
+    let preview = std::fs::read_dir(theme_dir)
+        .ok()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.file_stem().map(|stem| stem == "preview").unwrap_or(false) && is_asset(path));
+    manifest.preview = preview.as_ref().and_then(|path| path.file_name()).map(|name| name.to_string_lossy().into_owned());
+
+    let mut zip = ZipWriter::new(writer);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut add = |path: &str, content: &[u8]| -> Result<(), String> {
+        zip.start_file(path, options).map_err(|e| e.to_string())?;
+        zip.write_all(content).map_err(|e| e.to_string())
+    };
+
+    let manifest_json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    add(MANIFEST_FILE, manifest_json.as_bytes())?;
+    add("index.html", html_code.as_bytes())?;
+    add("style.css", css_code.as_bytes())?;
+    for (kind, html_code) in &event_templates {
+        add(&format!("{}/{}.html", TEMPLATES_DIR, kind), html_code.as_bytes())?;
+    }
+    for file in assets.iter().map(|asset| theme_dir.join(asset)).chain(preview.clone()) {
+        let content = std::fs::read(&file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+        add(&asset_name(file.strip_prefix(theme_dir).unwrap_or(&file)), &content)?;
+    }
+
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(manifest)
+}
+
+/// Reads and validates an archive, nothing is written yet.
+pub(crate) fn read_package<R: Read + Seek>(reader: R) -> Result<ThemePackage, String> {
+    let mut archive = ZipArchive::new(reader).map_err(|e| format!("Not a theme archive: {}", e))?;
+    if archive.len() > MAX_FILES {
+        return Err(format!("The archive has more than {} files", MAX_FILES));
+    }
+
+    let mut manifest = None;
+    let mut files = Vec::new();
+    let mut unpacked_size = 0;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
+        if file.is_dir() {
+            continue;
+        }
+        let path = file
+            .enclosed_name()
+            .ok_or_else(|| format!("Invalid path in the archive: {}", file.name()))?;
+
+        unpacked_size += file.size();
+        if unpacked_size > MAX_UNPACKED_SIZE {
+            return Err("The archive is too big".into());
+        }
+
+        let name = asset_name(&path);
+        let allowed = matches!(name.as_str(), MANIFEST_FILE | "index.html" | "style.css")
+            || (name.starts_with("assets/") && is_asset(&path))
+            || event_template_kind(&name).is_some()
+            || (path.parent() == Some(Path::new("")) && path.file_stem().map(|stem| stem == "preview").unwrap_or(false) && is_asset(&path));
+        if !allowed {
+            return Err(format!("Unexpected file in the archive: {}", name));
+        }
+
+        let mut content = Vec::with_capacity(file.size() as usize);
+        // The size in the header could lie, the limit still holds while reading
+        file.by_ref().take(MAX_UNPACKED_SIZE).read_to_end(&mut content).map_err(|e| e.to_string())?;
+
+        if name == MANIFEST_FILE {
+            manifest = Some(serde_json::from_slice::<ThemeManifest>(&content).map_err(|e| format!("Invalid theme.json: {}", e))?);
+        } else {
+            files.push((path, content));
+        }
+    }
+
+    let manifest = manifest.ok_or("The archive has no theme.json")?;
+    let package = ThemePackage { manifest, files };
+    package.validate()?;
+    Ok(package)
+}
+
+impl ThemePackage {
+    fn file(&self, name: &str) -> Option<&[u8]> {
+        self.files
+            .iter()
+            .find(|(path, _)| asset_name(path) == name)
+            .map(|(_, content)| content.as_slice())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let manifest = &self.manifest;
+        if !is_valid_theme_name(&manifest.name) {
+            return Err(format!("Invalid theme name: {}", manifest.name));
+        }
+        if manifest.version.trim().is_empty() {
+            return Err("The theme has no version".into());
+        }
+        validate_variables(&manifest.variables)?;
+
+        let html_code = self.file("index.html").ok_or("The archive has no index.html")?;
+        let html_code = std::str::from_utf8(html_code).map_err(|_| "index.html isn't valid UTF-8".to_string())?;
+        Template::parse(html_code).map_err(|e| format!("The theme has an error: {}", e))?;
+        if self.file("style.css").map(|css| std::str::from_utf8(css).is_err()).unwrap_or(false) {
+            return Err("style.css isn't valid UTF-8".into());
+        }
+        for (path, content) in &self.files {
+            if let Some(kind) = event_template_kind(&asset_name(path)) {
+                let html_code = std::str::from_utf8(content).map_err(|_| format!("The {} template isn't valid UTF-8", kind))?;
+                Template::parse(html_code).map_err(|e| format!("The {} template has an error: {}", kind, e))?;
+            }
+        }
+
+        let unknown: Vec<&str> = manifest
+            .placeholders
+            .iter()
+            .filter(|name| !PLACEHOLDERS.iter().any(|(placeholder, _)| placeholder == name))
+            .map(|name| name.as_str())
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!(
+                "The theme needs placeholders this version doesn't have: {}",
+                unknown.join(", ")
+            ));
+        }
+
+        for file in manifest.assets.iter().chain(&manifest.preview) {
+            if self.file(file).is_none() {
+                return Err(format!("{} is listed in theme.json but missing from the archive", file));
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the theme to `themes_dir`, returning the name it was installed as.
+    pub(crate) fn install(&self, themes_dir: &Path, conflict: ImportConflict) -> Result<String, String> {
+        let mut name = self.manifest.name.clone();
+        if themes_dir.join(&name).exists() {
+            match conflict {
+                ImportConflict::Fail => return Err(format!("A theme named \"{}\" already exists", name)),
+                ImportConflict::Overwrite => {}
+                ImportConflict::Rename => {
+                    name = (2..)
+                        .map(|i| format!("{} ({})", self.manifest.name, i))
+                        .find(|candidate| !themes_dir.join(candidate).exists())
+                        .unwrap();
+                }
+            }
+        }
+
+        // Unpacked next to the themes first, so a failed import doesn't leave half a theme behind
+        let staging = themes_dir.join(format!(".import-{}", name));
+        let _ = std::fs::remove_dir_all(&staging);
+        let unpack = || -> std::io::Result<()> {
+            std::fs::create_dir_all(&staging)?;
+            for (path, content) in &self.files {
+                let destination = staging.join(path);
+                if let Some(parent) = destination.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(destination, content)?;
+            }
+            if self.file("style.css").is_none() {
+                std::fs::write(staging.join("style.css"), "")?;
+            }
+
+            let manifest = ThemeManifest {
+                name: name.clone(),
+                created_at: Some(chrono::Local::now().timestamp_millis()),
+                ..self.manifest.clone()
+            };
+            write_manifest(&staging, &manifest)
+        };
+        let replace = || -> std::io::Result<()> {
+            let destination = themes_dir.join(&name);
+            if !destination.exists() {
+                return std::fs::rename(&staging, &destination);
+            }
+
+            // The theme being replaced is only deleted once the new one is in its place
+            let backup = themes_dir.join(format!(".backup-{}", name));
+            let _ = std::fs::remove_dir_all(&backup);
+            std::fs::rename(&destination, &backup)?;
+            if let Err(e) = std::fs::rename(&staging, &destination) {
+                let _ = std::fs::rename(&backup, &destination);
+                return Err(e);
+            }
+            let _ = std::fs::remove_dir_all(&backup);
+            Ok(())
+        };
+
+        if let Err(e) = unpack().and_then(|_| replace()) {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(format!("Failed to install the theme: {}", e));
+        }
+        Ok(name)
+    }
+}
+
+/// Packages a theme into a single archive at `path`, returning its manifest.
+#[tauri::command]
+pub(crate) async fn export_theme(theme: String, path: String) -> Result<ThemeManifest, String> {
+    let (html_code, css_code) = load_theme_files(&theme)?;
+    let file = std::fs::File::create(&path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+
+    let manifest = write_package(&themes_dir()?.join(&theme), &theme, &html_code, &css_code, file);
+    if manifest.is_err() {
+        let _ = std::fs::remove_file(&path);
+    }
+    manifest
+}
+
+/// Installs a theme archive, returning the name it was installed as.
+#[tauri::command]
+pub(crate) async fn import_theme(path: String, conflict: Option<ImportConflict>, app: AppHandle) -> Result<String, String> {
+    let file = std::fs::File::open(&path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let package = read_package(file)?;
+
+    let themes_dir = themes_dir()?;
+    std::fs::create_dir_all(&themes_dir).map_err(|e| e.to_string())?;
+    let replaced = themes_dir.join(&package.manifest.name).exists();
+    let name = package.install(&themes_dir, conflict.unwrap_or_default())?;
+
+    // The revisions and draft of the theme it replaced don't belong to the imported one
+    if replaced && name == package.manifest.name {
+        if let Err(e) = remove_revisions(&app, &name).and_then(|_| discard_draft(&app, &name)) {
+            eprintln!("Failed to remove the history of {}: {}", name, e);
+        }
+    }
+    app.state::<Mutex<ThemeRegistry>>().lock().unwrap().refresh(&themes_dir, &name);
+
+    app.emit_to("main", "editor::theme_saved", Some(name.clone())).map_err(|e| e.to_string())?;
+    Ok(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("united-chat-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn exports_and_imports_a_theme() {
+        let source = temp_dir("export");
+        std::fs::create_dir_all(source.join("assets/fonts")).unwrap();
+        std::fs::write(source.join("assets/fonts/font.woff2"), b"font").unwrap();
+        std::fs::write(source.join("assets/notes.txt"), b"not packaged").unwrap();
+        std::fs::write(source.join("preview.png"), b"png").unwrap();
+        std::fs::create_dir_all(source.join(TEMPLATES_DIR)).unwrap();
+        std::fs::write(source.join("templates/raid.html"), "<b>{user} raids with {amount}</b>").unwrap();
+        std::fs::write(source.join(MANIFEST_FILE), r#"{"name": "old", "author": "me"}"#).unwrap();
+
+        let mut archive = Cursor::new(Vec::new());
+        let manifest = write_package(&source, "mine", "<p>{user}: {formatedMessage}</p>", "p {}", &mut archive).unwrap();
+        assert_eq!(manifest.author.as_deref(), Some("me"));
+        assert_eq!(manifest.placeholders, vec!["user", "formatedMessage", "amount"]);
+        assert_eq!(manifest.assets, vec!["assets/fonts/font.woff2"]);
+        assert_eq!(manifest.preview.as_deref(), Some("preview.png"));
+
+        archive.set_position(0);
+        let package = read_package(archive).unwrap();
+        assert_eq!(package.manifest, manifest);
+
+        let themes = temp_dir("import");
+        assert_eq!(package.install(&themes, ImportConflict::Fail).unwrap(), "mine");
+        assert!(package.install(&themes, ImportConflict::Fail).is_err());
+        assert_eq!(package.install(&themes, ImportConflict::Rename).unwrap(), "mine (2)");
+        assert_eq!(package.install(&themes, ImportConflict::Overwrite).unwrap(), "mine");
+        assert!(!themes.join(".backup-mine").exists());
+
+        assert_eq!(std::fs::read(themes.join("mine/assets/fonts/font.woff2")).unwrap(), b"font");
+        assert_eq!(std::fs::read_to_string(themes.join("mine/style.css")).unwrap(), "p {}");
+        assert_eq!(read_event_templates(&themes.join("mine")), vec![("raid".to_string(), "<b>{user} raids with {amount}</b>".to_string())]);
+        assert!(std::fs::read_to_string(themes.join("mine (2)/theme.json")).unwrap().contains("\"name\": \"mine (2)\""));
+
+        let _ = std::fs::remove_dir_all(source);
+        let _ = std::fs::remove_dir_all(themes);
+    }
+
+    #[test]
+    fn refuses_invalid_archives() {
+        let package = |files: &[(&str, &str)]| {
+            let mut archive = Cursor::new(Vec::new());
+            let mut zip = ZipWriter::new(&mut archive);
+            for (name, content) in files {
+                zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+                zip.write_all(content.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+            archive.set_position(0);
+            read_package(archive)
+        };
+
+        let manifest = r#"{"name": "theme"}"#;
+        assert!(package(&[(MANIFEST_FILE, manifest), ("index.html", "{user}")]).is_ok());
+        assert!(package(&[("index.html", "{user}")]).unwrap_err().contains("theme.json"));
+        assert!(package(&[(MANIFEST_FILE, manifest), ("index.html", "{nope}")]).unwrap_err().contains("{nope}"));
+        assert!(package(&[(MANIFEST_FILE, manifest), ("index.html", ""), ("../evil.html", "")]).is_err());
+        assert!(package(&[(MANIFEST_FILE, manifest), ("index.html", ""), ("script.js", "")]).is_err());
+        assert!(package(&[(MANIFEST_FILE, manifest), ("index.html", ""), ("templates/raid.html", "{#if user}")]).is_err());
+        assert!(package(&[(MANIFEST_FILE, manifest), ("index.html", ""), ("templates/unknown.html", "")]).is_err());
+        assert!(package(&[(MANIFEST_FILE, r#"{"name": "../up"}"#), ("index.html", "")]).is_err());
+        assert!(package(&[(MANIFEST_FILE, r#"{"name": "t", "placeholders": ["future"]}"#), ("index.html", "")])
+            .unwrap_err()
+            .contains("future"));
+        assert!(package(&[(MANIFEST_FILE, r#"{"name": "t", "preview": "preview.png"}"#), ("index.html", "")]).is_err());
+    }
+}
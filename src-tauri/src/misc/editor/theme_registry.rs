@@ -0,0 +1,451 @@
+use crate::misc::editor::default_themes::{default, sakura};
+use crate::misc::editor::overlay_windows::{remove_overlay_theme, rename_overlay_theme};
+use crate::misc::editor::theme_assets::is_asset;
+use crate::misc::editor::theme_drafts::{discard_draft, rename_draft};
+use crate::misc::editor::theme_history::{remove_revisions, rename_revisions};
+use crate::misc::editor::theme_package::{is_valid_theme_name, read_manifest, themes_dir, write_manifest, ThemeManifest};
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Themes that come with the app, they can be edited but not renamed or deleted
+pub(crate) const BUILT_IN_THEMES: &[&str] = &["default", "sakura"];
+const SETTINGS_TREE: &str = "settings";
+const ACTIVE_THEME_KEY: &str = "active_theme";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct ThemeMetadata {
+    /// Name of the theme's folder, what every command takes
+    pub(crate) name: String,
+    pub(crate) display_name: String,
+    pub(crate) author: Option<String>,
+    pub(crate) description: Option<String>,
+    pub(crate) version: String,
+    /// Local timestamps (ms)
+    pub(crate) created_at: i64,
+    pub(crate) modified_at: i64,
+    pub(crate) built_in: bool,
+    /// Preview image in the theme's folder
+    pub(crate) preview: Option<PathBuf>,
+    pub(crate) html_path: PathBuf,
+    pub(crate) css_path: PathBuf,
+}
+
+/// The themes in the themes folder, managed as `Mutex<ThemeRegistry>`.
+#[derive(Default)]
+pub(crate) struct ThemeRegistry {
+    pub(crate) themes: Vec<ThemeMetadata>,
+}
+
+fn timestamp(time: std::io::Result<SystemTime>) -> Option<i64> {
+    let time = time.ok()?.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+    Some(time.as_millis() as i64)
+}
+
+pub(crate) fn is_built_in(name: &str) -> bool {
+    BUILT_IN_THEMES.contains(&name)
+}
+
+fn find_preview(theme_dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(theme_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.file_stem().map(|stem| stem == "preview").unwrap_or(false) && is_asset(path))
+}
+
+/// A theme's metadata, from its theme.json and its files.
+pub(crate) fn read_theme(themes_dir: &Path, name: &str) -> Option<ThemeMetadata> {
+    let theme_dir = themes_dir.join(name);
+    if !theme_dir.is_dir() && !is_built_in(name) {
+        return None;
+    }
+
+    let manifest = read_manifest(&theme_dir).unwrap_or_default();
+    let html_path = theme_dir.join("index.html");
+    let css_path = theme_dir.join("style.css");
+    let modified_at = [&html_path, &css_path]
+        .iter()
+        .filter_map(|path| timestamp(std::fs::metadata(path).and_then(|metadata| metadata.modified())))
+        .max()
+        .unwrap_or(0);
+    let created_at = manifest
+        .created_at
+        .or_else(|| timestamp(std::fs::metadata(&theme_dir).and_then(|metadata| metadata.created())))
+        .unwrap_or(modified_at);
+
+    Some(ThemeMetadata {
+        name: name.to_string(),
+        display_name: manifest.display_name.unwrap_or_else(|| name.to_string()),
+        author: manifest.author,
+        description: manifest.description,
+        version: manifest.version,
+        created_at,
+        modified_at,
+        built_in: is_built_in(name),
+        preview: find_preview(&theme_dir),
+        html_path,
+        css_path,
+    })
+}
+
+impl ThemeRegistry {
+    /// Every theme folder, plus the built-in themes even when they were never saved.
+    pub(crate) fn load(themes_dir: &Path) -> Self {
+        let mut names: Vec<String> = BUILT_IN_THEMES.iter().map(|name| name.to_string()).collect();
+        for entry in std::fs::read_dir(themes_dir).into_iter().flatten().filter_map(|entry| entry.ok()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Hidden folders are imports that are still being unpacked
+            if entry.path().is_dir() && !name.starts_with('.') && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        Self {
+            themes: names.iter().filter_map(|name| read_theme(themes_dir, name)).collect(),
+        }
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&ThemeMetadata> {
+        self.themes.iter().find(|theme| theme.name == name)
+    }
+
+    /// Reads a theme again after it changed on disk, dropping it if it's gone.
+    pub(crate) fn refresh(&mut self, themes_dir: &Path, name: &str) {
+        let theme = read_theme(themes_dir, name);
+        match (self.themes.iter().position(|theme| theme.name == name), theme) {
+            (Some(index), Some(theme)) => self.themes[index] = theme,
+            (Some(index), None) => {
+                self.themes.remove(index);
+            }
+            (None, Some(theme)) => self.themes.push(theme),
+            (None, None) => {}
+        }
+    }
+}
+
+fn unique_name(themes_dir: &Path, base: &str) -> String {
+    if !themes_dir.join(base).exists() && !is_built_in(base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|i| format!("{} ({})", base, i))
+        .find(|name| !themes_dir.join(name).exists())
+        .unwrap()
+}
+
+fn copy_dir(source: &Path, destination: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(destination)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        // Leftovers of interrupted atomic writes
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if entry.path().is_dir() {
+            copy_dir(&entry.path(), &destination.join(&name))?;
+        } else {
+            std::fs::copy(entry.path(), destination.join(&name))?;
+        }
+    }
+    Ok(())
+}
+
+fn check_new_name(themes_dir: &Path, name: &str) -> Result<(), String> {
+    if !is_valid_theme_name(name) || name.starts_with('.') {
+        return Err(format!("Invalid theme name: {}", name));
+    }
+    if themes_dir.join(name).exists() || is_built_in(name) {
+        return Err(format!("A theme named \"{}\" already exists", name));
+    }
+    Ok(())
+}
+
+/// Moves a theme's folder, its theme.json follows the new name.
+pub(crate) fn rename_theme_folder(themes_dir: &Path, name: &str, new_name: &str) -> Result<(), String> {
+    if is_built_in(name) {
+        return Err("Built-in themes can't be renamed, duplicate it instead".into());
+    }
+    if !is_valid_theme_name(name) || !themes_dir.join(name).is_dir() {
+        return Err("Theme not found".into());
+    }
+    check_new_name(themes_dir, new_name)?;
+
+    std::fs::rename(themes_dir.join(name), themes_dir.join(new_name)).map_err(|e| e.to_string())?;
+    let mut manifest = read_manifest(&themes_dir.join(new_name)).unwrap_or_default();
+    manifest.name = new_name.to_string();
+    write_manifest(&themes_dir.join(new_name), &manifest).map_err(|e| e.to_string())
+}
+
+/// Copies a theme's folder, built-in themes that were never saved get their built-in code.
+pub(crate) fn duplicate_theme_folder(themes_dir: &Path, name: &str, new_name: &str) -> Result<(), String> {
+    if !is_valid_theme_name(name) {
+        return Err("Theme not found".into());
+    }
+    check_new_name(themes_dir, new_name)?;
+
+    let source = themes_dir.join(name);
+    let mut html_code = std::fs::read_to_string(source.join("index.html")).unwrap_or_default();
+    let css_code = std::fs::read_to_string(source.join("style.css")).unwrap_or_default();
+    if html_code.trim().is_empty() {
+        html_code = match name {
+            "default" => default(),
+            "sakura" => sakura(),
+            _ => return Err("Theme not found".into()),
+        };
+    }
+
+    let destination = themes_dir.join(new_name);
+    let copy = || -> std::io::Result<()> {
+        if source.is_dir() {
+            copy_dir(&source, &destination)?;
+        } else {
+            std::fs::create_dir_all(&destination)?;
+        }
+        std::fs::write(destination.join("index.html"), &html_code)?;
+        std::fs::write(destination.join("style.css"), &css_code)?;
+
+        let manifest = ThemeManifest {
+            name: new_name.to_string(),
+            display_name: None,
+            created_at: Some(chrono::Local::now().timestamp_millis()),
+            ..read_manifest(&source).unwrap_or_default()
+        };
+        write_manifest(&destination, &manifest)
+    };
+
+    copy().map_err(|e| {
+        let _ = std::fs::remove_dir_all(&destination);
+        format!("Failed to duplicate the theme: {}", e)
+    })
+}
+
+pub(crate) fn delete_theme_folder(themes_dir: &Path, name: &str) -> Result<(), String> {
+    if is_built_in(name) {
+        return Err("Built-in themes can't be deleted".into());
+    }
+    if !is_valid_theme_name(name) || !themes_dir.join(name).is_dir() {
+        return Err("Theme not found".into());
+    }
+    std::fs::remove_dir_all(themes_dir.join(name)).map_err(|e| e.to_string())
+}
+
+fn settings_tree(app: &AppHandle) -> Result<sled::Tree, String> {
+    let db: Arc<Db> = app.state::<Arc<Db>>().deref().clone();
+    db.open_tree(SETTINGS_TREE).map_err(|e| e.to_string())
+}
+
+pub(crate) fn active_theme(app: &AppHandle) -> String {
+    settings_tree(app)
+        .ok()
+        .and_then(|tree| tree.get(ACTIVE_THEME_KEY).ok().flatten())
+        .and_then(|value| String::from_utf8(value.to_vec()).ok())
+        .unwrap_or_else(|| "default".to_string())
+}
+
+fn store_active_theme(app: &AppHandle, name: &str) -> Result<(), String> {
+    settings_tree(app)?.insert(ACTIVE_THEME_KEY, name.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn refresh_themes(app: &AppHandle, names: &[&str]) -> Result<(), String> {
+    let themes_dir = themes_dir()?;
+    let registry = app.state::<Mutex<ThemeRegistry>>();
+    let mut registry = registry.lock().unwrap();
+    for name in names {
+        registry.refresh(&themes_dir, name);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) async fn list_themes(app: AppHandle) -> Result<Vec<ThemeMetadata>, String> {
+    // Themes can be added or changed by hand in the folder, it's read again every time
+    let registry = ThemeRegistry::load(&themes_dir()?);
+    let themes = registry.themes.clone();
+    *app.state::<Mutex<ThemeRegistry>>().lock().unwrap() = registry;
+    Ok(themes)
+}
+
+#[tauri::command]
+pub(crate) async fn rename_theme(name: String, new_name: String, app: AppHandle) -> Result<ThemeMetadata, String> {
+    let themes_dir = themes_dir()?;
+    rename_theme_folder(&themes_dir, &name, &new_name)?;
+
+    // The history and the draft follow, the theme itself is already renamed if they fail
+    if let Err(e) = rename_revisions(&app, &name, &new_name).and_then(|_| rename_draft(&app, &name, &new_name)) {
+        eprintln!("Failed to move the history of {} to {}: {}", name, new_name, e);
+    }
+    if let Err(e) = rename_overlay_theme(&app, &name, &new_name).await {
+        eprintln!("Failed to move the overlay windows of {} to {}: {}", name, new_name, e);
+    }
+    if active_theme(&app) == name {
+        store_active_theme(&app, &new_name)?;
+    }
+
+    refresh_themes(&app, &[&name, &new_name])?;
+    app.emit_to("main", "editor::theme_renamed", (&name, &new_name)).map_err(|e| e.to_string())?;
+    read_theme(&themes_dir, &new_name).ok_or_else(|| "Theme not found".to_string())
+}
+
+/// Copies a theme, `new_name` defaults to "<name> copy".
+#[tauri::command]
+pub(crate) async fn duplicate_theme(name: String, new_name: Option<String>, app: AppHandle) -> Result<ThemeMetadata, String> {
+    let themes_dir = themes_dir()?;
+    let new_name = new_name.unwrap_or_else(|| unique_name(&themes_dir, &format!("{} copy", name)));
+    duplicate_theme_folder(&themes_dir, &name, &new_name)?;
+
+    refresh_themes(&app, &[&new_name])?;
+    app.emit_to("main", "editor::theme_saved", Some(&new_name)).map_err(|e| e.to_string())?;
+    read_theme(&themes_dir, &new_name).ok_or_else(|| "Theme not found".to_string())
+}
+
+#[tauri::command]
+pub(crate) async fn delete_theme(name: String, app: AppHandle) -> Result<(), String> {
+    let themes_dir = themes_dir()?;
+    delete_theme_folder(&themes_dir, &name)?;
+
+    if let Err(e) = remove_revisions(&app, &name).and_then(|_| discard_draft(&app, &name)) {
+        eprintln!("Failed to remove the history of {}: {}", name, e);
+    }
+    if let Err(e) = remove_overlay_theme(&app, &name) {
+        eprintln!("Failed to remove the overlay windows of {}: {}", name, e);
+    }
+    if active_theme(&app) == name {
+        store_active_theme(&app, "default")?;
+    }
+
+    refresh_themes(&app, &[&name])?;
+    app.emit_to("main", "editor::theme_deleted", Some(name)).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// The theme the editor and the webchat window start with.
+#[tauri::command]
+pub(crate) async fn get_active_theme(app: AppHandle) -> Result<String, String> {
+    Ok(active_theme(&app))
+}
+
+#[tauri::command]
+pub(crate) async fn set_active_theme(name: String, app: AppHandle) -> Result<(), String> {
+    if read_theme(&themes_dir()?, &name).is_none() {
+        return Err("Theme not found".into());
+    }
+    store_active_theme(&app, &name)?;
+    app.emit_to("main", "editor::active_theme_changed", Some(name)).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Updates what's shown about a theme, fields left out stay as they are and empty ones are cleared.
+#[tauri::command]
+pub(crate) async fn update_theme_metadata(
+    name: String,
+    display_name: Option<String>,
+    author: Option<String>,
+    description: Option<String>,
+    app: AppHandle,
+) -> Result<ThemeMetadata, String> {
+    let themes_dir = themes_dir()?;
+    if read_theme(&themes_dir, &name).is_none() {
+        return Err("Theme not found".into());
+    }
+
+    let theme_dir = themes_dir.join(&name);
+    std::fs::create_dir_all(&theme_dir).map_err(|e| e.to_string())?;
+    let mut manifest = read_manifest(&theme_dir).unwrap_or_default();
+    manifest.name = name.clone();
+    let clean = |value: String| Some(value.trim().to_string()).filter(|value| !value.is_empty());
+    if let Some(display_name) = display_name {
+        manifest.display_name = clean(display_name);
+    }
+    if let Some(author) = author {
+        manifest.author = clean(author);
+    }
+    if let Some(description) = description {
+        manifest.description = clean(description);
+    }
+    write_manifest(&theme_dir, &manifest).map_err(|e| e.to_string())?;
+
+    refresh_themes(&app, &[&name])?;
+    read_theme(&themes_dir, &name).ok_or_else(|| "Theme not found".to_string())
+}
+
+/// Sets the theme's preview thumbnail from an image file.
+#[tauri::command]
+pub(crate) async fn set_theme_preview(name: String, source: String, app: AppHandle) -> Result<ThemeMetadata, String> {
+    let themes_dir = themes_dir()?;
+    if read_theme(&themes_dir, &name).is_none() {
+        return Err("Theme not found".into());
+    }
+
+    let source = PathBuf::from(source);
+    let extension = source
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase())
+        .filter(|extension| ["png", "jpg", "jpeg", "gif", "webp"].contains(&extension.as_str()))
+        .ok_or("The preview has to be a PNG, JPEG, GIF or WebP image")?;
+
+    let theme_dir = themes_dir.join(&name);
+    std::fs::create_dir_all(&theme_dir).map_err(|e| e.to_string())?;
+    if let Some(previous) = find_preview(&theme_dir) {
+        std::fs::remove_file(previous).map_err(|e| e.to_string())?;
+    }
+    std::fs::copy(&source, theme_dir.join(format!("preview.{}", extension)))
+        .map_err(|e| format!("Failed to copy {}: {}", source.display(), e))?;
+
+    refresh_themes(&app, &[&name])?;
+    read_theme(&themes_dir, &name).ok_or_else(|| "Theme not found".to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn manages_theme_folders() {
+        let themes_dir = std::env::temp_dir().join(format!("united-chat-registry-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&themes_dir);
+        std::fs::create_dir_all(themes_dir.join("mine")).unwrap();
+        std::fs::write(themes_dir.join("mine/index.html"), "<p>{user}</p>").unwrap();
+        std::fs::write(themes_dir.join("mine/style.css"), "").unwrap();
+        std::fs::create_dir_all(themes_dir.join(".import-other")).unwrap();
+
+        let registry = ThemeRegistry::load(&themes_dir);
+        let names: Vec<&str> = registry.themes.iter().map(|theme| theme.name.as_str()).collect();
+        assert_eq!(names, vec!["default", "sakura", "mine"]);
+        assert!(registry.get("default").unwrap().built_in);
+        assert!(!registry.get("mine").unwrap().built_in);
+
+        assert!(rename_theme_folder(&themes_dir, "default", "mine 2").is_err());
+        assert!(rename_theme_folder(&themes_dir, "mine", "sakura").is_err());
+        assert!(rename_theme_folder(&themes_dir, "mine", "../mine").is_err());
+        // Neither can reach a folder outside of the themes one
+        assert!(rename_theme_folder(&themes_dir, "../united-chat-registry", "escaped").is_err());
+        assert!(duplicate_theme_folder(&themes_dir, "..", "escaped").is_err());
+        assert!(!themes_dir.join("escaped").exists());
+        rename_theme_folder(&themes_dir, "mine", "renamed").unwrap();
+        assert_eq!(read_manifest(&themes_dir.join("renamed")).unwrap().name, "renamed");
+
+        duplicate_theme_folder(&themes_dir, "renamed", "copy").unwrap();
+        assert_eq!(std::fs::read_to_string(themes_dir.join("copy/index.html")).unwrap(), "<p>{user}</p>");
+        assert_eq!(read_manifest(&themes_dir.join("copy")).unwrap().name, "copy");
+        // Built-in themes can be duplicated before they were ever saved
+        duplicate_theme_folder(&themes_dir, "sakura", "petals").unwrap();
+        assert_eq!(std::fs::read_to_string(themes_dir.join("petals/index.html")).unwrap(), sakura());
+        assert_eq!(unique_name(&themes_dir, "renamed"), "renamed (2)");
+
+        assert!(delete_theme_folder(&themes_dir, "sakura").is_err());
+        delete_theme_folder(&themes_dir, "renamed").unwrap();
+        let mut registry = registry;
+        registry.refresh(&themes_dir, "mine");
+        assert!(registry.get("mine").is_none());
+
+        let _ = std::fs::remove_dir_all(themes_dir);
+    }
+}
@@ -0,0 +1,349 @@
+use crate::misc::editor::default_themes::sakura_variables;
+use crate::misc::editor::save_theme::write_atomically;
+use crate::misc::editor::theme_package::{is_valid_theme_name, read_manifest, themes_dir};
+use crate::misc::editor::theme_watcher::announce_theme_change;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::path::Path;
+use tauri::AppHandle;
+
+/// The values set for a theme's variables, next to its files. It isn't part of an exported theme.
+pub(crate) const VALUES_FILE: &str = "variables.json";
+
+/// A setting a theme declares in its theme.json, available to its CSS as `var(--<name>)`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct ThemeVariable {
+    pub(crate) name: String,
+    /// Shown in the settings instead of the name
+    #[serde(default)]
+    pub(crate) label: Option<String>,
+    #[serde(default)]
+    pub(crate) description: Option<String>,
+    #[serde(flatten)]
+    pub(crate) kind: VariableKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum VariableKind {
+    /// Hex, rgb()/hsl() or a named color
+    Color { default: String },
+    Number {
+        default: f64,
+        #[serde(default)]
+        min: Option<f64>,
+        #[serde(default)]
+        max: Option<f64>,
+        #[serde(default)]
+        step: Option<f64>,
+        /// Appended to the value in the CSS, e.g. "px"
+        #[serde(default)]
+        unit: Option<String>,
+    },
+    /// A font-family list, e.g. `"Inter", sans-serif`
+    Font { default: String },
+    /// `on` and `off` are what ends up in the CSS, e.g. "block" and "none" to show or hide something
+    Boolean {
+        default: bool,
+        #[serde(default = "default_on")]
+        on: String,
+        #[serde(default = "default_off")]
+        off: String,
+    },
+    Enum { default: String, options: Vec<String> },
+}
+
+fn default_on() -> String {
+    "1".to_string()
+}
+
+fn default_off() -> String {
+    "0".to_string()
+}
+
+/// Everything the settings need to show a theme's variables.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct ThemeVariables {
+    pub(crate) variables: Vec<ThemeVariable>,
+    /// Every variable's value, the default one when it wasn't set
+    pub(crate) values: Map<String, Value>,
+    /// The values as CSS custom properties, injected before the theme's CSS
+    pub(crate) css: String,
+}
+
+fn is_valid_variable_name(name: &str) -> bool {
+    name.len() <= 64
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Keywords and plain values that can't end the declaration they're in
+fn is_plain_value(value: &str) -> bool {
+    !value.trim().is_empty()
+        && value.len() <= 64
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || " -_.%#(),".contains(c))
+}
+
+fn is_color(value: &str) -> bool {
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(hex) = value.strip_prefix('#') {
+        return [3, 4, 6, 8].contains(&hex.len()) && hex.chars().all(|c| c.is_ascii_hexdigit());
+    }
+    for function in ["rgb(", "rgba(", "hsl(", "hsla("] {
+        if let Some(arguments) = value.strip_prefix(function).and_then(|rest| rest.strip_suffix(')')) {
+            return !arguments.is_empty() && arguments.chars().all(|c| c.is_ascii_digit() || " .,%/deg".contains(c));
+        }
+    }
+    !value.is_empty() && value.len() <= 32 && value.chars().all(|c| c.is_ascii_lowercase())
+}
+
+fn is_font(value: &str) -> bool {
+    !value.trim().is_empty()
+        && value.len() <= 200
+        && value.chars().all(|c| c.is_alphanumeric() || " -_,'\"".contains(c))
+        && value.matches('"').count().is_multiple_of(2)
+        && value.matches('\'').count().is_multiple_of(2)
+}
+
+fn format_number(number: f64) -> String {
+    if number.fract() == 0.0 && number.abs() < 1e15 {
+        format!("{}", number as i64)
+    } else {
+        format!("{}", number)
+    }
+}
+
+impl ThemeVariable {
+    pub(crate) fn default_value(&self) -> Value {
+        match &self.kind {
+            VariableKind::Color { default } | VariableKind::Font { default } | VariableKind::Enum { default, .. } => {
+                Value::from(default.as_str())
+            }
+            VariableKind::Number { default, .. } => Value::from(*default),
+            VariableKind::Boolean { default, .. } => Value::from(*default),
+        }
+    }
+
+    /// Checks a value and turns it into what's put in the CSS.
+    pub(crate) fn css_value(&self, value: &Value) -> Result<String, String> {
+        let invalid = || format!("Invalid value for {}: {}", self.name, value);
+        match &self.kind {
+            VariableKind::Color { .. } => match value.as_str() {
+                Some(color) if is_color(color) => Ok(color.trim().to_string()),
+                _ => Err(invalid()),
+            },
+            VariableKind::Number { min, max, unit, .. } => {
+                let number = value.as_f64().filter(|number| number.is_finite()).ok_or_else(invalid)?;
+                if min.map(|min| number < min).unwrap_or(false) || max.map(|max| number > max).unwrap_or(false) {
+                    let bound = |bound: &Option<f64>, infinite: &str| bound.map(format_number).unwrap_or(infinite.to_string());
+                    return Err(format!("{} has to be between {} and {}", self.name, bound(min, "-∞"), bound(max, "∞")));
+                }
+                Ok(format!("{}{}", format_number(number), unit.as_deref().unwrap_or("")))
+            }
+            VariableKind::Font { .. } => match value.as_str() {
+                Some(font) if is_font(font) => Ok(font.trim().to_string()),
+                _ => Err(invalid()),
+            },
+            VariableKind::Boolean { on, off, .. } => match value.as_bool() {
+                Some(true) => Ok(on.clone()),
+                Some(false) => Ok(off.clone()),
+                None => Err(invalid()),
+            },
+            VariableKind::Enum { options, .. } => match value.as_str() {
+                Some(option) if options.iter().any(|allowed| allowed == option) => Ok(option.to_string()),
+                _ => Err(format!("{} has to be one of: {}", self.name, options.join(", "))),
+            },
+        }
+    }
+}
+
+/// Checks what a theme.json declares, so a bad variable is reported when the theme is imported
+/// instead of breaking its CSS later.
+pub(crate) fn validate_variables(variables: &[ThemeVariable]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for variable in variables {
+        if !is_valid_variable_name(&variable.name) {
+            return Err(format!("Invalid variable name: {}", variable.name));
+        }
+        if !names.insert(variable.name.as_str()) {
+            return Err(format!("The variable {} is declared twice", variable.name));
+        }
+
+        match &variable.kind {
+            VariableKind::Number { min: Some(min), max: Some(max), .. } if min > max => {
+                return Err(format!("{} has a minimum above its maximum", variable.name));
+            }
+            VariableKind::Number { unit: Some(unit), .. }
+                if unit.len() > 4 || !unit.chars().all(|c| c.is_ascii_alphabetic() || c == '%') =>
+            {
+                return Err(format!("Invalid unit for {}: {}", variable.name, unit));
+            }
+            VariableKind::Boolean { on, off, .. } if !is_plain_value(on) || !is_plain_value(off) => {
+                return Err(format!("Invalid on/off values for {}", variable.name));
+            }
+            VariableKind::Enum { options, .. } if options.is_empty() || !options.iter().all(|option| is_plain_value(option)) => {
+                return Err(format!("Invalid options for {}", variable.name));
+            }
+            _ => {}
+        }
+
+        variable
+            .css_value(&variable.default_value())
+            .map_err(|e| format!("Invalid default value: {}", e))?;
+    }
+    Ok(())
+}
+
+/// The variables a theme declares, the built-in themes have theirs even without a theme.json.
+pub(crate) fn declared_variables(theme_dir: &Path, theme: &str) -> Vec<ThemeVariable> {
+    let variables = read_manifest(theme_dir)
+        .map(|manifest| manifest.variables)
+        .filter(|variables| !variables.is_empty() && validate_variables(variables).is_ok());
+    match (variables, theme) {
+        (Some(variables), _) => variables,
+        (None, "sakura") => sakura_variables(),
+        _ => Vec::new(),
+    }
+}
+
+pub(crate) fn read_values(theme_dir: &Path) -> Map<String, Value> {
+    std::fs::read_to_string(theme_dir.join(VALUES_FILE))
+        .ok()
+        .and_then(|values| serde_json::from_str(&values).ok())
+        .unwrap_or_default()
+}
+
+/// Every variable's value, the stored ones that are still valid and the defaults for the rest.
+pub(crate) fn resolve_values(variables: &[ThemeVariable], stored: &Map<String, Value>) -> Map<String, Value> {
+    variables
+        .iter()
+        .map(|variable| {
+            let value = stored
+                .get(&variable.name)
+                .filter(|value| variable.css_value(value).is_ok())
+                .cloned()
+                .unwrap_or_else(|| variable.default_value());
+            (variable.name.clone(), value)
+        })
+        .collect()
+}
+
+pub(crate) fn variables_css(variables: &[ThemeVariable], values: &Map<String, Value>) -> String {
+    if variables.is_empty() {
+        return String::new();
+    }
+
+    let mut css = String::from(":root {\n");
+    for variable in variables {
+        let value = values
+            .get(&variable.name)
+            .and_then(|value| variable.css_value(value).ok())
+            .or_else(|| variable.css_value(&variable.default_value()).ok())
+            .unwrap_or_default();
+        css.push_str(&format!("    --{}: {};\n", variable.name, value));
+    }
+    css.push_str("}\n");
+    css
+}
+
+fn load_variables(theme: &str) -> Result<ThemeVariables, String> {
+    if !is_valid_theme_name(theme) {
+        return Err("Invalid theme name".into());
+    }
+    let theme_dir = themes_dir()?.join(theme);
+    let variables = declared_variables(&theme_dir, theme);
+    let values = resolve_values(&variables, &read_values(&theme_dir));
+    let css = variables_css(&variables, &values);
+    Ok(ThemeVariables { variables, values, css })
+}
+
+/// CSS custom properties of a theme, empty when it has no variables.
+pub(crate) fn theme_variables_css(theme: &str) -> String {
+    load_variables(theme).map(|variables| variables.css).unwrap_or_default()
+}
+
+#[tauri::command]
+pub(crate) async fn get_theme_variables(theme: String) -> Result<ThemeVariables, String> {
+    load_variables(&theme)
+}
+
+/// Sets some of a theme's variables, the others keep their value. The overlays showing it reload with the new values.
+#[tauri::command]
+pub(crate) async fn set_theme_variables(theme: String, values: Map<String, Value>, app: AppHandle) -> Result<ThemeVariables, String> {
+    let current = load_variables(&theme)?;
+    for (name, value) in &values {
+        let variable = current
+            .variables
+            .iter()
+            .find(|variable| &variable.name == name)
+            .ok_or_else(|| format!("The theme has no variable named {}", name))?;
+        variable.css_value(value)?;
+    }
+
+    // Only what was set is stored, the others follow the theme's defaults
+    let theme_dir = themes_dir()?.join(&theme);
+    let mut stored = read_values(&theme_dir);
+    stored.retain(|name, _| current.variables.iter().any(|variable| &variable.name == name));
+    stored.extend(values);
+    std::fs::create_dir_all(&theme_dir).map_err(|e| e.to_string())?;
+    let content = serde_json::to_string_pretty(&stored).map_err(|e| e.to_string())?;
+    write_atomically(&theme_dir.join(VALUES_FILE), &content).map_err(|e| e.to_string())?;
+    // Not left to the theme watcher, the file may be written again before it catches up
+    announce_theme_change(&app, &theme).await;
+
+    load_variables(&theme)
+}
+
+/// Puts every variable back to the theme's default.
+#[tauri::command]
+pub(crate) async fn reset_theme_variables(theme: String, app: AppHandle) -> Result<ThemeVariables, String> {
+    if !is_valid_theme_name(&theme) {
+        return Err("Invalid theme name".into());
+    }
+    let path = themes_dir()?.join(&theme).join(VALUES_FILE);
+    if path.exists() {
+        std::fs::remove_file(path).map_err(|e| e.to_string())?;
+        announce_theme_change(&app, &theme).await;
+    }
+    load_variables(&theme)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn validates_and_renders_variables() {
+        let variables: Vec<ThemeVariable> = serde_json::from_value(json!([
+            { "name": "accent", "type": "color", "default": "#f9a8d4" },
+            { "name": "size", "type": "number", "default": 14, "min": 10, "max": 32, "unit": "px" },
+            { "name": "font", "type": "font", "default": "\"Inter\", sans-serif" },
+            { "name": "show-time", "type": "boolean", "default": true, "on": "block", "off": "none" },
+            { "name": "border", "type": "enum", "default": "solid", "options": ["solid", "dashed"] }
+        ]))
+        .unwrap();
+        validate_variables(&variables).unwrap();
+
+        let stored = json!({ "accent": "rgb(0, 0, 0)", "size": 100, "show-time": false, "unknown": 1 });
+        let values = resolve_values(&variables, stored.as_object().unwrap());
+        // Out of range values fall back to the default, unknown ones are dropped
+        assert_eq!(values["size"], json!(14.0));
+        assert!(!values.contains_key("unknown"));
+        assert_eq!(
+            variables_css(&variables, &values),
+            ":root {\n    --accent: rgb(0, 0, 0);\n    --size: 14px;\n    --font: \"Inter\", sans-serif;\n    --show-time: none;\n    --border: solid;\n}\n"
+        );
+
+        // Nothing can end the declaration or the style tag
+        assert!(variables[0].css_value(&json!("red; } body { display: none")).is_err());
+        assert!(variables[2].css_value(&json!("Inter</style>")).is_err());
+        assert!(variables[4].css_value(&json!("dotted")).is_err());
+
+        let invalid: Vec<ThemeVariable> =
+            serde_json::from_value(json!([{ "name": "x", "type": "enum", "default": "a", "options": ["a", "b;"] }])).unwrap();
+        assert!(validate_variables(&invalid).is_err());
+        validate_variables(&sakura_variables()).unwrap();
+    }
+}
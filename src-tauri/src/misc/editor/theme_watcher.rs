@@ -0,0 +1,106 @@
+use crate::chat::websocket::protocol::Envelope;
+use crate::chat::websocket::start_ws::current_server;
+use crate::misc::editor::theme_registry::ThemeRegistry;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::mpsc;
+
+// Editors save in a few steps (temp file, rename, ...), changes are grouped until the folder is quiet for this long
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// The theme a changed path belongs to. Hidden files and folders are skipped, they're the app's own
+/// atomic writes and imports being unpacked, the final rename is what counts.
+pub(crate) fn changed_theme(themes_dir: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(themes_dir).ok()?;
+    let hidden = relative
+        .components()
+        .any(|component| component.as_os_str().to_string_lossy().starts_with('.'));
+    if hidden {
+        return None;
+    }
+    relative
+        .components()
+        .next()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+}
+
+/// Updates the registry and tells the editor and the overlays showing the theme.
+async fn reload_theme(app: &AppHandle, themes_dir: &Path, theme: &str) {
+    if let Some(registry) = app.try_state::<Mutex<ThemeRegistry>>() {
+        registry.lock().unwrap().refresh(themes_dir, theme);
+    }
+    println!("Theme {} changed on disk", theme);
+    announce_theme_change(app, theme).await;
+}
+
+/// Tells the editor and the overlays showing the theme that it changed.
+pub(crate) async fn announce_theme_change(app: &AppHandle, theme: &str) {
+    if let Err(e) = app.emit_to("main", "editor::theme_changed", Some(theme)) {
+        eprintln!("Failed to emit theme change: {}", e);
+    }
+    // The overlay page has the theme's CSS built in, it has to be loaded again
+    if let Some(server) = current_server(app) {
+        server.notify(Envelope::new("united_chat", None, "theme_changed", json!({ "theme": theme }))).await;
+    }
+}
+
+/// Watches the themes folder for changes made outside the app, e.g. a theme edited in another editor.
+pub(crate) fn start_theme_watcher(app: AppHandle, themes_dir: PathBuf) -> Result<(), String> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<PathBuf>();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+        // Reading a theme isn't a change
+        Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+        Ok(event) => {
+            for path in event.paths {
+                let _ = tx.send(path);
+            }
+        }
+        Err(e) => eprintln!("Theme watcher error: {}", e),
+    })
+    .map_err(|e| e.to_string())?;
+    watcher
+        .watch(&themes_dir, RecursiveMode::Recursive)
+        .map_err(|e| format!("Failed to watch {}: {}", themes_dir.display(), e))?;
+
+    tauri::async_runtime::spawn(async move {
+        // Dropping the watcher stops it, it lives as long as this task
+        let _watcher = watcher;
+
+        while let Some(path) = rx.recv().await {
+            let mut changed = BTreeSet::new();
+            changed.extend(changed_theme(&themes_dir, &path));
+            while let Ok(Some(path)) = tokio::time::timeout(DEBOUNCE, rx.recv()).await {
+                changed.extend(changed_theme(&themes_dir, &path));
+            }
+
+            for theme in changed {
+                reload_theme(&app, &themes_dir, &theme).await;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_the_changed_theme() {
+        let themes_dir = Path::new("/config/themes");
+
+        assert_eq!(changed_theme(themes_dir, &themes_dir.join("mine/style.css")), Some("mine".to_string()));
+        assert_eq!(changed_theme(themes_dir, &themes_dir.join("mine/assets/fonts/a.woff2")), Some("mine".to_string()));
+        assert_eq!(changed_theme(themes_dir, &themes_dir.join("mine")), Some("mine".to_string()));
+        // The app's own temp files and imports being unpacked
+        assert_eq!(changed_theme(themes_dir, &themes_dir.join("mine/.style.css.tmp")), None);
+        assert_eq!(changed_theme(themes_dir, &themes_dir.join(".import-mine/index.html")), None);
+        assert_eq!(changed_theme(themes_dir, Path::new("/elsewhere/index.html")), None);
+    }
+}
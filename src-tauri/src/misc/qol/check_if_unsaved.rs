@@ -1,36 +1,57 @@
-use crate::misc::editor::save_theme::ThemeState;
+use crate::misc::editor::theme_registry::ThemeRegistry;
+use crate::misc::editor::theme_drafts::get_theme_draft;
+use serde::Serialize;
 use std::sync::Mutex;
 use tauri::{AppHandle, Manager};
 
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct UnsavedStatus {
+    /// The editor has changes that aren't published
+    pub(crate) unsaved: bool,
+    /// The editor's changes are all in the draft, nothing is lost on a reload
+    pub(crate) draft_up_to_date: bool,
+    /// Local timestamp (ms) of the last autosave, None without a draft
+    pub(crate) draft_saved_at: Option<i64>,
+}
+
 #[tauri::command]   
 pub(crate) async fn check_if_unsaved(
     current_theme_name: String,
     current_theme_html: String,
     current_theme_css: String,
     app: AppHandle,
-) -> Result<bool, String> {
-    let state = app.state::<Mutex<ThemeState>>();
-    let theme_state = state.lock().unwrap();
-
-    let theme = theme_state.themes.iter().find(|(name, _, _)| name == &current_theme_name);
+) -> Result<UnsavedStatus, String> {
+    let unsaved = {
+        let state = app.state::<Mutex<ThemeRegistry>>();
+        let registry = state.lock().unwrap();
 
-    match theme {
-        Some((_, html_path, css_path)) => {
+        match registry.get(&current_theme_name) {
+            Some(metadata) => {
+                let (html_path, css_path) = (&metadata.html_path, &metadata.css_path);
 
-            // Check if the path exists
-            if !html_path.exists() || !css_path.exists() {
-                return Err("Theme not found".into());
-            }
+                // Check if the path exists
+                if !html_path.exists() || !css_path.exists() {
+                    return Err("Theme not found".into());
+                }
 
-            let html_code = std::fs::read_to_string(html_path).unwrap();
-            let css_code = std::fs::read_to_string(css_path).unwrap();
+                let html_code = std::fs::read_to_string(html_path).unwrap();
+                let css_code = std::fs::read_to_string(css_path).unwrap();
 
-            if html_code == current_theme_html && css_code == current_theme_css {
-                Ok(false)
-            } else {
-                Ok(true)
+                html_code != current_theme_html || css_code != current_theme_css
             }
+            None => return Err("Theme not found".into()),
         }
-        None => Err("Theme not found".into()),
-    }
-}
\ No newline at end of file
+    };
+
+    let draft = get_theme_draft(current_theme_name, app).await?;
+    let draft_up_to_date = match &draft {
+        Some(draft) => draft.html_code == current_theme_html && draft.css_code == current_theme_css,
+        None => !unsaved,
+    };
+
+    Ok(UnsavedStatus {
+        unsaved,
+        draft_up_to_date,
+        draft_saved_at: draft.map(|draft| draft.updated_at),
+    })
+}
@@ -1,7 +1,10 @@
 use crate::chat::twitch::auth::{ImplicitGrantFlow, UserInformation, UserSkippedInformation};
 use crate::chat::youtube::state_manager::get_all_videos;
 use crate::misc::editor::get_theme::get_themes;
-use crate::misc::editor::save_theme::ThemeState;
+use crate::misc::editor::overlay_windows::{follow_chat_server, register_overlay_shortcut};
+use crate::misc::editor::theme_package::themes_dir;
+use crate::misc::editor::theme_registry::ThemeRegistry;
+use crate::misc::editor::theme_watcher::start_theme_watcher;
 use keyring::Entry;
 use serde_json::json;
 use sled::Db;
@@ -129,12 +132,17 @@ async fn backend_setup(app: AppHandle) {
         }
     };
 
-    let themes = get_themes(app_clone.clone()).await.unwrap();
-    let to_manage = Mutex::new(ThemeState {
-        themes,
-    });
+    // Creates the themes folder with the built-in themes on the first launch
+    get_themes(app_clone.clone()).await.unwrap();
+    let themes_dir = themes_dir().unwrap();
     // Manage the theme state
-    app_clone.manage(to_manage);
+    app_clone.manage(Mutex::new(ThemeRegistry::load(&themes_dir)));
+    if let Err(e) = start_theme_watcher(app_clone.clone(), themes_dir) {
+        eprintln!("Themes edited outside the app won't be reloaded: {}", e);
+    }
+    register_overlay_shortcut(&app_clone);
+    // The overlay windows left open last time come back with the chat server
+    follow_chat_server(&app_clone);
 
     get_all_videos(app_clone.clone(), Option::from(true), None).await.unwrap();
 }
@@ -13,6 +13,7 @@ import {Button} from "@/components/ui/button";
 import {cn} from "@/lib/utils";
 import {handleCopy} from "@/components/component/Main/Helpers/webChatUtils";
 import TauriApi from "@/lib/Tauri";
+import {toast} from "@/hooks/use-toast";
 import React, {useState} from "react";
 
 export type AlertsProps = {
@@ -120,6 +121,12 @@ export default function Alerts(
 								if (result) {
 									setNewTheme(true)
 								}
+							}).catch((errors) => {
+								toast({
+									title: "The theme wasn't saved",
+									description: `${errors}`,
+									variant: "destructive",
+								})
 							})
 						}}>
 							Save
@@ -18,6 +18,12 @@ export default function AvailableThemes({format, selectedTheme, setCurrentTheme,
 
 	useEffect(() => {
 		GetThemes().then(setThemes);
+
+		// Themes added or removed in the themes folder outside the app
+		const unsubscribe = TauriApi.ListenEvent("editor::theme_changed", () => GetThemes().then(setThemes));
+		return () => {
+			unsubscribe.then((unsub) => unsub());
+		}
 	}, []);
 
 	useEffect(() => {
@@ -1,18 +1,51 @@
-import React, {useEffect, useState} from "react"
+import React, {useEffect, useMemo, useRef, useState} from "react"
 import {Tabs, TabsContent, TabsList, TabsTrigger} from "@/components/ui/tabs"
 import {DropdownMenu, DropdownMenuTrigger} from "@/components/ui/dropdown-menu"
 import {Button} from "@/components/ui/button"
-import {PaintBucket, Pause, Play, Save} from "lucide-react"
+import {MessagesSquare, PaintBucket, Pause, Play, Save} from "lucide-react"
 import AvailableThemes from "@/components/component/Editor/AvailableThemes"
 import ConfigDropdown from "@/components/component/Editor/ConfigDropdown"
-import CodeMirror from "@uiw/react-codemirror"
+import CodeMirror, {EditorView} from "@uiw/react-codemirror"
 import {html} from "@codemirror/lang-html"
 import {dracula} from "@uiw/codemirror-theme-dracula"
 import {css} from "@codemirror/lang-css"
+import {Diagnostic as EditorDiagnostic, linter} from "@codemirror/lint"
 import TauriApi from "@/lib/Tauri"
 import {handleConfigChange, handleWebChatWindow} from "@/components/component/Main/Helpers/webChatUtils"
 import Alerts from "@/components/component/Editor/Alerts";
 
+// Places the app's diagnostics for a file in its editor, lines and columns start at 1
+// Lint columns count characters, CodeMirror positions count UTF-16 code units (emoji take two)
+function utf16Offset(text: string, column: number): number {
+	let offset = 0
+	let characters = 0
+	for (const character of text) {
+		if (characters >= column - 1) {
+			break
+		}
+		offset += character.length
+		characters++
+	}
+	return offset
+}
+
+function toEditorDiagnostics(view: EditorView, diagnostics: Diagnostic[], file: string): EditorDiagnostic[] {
+	const doc = view.state.doc
+	return diagnostics
+		.filter((diagnostic) => diagnostic.file === file)
+		.map((diagnostic) => {
+			const line = doc.line(Math.min(Math.max(diagnostic.line, 1), doc.lines))
+			const from = line.from + utf16Offset(line.text, diagnostic.column)
+			const next = line.text.codePointAt(from - line.from)
+			return {
+				from,
+				to: Math.min(from + (next !== undefined && next > 0xffff ? 2 : 1), line.to),
+				severity: diagnostic.severity,
+				message: diagnostic.message,
+			}
+		})
+}
+
 export type EditorHeaderProps = {
 	htmlCode: string;
 	setHtmlCode: React.Dispatch<React.SetStateAction<string>>;
@@ -52,6 +85,24 @@ export default function EditorHeader(
 
 	const [theme, setTheme] = useState<string>("default")
 	const [newTheme, setNewTheme] = useState<boolean>(false)
+	const [mockChatRunning, setMockChatRunning] = useState<boolean>(false)
+
+	// The linters read these, so they don't have to be created again on every change
+	const themeRef = useRef<string>(theme)
+	const htmlRef = useRef<string>(htmlCode)
+	const cssRef = useRef<string>(cssCode)
+	themeRef.current = theme
+	htmlRef.current = htmlCode
+	cssRef.current = cssCode
+
+	const htmlLinter = useMemo(() => linter(async (view) => {
+		const diagnostics = await TauriApi.LintTheme(themeRef.current, view.state.doc.toString(), cssRef.current).catch(() => [])
+		return toEditorDiagnostics(view, diagnostics, "index.html")
+	}), [])
+	const cssLinter = useMemo(() => linter(async (view) => {
+		const diagnostics = await TauriApi.LintTheme(themeRef.current, htmlRef.current, view.state.doc.toString()).catch(() => [])
+		return toEditorDiagnostics(view, diagnostics, "style.css")
+	}), [])
 
 	useEffect(() => {
 		const theme = localStorage.getItem("chatTheme")
@@ -69,6 +120,29 @@ export default function EditorHeader(
 		})
 	}, [theme])
 
+	useEffect(() => {
+		TauriApi.GetMockChat().then((options) => setMockChatRunning(options !== null))
+		const unsubscribe = TauriApi.ListenEvent("chat::mock_chat_stopped", () => setMockChatRunning(false))
+		return () => {
+			unsubscribe.then((unsub) => unsub())
+		}
+	}, [])
+
+	// The mock chat goes through the chat server, it stops with it
+	useEffect(() => {
+		if (!startWebsocket && mockChatRunning) {
+			TauriApi.StopMockChat().then(() => setMockChatRunning(false))
+		}
+	}, [startWebsocket])
+
+	const toggleMockChat = () => {
+		if (mockChatRunning) {
+			TauriApi.StopMockChat().then(() => setMockChatRunning(false))
+		} else {
+			TauriApi.StartMockChat().then(() => setMockChatRunning(true)).catch(console.error)
+		}
+	}
+
 	return (
 		<>
 			<Alerts
@@ -154,6 +228,17 @@ export default function EditorHeader(
 								</>
 							)}
 						</Button>
+						{startWebsocket && (
+							<Button
+								onClick={toggleMockChat}
+								size="sm"
+								variant={mockChatRunning ? "secondary" : "outline"}
+								className="transition-all duration-200 ease-in-out hover:bg-muted"
+							>
+								<MessagesSquare className="h-4 w-4 mr-1"/>
+								{mockChatRunning ? "Stop mock chat" : "Mock chat"}
+							</Button>
+						)}
 						<ConfigDropdown
 							config={config}
 							onConfigChange={(key, value) => handleConfigChange(key, value, setConfig)}
@@ -164,7 +249,7 @@ export default function EditorHeader(
 				             className={`flex-grow flex overflow-hidden p-0 h-screen ${editorSelected !== "html" ? "hidden" : ""}`}>
 					<CodeMirror
 						value={htmlCode}
-						extensions={[html()]}
+						extensions={[html(), htmlLinter]}
 						onChange={(value) => setHtmlCode(value)}
 						theme={dracula}
 						className="flex-grow h-full w-full"
@@ -175,7 +260,7 @@ export default function EditorHeader(
 				             className={`flex-grow flex overflow-hidden p-0 h-screen ${editorSelected !== "css" ? "hidden" : ""}`}>
 					<CodeMirror
 						value={cssCode}
-						extensions={[css()]}
+						extensions={[css(), cssLinter]}
 						onChange={(value) => setCssCode(value)}
 						theme={dracula}
 						className="flex-grow h-full w-full"
@@ -1,4 +1,4 @@
-import React, {useEffect, useState} from "react";
+import React, {useEffect, useRef, useState} from "react";
 import {getSeparatorStyle} from "@/components/component/Main/Helpers/MainFrame";
 import {
 	handleResize,
@@ -11,6 +11,11 @@ import {replacePlaceholders} from "@/components/component/Main/Helpers/webChatUt
 import PreviewHeader from "@/components/component/Editor/PreviewHeader";
 import EditorHeader from "@/components/component/Editor/EditorHeader";
 import randomMessageObject from "@/lib/mocks/editor_messages";
+import {Toaster} from "@/components/ui/toaster";
+import {toast} from "@/hooks/use-toast";
+
+// How often the editor's code is autosaved as the theme's draft
+const AUTOSAVE_INTERVAL = 10000;
 
 type EditorProps = {
 	setPreviewPosition: React.Dispatch<React.SetStateAction<PreviewPosition>>,
@@ -53,6 +58,12 @@ export default function Editor(
 
 
 	const [combinedCode, setCombinedCode] = useState<string>("");
+	const [editorLoaded, setEditorLoaded] = useState<boolean>(false);
+	const latestCode = useRef({htmlCode, cssCode});
+	// What's saved in the theme's files
+	const publishedCode = useRef({htmlCode: "", cssCode: ""});
+	// The theme's variables as CSS custom properties, the way the overlay gets them
+	const [variablesCss, setVariablesCss] = useState<string>("");
 	const [messages, setMessages] = useState<PlatformMessage<"twitch" | "youtube">[]>([]);
 
 	const [config, setConfig] = useState<ConfigState>({
@@ -114,6 +125,7 @@ export default function Editor(
         <head>
           <script src="/styles/tailwind_complete.css" data-tailwind="disable-warning"></script>
           <style>
+            ${variablesCss}
             ${cssCode};
             .message { display: flex; flex-direction: row; margin: 0; padding: 0; }
             .chat-container { display: flex; flex-direction: column; gap: 0; }
@@ -125,16 +137,64 @@ export default function Editor(
         </body>
       </html>
     `);
-	}, [htmlCode, cssCode, messages]);
+	}, [htmlCode, cssCode, variablesCss, messages]);
 
 	useEffect(() => {
 		const editorTheme = localStorage.getItem("chatTheme") || "default";
-		TauriApi.GetEditorTheme(editorTheme).then((theme) => {
+		TauriApi.GetEditorTheme(editorTheme).then(async (theme) => {
+			// Unpublished changes from the last session, or from before a crash, come first
+			const draft = await TauriApi.GetThemeDraft(editorTheme).catch(() => null);
+			publishedCode.current = {htmlCode: theme.html_code, cssCode: theme.css_code};
+			TauriApi.GetThemeVariables(editorTheme).then((variables) => setVariablesCss(variables.css)).catch(console.error);
+			setHtmlCode(draft?.html_code ?? theme.html_code);
+			setCssCode(draft?.css_code ?? theme.css_code);
+			setEditorLoaded(true);
+		});
+	}, [])
+
+	useEffect(() => {
+		// The theme was edited outside the app, its files replace the editor's content unless it has changes of its own
+		const unsubscribe = TauriApi.ListenEvent("editor::theme_changed", async (event) => {
+			const editorTheme = localStorage.getItem("chatTheme") || "default";
+			if (event.payload !== editorTheme) return;
+
+			TauriApi.GetThemeVariables(editorTheme).then((variables) => setVariablesCss(variables.css)).catch(console.error);
+			const theme = await TauriApi.GetEditorTheme(editorTheme);
+			const {htmlCode, cssCode} = latestCode.current;
+			const published = publishedCode.current;
+			publishedCode.current = {htmlCode: theme.html_code, cssCode: theme.css_code};
+			if (htmlCode !== published.htmlCode || cssCode !== published.cssCode) return;
+
 			setHtmlCode(theme.html_code);
 			setCssCode(theme.css_code);
 		});
+
+		return () => {
+			unsubscribe.then((unsub) => unsub());
+		}
 	}, [])
 
+	useEffect(() => {
+		latestCode.current = {htmlCode, cssCode};
+	}, [htmlCode, cssCode])
+
+	useEffect(() => {
+		if (!editorLoaded) return;
+
+		let lastSaved = "";
+		const autosave = setInterval(() => {
+			const {htmlCode, cssCode} = latestCode.current;
+			if (htmlCode + "\0" + cssCode === lastSaved) return;
+
+			const editorTheme = localStorage.getItem("chatTheme") || "default";
+			TauriApi.SaveThemeDraft(editorTheme, htmlCode, cssCode)
+				.then(() => lastSaved = htmlCode + "\0" + cssCode)
+				.catch(console.error);
+		}, AUTOSAVE_INTERVAL);
+
+		return () => clearInterval(autosave);
+	}, [editorLoaded])
+
 	useEffect(() => {
 		if (!startWebsocket) {
 			const messageInterval = setInterval(() => {
@@ -154,36 +214,26 @@ export default function Editor(
 				clearInterval(messageInterval);
 				clearInterval(cleanupInterval);
 			}
-		} else {
-			const startEverything = async () => {
-				const getYtStreams = await TauriApi.GetAllVideos();
-				if (getYtStreams.length > 0) {
-					// Get the first stream that has the live status
-					const liveStream = getYtStreams.find(stream => stream.stream_type === "live");
-
-					if (liveStream) {
-						try {
-							return await TauriApi.StartUnitedChat({yt_id: liveStream.video_id, interval: 2000});
-						} catch (e) {
-							console.error(e);
-						}
-					}
-				}
-
-				try {
-					await TauriApi.StartUnitedChat();
-					console.log("Everything should be started now");
-				} catch (e) {
-					console.error(e);
-				}
-			}
-
-			startEverything();
-
 		}
 
 	}, [startWebsocket]);
 
+	useEffect(() => {
+		// The server can still fail after it started, e.g. when it can't accept connections anymore
+		const unsubscribe = TauriApi.ListenEvent("chat::server_error", (event) => {
+			toast({
+				title: "The chat server stopped",
+				description: event.payload.error,
+				variant: "destructive",
+			});
+			TauriApi.DisconnectTwitchWebsocket().finally(() => setStartWebsocket(false));
+		});
+
+		return () => {
+			unsubscribe.then((unsub) => unsub());
+		}
+	}, []);
+
 	return (
 		<main className="flex-grow flex overflow-hidden">
 			<div ref={containerRef}
@@ -266,6 +316,7 @@ export default function Editor(
 					</>
 				)}
 			</div>
+			<Toaster/>
 		</main>
 	);
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 import TauriApi from "@/lib/Tauri";
 import React from "react";
 import moment from "moment";
+import {toast} from "@/hooks/use-toast";
 
 function formatPlatformBadge(platform: PlatformMessage<"twitch" | "youtube">["platform"]) {
 	switch (platform) {
@@ -95,7 +96,22 @@ function removeComments(html: string): string {
 }
 
 
-function handleWebChatWindow(
+/**
+ * Starts the chat, along with the YouTube chat of the first live stream if there's one.
+ * @returns The port the chat server is listening on.
+ */
+async function startUnitedChat(): Promise<number> {
+	const streams = await TauriApi.GetAllVideos();
+	const liveStream = streams.find(stream => stream.stream_type === "live");
+
+	if (liveStream) {
+		return await TauriApi.StartUnitedChat({yt_id: liveStream.video_id, interval: 2000});
+	}
+
+	return await TauriApi.StartUnitedChat();
+}
+
+async function handleWebChatWindow(
 	htmlCode: string,
 	cssCode: string,
 	config: ConfigState,
@@ -107,6 +123,18 @@ function handleWebChatWindow(
 	startWebsocket: boolean
 ) {
 	if (!startWebsocket) {
+		let wsPort: number;
+		try {
+			wsPort = await startUnitedChat();
+		} catch (e) {
+			toast({
+				title: "Couldn't start the chat",
+				description: String(e),
+				variant: "destructive",
+			});
+			return;
+		}
+
 		const cleanedCssCode = cssCode.replace(/\/\*[\s\S]*?\*\//g, ''); // Remove comments
 		const base64CssCode = btoa(cleanedCssCode); // Encode to Base64
 
@@ -138,6 +166,8 @@ function handleWebChatWindow(
 		configString += `&maxMessages=${maxMessages}&removalTimer=${messageRemoveTimer}`;
 		// Add the message transition
 		configString += `&messageTransition=${messageTransition}`;
+		// The server moves to the next port when the configured one is taken
+		configString += `&wsPort=${wsPort}`;
 
 		const url = `webchat?htmlTemplate=${encodeURIComponent(base64HtmlCode)}&css=${base64CssCode}&${configString}`;
 
@@ -147,7 +177,7 @@ function handleWebChatWindow(
 		setShowConfirmDialog(true);
 
 	} else {
-		TauriApi.DisconnectTwitchWebsocket();
+		await TauriApi.DisconnectTwitchWebsocket();
 	}
 
 	if (webChatWindowShown) {
@@ -12,7 +12,7 @@ export default class TauriApi {
 	public static async StartLinking(): Promise<string> {
 		return await this.command<string>("start_twitch_link", {
 			clientId: "h3yvglc6y3kmtrzyq7it20z7vi5sa2",
-			scopes: "user:read:chat+user:read:email"
+			scopes: "user:read:chat+user:read:email+chat:read+chat:edit+moderator:read:followers+channel:read:redemptions+channel:manage:redemptions+channel:read:hype_train+channel:read:polls+channel:read:predictions+channel:read:ads"
 		});
 	}
 
@@ -67,22 +67,22 @@ export default class TauriApi {
 	}
 
 	/**
-	 * Connects to the Twitch WebSocket by invoking the `connect_twitch_websocket` command.
-	 * @returns {Promise<void>} A promise that resolves when the connection is established.
+	 * Starts the chat by invoking the `united_chat_init` command.
+	 * @returns {Promise<number>} A promise that resolves to the port the chat server is listening on.
 	 */
 	public static async StartUnitedChat(
 		youtube?: {
 			yt_id?: string,
 			interval?: number,
 		}
-	): Promise<void> {
+	): Promise<number> {
 		if (youtube) {
-			return await this.command<void>("united_chat_init", {
+			return await this.command<number>("united_chat_init", {
 				youtube
 			})
 		}
 
-		return await this.command<void>("united_chat_init", {});
+		return await this.command<number>("united_chat_init", {});
 	}
 
 	/**
@@ -93,6 +93,31 @@ export default class TauriApi {
 		return await this.command<void>("united_chat_stop", {});
 	}
 
+	/**
+	 * Sends made up chat events to the overlays by invoking the `start_mock_chat` command, the chat has to be started.
+	 * @param {Partial<MockChatOptions>} [options] - Rate, platforms and kinds of events, the defaults are used for the rest.
+	 * @returns {Promise<void>} A promise that resolves when the mock chat is started, replacing one already running.
+	 */
+	public static async StartMockChat(options?: Partial<MockChatOptions>) {
+		return await this.command<void>("start_mock_chat", {options});
+	}
+
+	/**
+	 * Stops the mock chat by invoking the `stop_mock_chat` command.
+	 * @returns {Promise<boolean>} A promise that resolves to whether a mock chat was running.
+	 */
+	public static async StopMockChat() {
+		return await this.command<boolean>("stop_mock_chat", {});
+	}
+
+	/**
+	 * Retrieves the options of the running mock chat by invoking the `get_mock_chat` command.
+	 * @returns {Promise<MockChatOptions | null>} A promise that resolves to its options, or null if it isn't running.
+	 */
+	public static async GetMockChat() {
+		return await this.command<MockChatOptions | null>("get_mock_chat", {});
+	}
+
 	/**
 	 * Opens a web chat window by invoking the `open_webchat_window` command.
 	 * @param {string} url - The URL of the web chat.
@@ -110,6 +135,95 @@ export default class TauriApi {
 		return await this.command<void>("hide_webchat_window", {});
 	}
 
+	/**
+	 * Retrieves the URL to add a theme as an OBS browser source by invoking the `get_overlay_url` command.
+	 * @param {string} theme - The name of the theme to show.
+	 * @param {Record<string, string>} [options] - The webchat options, added to the URL's query string.
+	 * @returns {Promise<string>} A promise that resolves to the overlay URL.
+	 */
+	public static async GetOverlayUrl(theme: string, options?: Record<string, string>): Promise<string> {
+		return await this.command<string>("get_overlay_url", {theme, options});
+	}
+
+	/**
+	 * Lists the overlay windows by invoking the `list_overlay_windows` command.
+	 * @returns {Promise<OverlayWindow[]>} A promise that resolves to every overlay window, open or not.
+	 */
+	public static async ListOverlayWindows() {
+		return await this.command<OverlayWindow[]>("list_overlay_windows", {});
+	}
+
+	/**
+	 * Creates an overlay window by invoking the `create_overlay_window` command, it opens with the chat.
+	 * @param {OverlaySettings} settings - Its name, theme, filters, page options, size and position.
+	 * @returns {Promise<OverlayWindow>} A promise that resolves to the new overlay window.
+	 */
+	public static async CreateOverlayWindow(settings: OverlaySettings) {
+		return await this.command<OverlayWindow>("create_overlay_window", {settings});
+	}
+
+	/**
+	 * Changes an overlay window by invoking the `update_overlay_window` command, the window follows if it's open.
+	 * @param {number} id - The id of the overlay window.
+	 * @param {OverlaySettings} settings - Its new settings.
+	 * @returns {Promise<OverlayWindow>} A promise that resolves to the updated overlay window.
+	 */
+	public static async UpdateOverlayWindow(id: number, settings: OverlaySettings) {
+		return await this.command<OverlayWindow>("update_overlay_window", {id, settings});
+	}
+
+	/**
+	 * Opens an overlay window by invoking the `open_overlay_window` command, right away if the chat is running.
+	 * @param {number} id - The id of the overlay window.
+	 * @returns {Promise<OverlayWindow>} A promise that resolves to the overlay window.
+	 */
+	public static async OpenOverlayWindow(id: number) {
+		return await this.command<OverlayWindow>("open_overlay_window", {id});
+	}
+
+	/**
+	 * Closes an overlay window by invoking the `close_overlay_window` command, it stays closed on the next launch.
+	 * @param {number} id - The id of the overlay window.
+	 * @returns {Promise<OverlayWindow>} A promise that resolves to the overlay window.
+	 */
+	public static async CloseOverlayWindow(id: number) {
+		return await this.command<OverlayWindow>("close_overlay_window", {id});
+	}
+
+	/**
+	 * Makes the click-through overlay windows take clicks, or lets clicks through them again, by invoking the `toggle_overlay_interaction` command.
+	 * @returns {Promise<boolean>} A promise that resolves to whether they're interactive now.
+	 */
+	public static async ToggleOverlayInteraction() {
+		return await this.command<boolean>("toggle_overlay_interaction", {});
+	}
+
+	/**
+	 * Retrieves the global shortcut toggling the overlays' interaction by invoking the `get_overlay_shortcut` command.
+	 * @returns {Promise<string | null>} A promise that resolves to the shortcut, e.g. "CommandOrControl+Shift+O", or null if it's turned off.
+	 */
+	public static async GetOverlayShortcut() {
+		return await this.command<string | null>("get_overlay_shortcut", {});
+	}
+
+	/**
+	 * Changes the global shortcut toggling the overlays' interaction by invoking the `set_overlay_shortcut` command.
+	 * @param {string | null} shortcut - The new shortcut, e.g. "Alt+F9", null turns it off.
+	 * @returns {Promise<void>} A promise that resolves when the shortcut is registered, rejected if it's taken or invalid.
+	 */
+	public static async SetOverlayShortcut(shortcut: string | null) {
+		return await this.command<void>("set_overlay_shortcut", {shortcut});
+	}
+
+	/**
+	 * Deletes an overlay window by invoking the `delete_overlay_window` command.
+	 * @param {number} id - The id of the overlay window.
+	 * @returns {Promise<void>} A promise that resolves when the overlay window is deleted.
+	 */
+	public static async DeleteOverlayWindow(id: number) {
+		return await this.command<void>("delete_overlay_window", {id});
+	}
+
 	/**
 	 * Retrieves user information by invoking the `get_user` command.
 	 * @returns {Promise<UserInformation | null>} A promise that resolves to the user information or null if an error occurs.
@@ -149,17 +263,324 @@ export default class TauriApi {
 		return await this.command<AvailableThemes>("get_themes", {});
 	}
 
+	/**
+	 * Lists the themes with their metadata by invoking the `list_themes` command.
+	 * @returns {Promise<ThemeMetadata[]>} A promise that resolves to the themes, built-in ones first.
+	 */
+	public static async ListThemes() {
+		return await this.command<ThemeMetadata[]>("list_themes", {});
+	}
+
+	/**
+	 * Renames a theme by invoking the `rename_theme` command, its history and draft follow it.
+	 * @param {string} name - The name of the theme.
+	 * @param {string} newName - The new name, it can't be taken.
+	 * @returns {Promise<ThemeMetadata>} A promise that resolves to the renamed theme.
+	 */
+	public static async RenameTheme(name: string, newName: string) {
+		return await this.command<ThemeMetadata>("rename_theme", {name, newName});
+	}
+
+	/**
+	 * Copies a theme by invoking the `duplicate_theme` command.
+	 * @param {string} name - The name of the theme.
+	 * @param {string} [newName] - The name of the copy, "<name> copy" when left out.
+	 * @returns {Promise<ThemeMetadata>} A promise that resolves to the copy.
+	 */
+	public static async DuplicateTheme(name: string, newName?: string) {
+		return await this.command<ThemeMetadata>("duplicate_theme", {name, newName: newName ?? null});
+	}
+
+	/**
+	 * Deletes a theme with its history and draft by invoking the `delete_theme` command. Built-in themes can't be deleted.
+	 * @param {string} name - The name of the theme.
+	 * @returns {Promise<void>} A promise that resolves when the theme is deleted.
+	 */
+	public static async DeleteTheme(name: string) {
+		return await this.command<void>("delete_theme", {name});
+	}
+
+	/**
+	 * Retrieves the active theme by invoking the `get_active_theme` command.
+	 * @returns {Promise<string>} A promise that resolves to the active theme's name.
+	 */
+	public static async GetActiveTheme() {
+		return await this.command<string>("get_active_theme", {});
+	}
+
+	/**
+	 * Sets the active theme by invoking the `set_active_theme` command.
+	 * @param {string} name - The name of the theme.
+	 * @returns {Promise<void>} A promise that resolves when the theme is active.
+	 */
+	public static async SetActiveTheme(name: string) {
+		return await this.command<void>("set_active_theme", {name});
+	}
+
+	/**
+	 * Updates what's shown about a theme by invoking the `update_theme_metadata` command.
+	 * Fields left out stay as they are, empty ones are cleared.
+	 * @param {string} name - The name of the theme.
+	 * @param {{displayName?: string, author?: string, description?: string}} metadata - The fields to update.
+	 * @returns {Promise<ThemeMetadata>} A promise that resolves to the updated theme.
+	 */
+	public static async UpdateThemeMetadata(
+		name: string,
+		metadata: { displayName?: string, author?: string, description?: string }
+	) {
+		return await this.command<ThemeMetadata>("update_theme_metadata", {
+			name,
+			displayName: metadata.displayName ?? null,
+			author: metadata.author ?? null,
+			description: metadata.description ?? null
+		});
+	}
+
+	/**
+	 * Sets a theme's preview thumbnail from an image by invoking the `set_theme_preview` command.
+	 * @param {string} name - The name of the theme.
+	 * @param {string} source - The path of the image.
+	 * @returns {Promise<ThemeMetadata>} A promise that resolves to the updated theme.
+	 */
+	public static async SetThemePreview(name: string, source: string) {
+		return await this.command<ThemeMetadata>("set_theme_preview", {name, source});
+	}
+
+	/**
+	 * Retrieves a theme's variables and their values by invoking the `get_theme_variables` command.
+	 * @param {string} theme - The name of the theme.
+	 * @returns {Promise<ThemeVariables>} A promise that resolves to the variables, their values and the resulting CSS.
+	 */
+	public static async GetThemeVariables(theme: string) {
+		return await this.command<ThemeVariables>("get_theme_variables", {theme});
+	}
+
+	/**
+	 * Sets some of a theme's variables by invoking the `set_theme_variables` command, the others keep their value.
+	 * @param {string} theme - The name of the theme.
+	 * @param {Record<string, string | number | boolean>} values - The new values, by variable name.
+	 * @returns {Promise<ThemeVariables>} A promise that resolves to the updated variables.
+	 */
+	public static async SetThemeVariables(theme: string, values: Record<string, string | number | boolean>) {
+		return await this.command<ThemeVariables>("set_theme_variables", {theme, values});
+	}
+
+	/**
+	 * Puts a theme's variables back to their defaults by invoking the `reset_theme_variables` command.
+	 * @param {string} theme - The name of the theme.
+	 * @returns {Promise<ThemeVariables>} A promise that resolves to the default variables.
+	 */
+	public static async ResetThemeVariables(theme: string) {
+		return await this.command<ThemeVariables>("reset_theme_variables", {theme});
+	}
+
+	/**
+	 * Lists the kinds of templates a theme can have by invoking the `list_theme_templates` command.
+	 * @param {string} theme - The name of the theme.
+	 * @returns {Promise<EventTemplate[]>} A promise that resolves to the templates, without code for the ones using index.html.
+	 */
+	public static async ListThemeTemplates(theme: string) {
+		return await this.command<EventTemplate[]>("list_theme_templates", {theme});
+	}
+
+	/**
+	 * Saves a theme's template for an event by invoking the `save_theme_template` command.
+	 * @param {string} theme - The name of the theme.
+	 * @param {EventTemplate["kind"]} kind - The kind of template, messages are saved with the theme instead.
+	 * @param {string} htmlCode - The HTML code of the template.
+	 * @returns {Promise<void>} A promise that resolves when the template is saved.
+	 */
+	public static async SaveThemeTemplate(theme: string, kind: EventTemplate["kind"], htmlCode: string) {
+		return await this.command<void>("save_theme_template", {theme, kind, htmlCode});
+	}
+
+	/**
+	 * Removes a theme's template by invoking the `remove_theme_template` command, its events use index.html again.
+	 * @param {string} theme - The name of the theme.
+	 * @param {EventTemplate["kind"]} kind - The kind of template.
+	 * @returns {Promise<void>} A promise that resolves when the template is removed.
+	 */
+	public static async RemoveThemeTemplate(theme: string, kind: EventTemplate["kind"]) {
+		return await this.command<void>("remove_theme_template", {theme, kind});
+	}
+
+	/**
+	 * Lints the editor's content by invoking the `lint_theme` command.
+	 * @param {string | null} theme - The name of the theme, its saved templates and assets are checked too.
+	 * @param {string} htmlCode - The HTML code in the editor.
+	 * @param {string} cssCode - The CSS code in the editor.
+	 * @returns {Promise<Diagnostic[]>} A promise that resolves to the problems found, errors keep the theme from being saved.
+	 */
+	public static async LintTheme(theme: string | null, htmlCode: string, cssCode: string) {
+		return await this.command<Diagnostic[]>("lint_theme", {theme, htmlCode, cssCode});
+	}
+
 	/**
 	 * Saves a theme by invoking the `save_theme` command.
 	 * @param {string} themeName - The name of the theme to save.
 	 * @param {string} htmlCode - The HTML code of the theme.
 	 * @param {string} cssCode - The CSS code of the theme.
-	 * @returns {Promise<boolean>} A promise that resolves to a boolean indicating success or failure.
+	 * @returns {Promise<boolean>} A promise that resolves to a boolean indicating success or failure, rejected with the lint errors if there are any.
 	 */
 	public static async SaveTheme(themeName: string, htmlCode: string, cssCode: string) {
 		return await this.command<boolean>("save_theme", {themeName, htmlCode, cssCode});
 	}
 
+	/**
+	 * Autosaves the editor's content as a theme's draft by invoking the `save_theme_draft` command.
+	 * @param {string} theme - The name of the theme.
+	 * @param {string} htmlCode - The HTML code in the editor.
+	 * @param {string} cssCode - The CSS code in the editor.
+	 * @returns {Promise<ThemeDraft | null>} A promise that resolves to the draft, or null when the content is the published one.
+	 */
+	public static async SaveThemeDraft(theme: string, htmlCode: string, cssCode: string) {
+		return await this.command<ThemeDraft | null>("save_theme_draft", {theme, htmlCode, cssCode});
+	}
+
+	/**
+	 * Retrieves a theme's draft by invoking the `get_theme_draft` command.
+	 * @param {string} theme - The name of the theme.
+	 * @returns {Promise<ThemeDraft | null>} A promise that resolves to the draft, or null if the theme has none.
+	 */
+	public static async GetThemeDraft(theme: string) {
+		return await this.command<ThemeDraft | null>("get_theme_draft", {theme});
+	}
+
+	/**
+	 * Lists every theme draft, most recent first, by invoking the `list_theme_drafts` command.
+	 * @returns {Promise<ThemeDraft[]>} A promise that resolves to the drafts.
+	 */
+	public static async ListThemeDrafts() {
+		return await this.command<ThemeDraft[]>("list_theme_drafts", {});
+	}
+
+	/**
+	 * Drops a theme's draft by invoking the `discard_theme_draft` command.
+	 * @param {string} theme - The name of the theme.
+	 * @returns {Promise<void>} A promise that resolves when the draft is removed.
+	 */
+	public static async DiscardThemeDraft(theme: string) {
+		return await this.command<void>("discard_theme_draft", {theme});
+	}
+
+	/**
+	 * Saves a theme's draft as its files by invoking the `publish_theme_draft` command.
+	 * @param {string} theme - The name of the theme.
+	 * @returns {Promise<void>} A promise that resolves when the draft is published.
+	 */
+	public static async PublishThemeDraft(theme: string) {
+		return await this.command<void>("publish_theme_draft", {theme});
+	}
+
+	/**
+	 * Lists the saved revisions of a theme, newest first, by invoking the `list_theme_revisions` command.
+	 * @param {string} theme - The name of the theme.
+	 * @returns {Promise<RevisionSummary[]>} A promise that resolves to the theme's revisions.
+	 */
+	public static async ListThemeRevisions(theme: string) {
+		return await this.command<RevisionSummary[]>("list_theme_revisions", {theme});
+	}
+
+	/**
+	 * Retrieves a revision of a theme, with its code, by invoking the `get_theme_revision` command.
+	 * @param {string} theme - The name of the theme.
+	 * @param {number} id - The id of the revision.
+	 * @returns {Promise<ThemeRevision>} A promise that resolves to the revision.
+	 */
+	public static async GetThemeRevision(theme: string, id: number) {
+		return await this.command<ThemeRevision>("get_theme_revision", {theme, id});
+	}
+
+	/**
+	 * Compares two revisions of a theme by invoking the `diff_theme_revisions` command.
+	 * @param {string} theme - The name of the theme.
+	 * @param {number} from - The id of the older revision.
+	 * @param {number} [to] - The id of the newer revision, defaults to the latest one.
+	 * @returns {Promise<{html: string, css: string}>} A promise that resolves to the unified diffs of both files.
+	 */
+	public static async DiffThemeRevisions(theme: string, from: number, to?: number) {
+		return await this.command<{ html: string, css: string }>("diff_theme_revisions", {theme, from, to});
+	}
+
+	/**
+	 * Restores a revision of a theme by invoking the `restore_theme_revision` command.
+	 * @param {string} theme - The name of the theme.
+	 * @param {number} id - The id of the revision to restore.
+	 * @returns {Promise<ThemeRevision>} A promise that resolves to the new revision the restore created.
+	 */
+	public static async RestoreThemeRevision(theme: string, id: number) {
+		return await this.command<ThemeRevision>("restore_theme_revision", {theme, id});
+	}
+
+	/**
+	 * Renders a message with a theme's HTML by invoking the `render_theme_preview` command.
+	 * @param {string} htmlCode - The HTML code of the theme.
+	 * @param {"twitch" | "youtube"} platform - The platform the message comes from.
+	 * @param {Message["message"]} message - The message to render.
+	 * @returns {Promise<string>} A promise that resolves to the rendered HTML, rejected when the theme doesn't parse.
+	 */
+	public static async RenderThemePreview(htmlCode: string, platform: "twitch" | "youtube", message: Message["message"]) {
+		return await this.command<string>("render_theme_preview", {htmlCode, platform, message});
+	}
+
+	/**
+	 * Packages a theme into a single archive by invoking the `export_theme` command.
+	 * @param {string} theme - The name of the theme to export.
+	 * @param {string} path - Where to write the archive.
+	 * @returns {Promise<ThemeManifest>} A promise that resolves to the manifest written in the archive.
+	 */
+	public static async ExportTheme(theme: string, path: string) {
+		return await this.command<ThemeManifest>("export_theme", {theme, path});
+	}
+
+	/**
+	 * Installs a theme archive by invoking the `import_theme` command.
+	 * @param {string} path - The archive to import.
+	 * @param {"fail" | "overwrite" | "rename"} [conflict="fail"] - What to do when a theme with the same name exists.
+	 * @returns {Promise<string>} A promise that resolves to the name the theme was installed as.
+	 */
+	public static async ImportTheme(path: string, conflict: "fail" | "overwrite" | "rename" = "fail") {
+		return await this.command<string>("import_theme", {path, conflict});
+	}
+
+	/**
+	 * Lists the fonts, images and sounds of a theme by invoking the `list_theme_assets` command.
+	 * @param {string} theme - The name of the theme.
+	 * @returns {Promise<ThemeAsset[]>} A promise that resolves to the theme's assets.
+	 */
+	public static async ListThemeAssets(theme: string) {
+		return await this.command<ThemeAsset[]>("list_theme_assets", {theme});
+	}
+
+	/**
+	 * Copies a file into a theme's assets by invoking the `add_theme_asset` command.
+	 * @param {string} theme - The name of the theme.
+	 * @param {string} source - The file to add.
+	 * @param {string} [name] - The name to give it, defaults to the file's name.
+	 * @returns {Promise<ThemeAsset>} A promise that resolves to the added asset.
+	 */
+	public static async AddThemeAsset(theme: string, source: string, name?: string) {
+		return await this.command<ThemeAsset>("add_theme_asset", {theme, source, name});
+	}
+
+	/**
+	 * Removes an asset from a theme by invoking the `remove_theme_asset` command.
+	 * @param {string} theme - The name of the theme.
+	 * @param {string} name - The name of the asset.
+	 * @returns {Promise<void>} A promise that resolves when the asset is removed.
+	 */
+	public static async RemoveThemeAsset(theme: string, name: string) {
+		return await this.command<void>("remove_theme_asset", {theme, name});
+	}
+
+	/**
+	 * Retrieves the placeholders themes can use by invoking the `get_theme_placeholders` command.
+	 * @returns {Promise<[string, string][]>} A promise that resolves to the placeholders and their descriptions.
+	 */
+	public static async GetThemePlaceholders() {
+		return await this.command<[string, string][]>("get_theme_placeholders", {});
+	}
+
 	/**
 	 * Event Handling
 	 */
@@ -168,12 +589,13 @@ export default class TauriApi {
 	 * Listens for an event by invoking the `listen` function from the Tauri API.
 	 * @param {string} event - The name of the event to listen for.
 	 * @param {(event: any) => void} callback - The callback function to execute when the event occurs.
-	 * @returns {Promise<void>} A promise that resolves when the event listener is set up.
+	 * @returns {Promise<() => void>} A promise that resolves to a function removing this listener, once it's set up.
 	 */
 	public static async ListenEvent(event: string, callback: (event: any) => void) {
 		const {listen} = await import('@tauri-apps/api/event');
 		const unsub = await listen(event, callback);
 		this.eventSubscriptions.set(event, unsub);
+		return unsub;
 	}
 
 	/**
@@ -206,7 +628,7 @@ export default class TauriApi {
 		currentThemeHtml: string,
 		currentThemeCss: string
 	) {
-		return await this.command<boolean>("check_if_unsaved", {
+		return await this.command<UnsavedStatus>("check_if_unsaved", {
 			currentThemeName,
 			currentThemeHtml,
 			currentThemeCss
@@ -55,7 +55,8 @@ export default function UnitedChat() {
 						e.preventDefault();
 						const theme = localStorage.getItem('chatTheme') || 'default';
 						TauriApi.CheckThemeBeforeReload(theme, htmlCode, cssCode).then((result) => {
-							if (result) {
+							// Changes that made it into the draft come back after the reload
+							if (result.unsaved && !result.draft_up_to_date) {
 								return setTriggerReloadAlert(true);
 							}
 
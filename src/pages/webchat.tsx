@@ -17,7 +17,8 @@ export default function WebChat() {
 		scaling,
 		scalingValue,
 		messageTransition,
-		isDebug
+		isDebug,
+		wsPort
 	} = router.query;
 
 	const decodedHtmlTemplate = htmlTemplate ? atob(decodeURIComponent(htmlTemplate as string)) : '';
@@ -120,14 +121,36 @@ export default function WebChat() {
 	}, [messages, messagesLimit]);
 
 	useEffect(() => {
-		const ws = new WebSocket('ws://localhost:9888');
+		// The chat server may fall back to another port when 9888 is taken
+		const ws = new WebSocket(`ws://localhost:${wsPort ?? 9888}`);
 
 		ws.onopen = () => {
 			console.log('WebSocket connection established');
+			// Only chat messages are shown here, along with their removals
+			ws.send(JSON.stringify({type: 'subscribe', events: ['message', 'message_deleted', 'messages_cleared']}));
 		};
 
 		ws.onmessage = (event) => {
 			const data = JSON.parse(event.data);
+			// Hello, pong and other protocol messages
+			if (data.type && data.type !== "event") return;
+			if (data.event === "message_deleted") {
+				setMessages(prevMessages => prevMessages.filter(msg => msg.message.id !== data.data.id));
+				return;
+			}
+			if (data.event === "messages_cleared") {
+				const userId = data.data.user_id;
+				setMessages(prevMessages => prevMessages.filter(msg => {
+					if (msg.platform !== data.platform) return true;
+					if (!userId) return false;
+					const tags: [string, string][] = (msg.message as any).tags ?? [];
+					return !tags.some(([name, value]) => name === "user-id" && value === userId);
+				}));
+				return;
+			}
+			// Room state and other events are not chat messages
+			if (data.event && data.event !== "message") return;
+
 			const newMessage: Message = {
 				platform: data.platform,
 				message: {
@@ -151,7 +174,7 @@ export default function WebChat() {
 		return () => {
 			ws.close();
 		};
-	}, [decodedHtmlTemplate]);
+	}, [decodedHtmlTemplate, wsPort]);
 
 	return (
 		<div
@@ -38,6 +38,7 @@ declare global {
 		message_emotes: [string, string][],
 		timestamp: string,
 		tracking_params: string,
+		amount?: string,
 	}
 
 	type TwitchMessage = {
@@ -72,6 +73,133 @@ declare global {
 
 	type AvailableThemes = [string, string, string][];
 
+	type ThemeManifest = {
+		name: string
+		author: string | null
+		version: string
+		description: string | null
+		preview: string | null
+		placeholders: string[]
+		assets: string[]
+	}
+
+	type ThemeDraft = {
+		theme: string
+		html_code: string
+		css_code: string
+		updated_at: number
+	}
+
+	type ThemeMetadata = {
+		name: string
+		display_name: string
+		author: string | null
+		description: string | null
+		version: string
+		created_at: number
+		modified_at: number
+		built_in: boolean
+		preview: string | null
+		html_path: string
+		css_path: string
+	}
+
+	type ThemeVariable = {
+		name: string
+		label: string | null
+		description: string | null
+	} & (
+		| { type: "color", default: string }
+		| { type: "number", default: number, min: number | null, max: number | null, step: number | null, unit: string | null }
+		| { type: "font", default: string }
+		| { type: "boolean", default: boolean, on: string, off: string }
+		| { type: "enum", default: string, options: string[] }
+	)
+
+	type ThemeVariables = {
+		variables: ThemeVariable[]
+		values: Record<string, string | number | boolean>
+		css: string
+	}
+
+	type EventTemplate = {
+		kind: "message" | "action" | "highlight" | "subscription" | "raid" | "super_chat" | "system_notice"
+		description: string
+		html_code: string | null
+	}
+
+	type OverlaySettings = {
+		name: string
+		theme: string
+		/** All of them when empty */
+		platforms?: ("twitch" | "youtube")[]
+		channels?: string[]
+		/** Options of the overlay page, e.g. maxMessages or fadeOut */
+		options?: Record<string, string>
+		width?: number
+		height?: number
+		/** Centered when there's none */
+		x?: number | null
+		y?: number | null
+		always_on_top?: boolean
+		/** Clicks go through it, the overlay shortcut makes it interactive again */
+		click_through?: boolean
+		/** Of the chat, from 0.1 to 1 */
+		opacity?: number
+	}
+
+	type OverlayWindow = Required<OverlaySettings> & {
+		id: number
+		/** Opened again with the chat */
+		open: boolean
+	}
+
+	type MockChatOptions = {
+		/** Events per second, up to 1000 */
+		rate: number
+		platforms: ("twitch" | "youtube")[]
+		/** All of them when empty */
+		events: ("message" | "long_message" | "emotes" | "rtl" | "action" | "highlight" | "subscription" | "raid" | "super_chat" | "deletion")[]
+		limit: number | null
+		seed: number | null
+	}
+
+	type Diagnostic = {
+		severity: "error" | "warning"
+		message: string
+		file: string
+		line: number
+		column: number
+	}
+
+	type UnsavedStatus = {
+		unsaved: boolean
+		draft_up_to_date: boolean
+		draft_saved_at: number | null
+	}
+
+	type RevisionSummary = {
+		id: number
+		saved_at: number
+		html_size: number
+		css_size: number
+	}
+
+	type ThemeRevision = {
+		id: number
+		theme: string
+		html_code: string
+		css_code: string
+		saved_at: number
+	}
+
+	type ThemeAsset = {
+		name: string
+		size: number
+		kind: "image" | "font" | "sound"
+		url: string
+	}
+
 	type Video = {
 		is_replay: boolean | null
 		api_key: string